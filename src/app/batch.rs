@@ -0,0 +1,100 @@
+use std::collections::HashSet;
+
+use yew::prelude::*;
+
+use super::recent;
+use crate::logic::{batch::evaluate_batch, Program};
+
+#[derive(Properties, PartialEq)]
+pub struct ShoppingListPopupProps {
+    /// The document whose recipes the saved documents are evaluated with.
+    pub text: AttrValue,
+    pub onclose: Callback<()>,
+}
+
+/// A popup that adds up what several saved documents are missing, using the recipes of the
+/// current document, such as the personal need lists for a shared project.
+#[function_component(ShoppingListPopup)]
+pub fn shopping_list_popup(props: &ShoppingListPopupProps) -> Html {
+    let documents = use_memo(|()| recent::load(), ());
+    let selected = use_state(HashSet::<String>::new);
+
+    let Ok(mut pack) = Program::parse_from_string(&props.text) else {
+        return html! {
+            <div class="popup">
+                <div class="popup-content shopping-list">
+                    <p>{ "The current document has to be valid to use its recipes." }</p>
+                    <div class="buttons">
+                        <button onclick={props.onclose.reform(|_| ())}>{ "Close" }</button>
+                    </div>
+                </div>
+            </div>
+        };
+    };
+    // include errors are shown in the editor already
+    let _ = pack.resolve_includes(|name| {
+        let document = documents.iter().find(|document| document.name == name)?;
+        Some(document.content.clone())
+    });
+
+    let chosen = documents
+        .iter()
+        .filter(|document| selected.contains(&document.name))
+        .map(|document| (document.name.as_str(), document.content.as_str()));
+    let batch = evaluate_batch(&pack, chosen);
+
+    let entries = documents.iter().map(|document| {
+        let onchange = {
+            let selected = selected.clone();
+            let name = document.name.clone();
+            Callback::from(move |_| {
+                let mut new = (*selected).clone();
+                if !new.remove(&name) {
+                    new.insert(name.clone());
+                }
+                selected.set(new);
+            })
+        };
+        let result = batch
+            .documents
+            .iter()
+            .find(|(name, _)| *name == document.name);
+        let status = match result {
+            Some((_, Ok(report))) => format!(" ({} items missing)", report.missing_items.len()),
+            Some((_, Err(_))) => " (could not be evaluated)".to_string(),
+            None => String::new(),
+        };
+
+        html! {
+            <li>
+                <label>
+                    <input type="checkbox" checked={result.is_some()} {onchange} />
+                    { &document.name }
+                    <span class="details">{ status }</span>
+                </label>
+            </li>
+        }
+    });
+
+    html! {
+        <div class="popup">
+            <div class="popup-content shopping-list">
+                <h2>{ "Shopping list" }</h2>
+                <p>{ "Pick saved documents to add up what they are missing, using the recipes of this document." }</p>
+                <ul>{ for entries }</ul>
+                if selected.is_empty() {
+                    <p>{ "No documents picked yet." }</p>
+                } else if batch.shopping_list.is_empty() {
+                    <p>{ "Nothing is missing." }</p>
+                } else {
+                    <pre class="preview">
+                        { for batch.shopping_list.iter().map(|stack| format!("- {stack}\n")) }
+                    </pre>
+                }
+                <div class="buttons">
+                    <button onclick={props.onclose.reform(|_| ())}>{ "Close" }</button>
+                </div>
+            </div>
+        </div>
+    }
+}
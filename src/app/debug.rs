@@ -0,0 +1,156 @@
+use std::rc::Rc;
+
+use std::str::FromStr;
+
+use log::LevelFilter;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+use super::{logging, Timings};
+use crate::logic::{Evaluation, Statistics};
+
+/// How many trace steps are shown at most, since large documents can take many thousands.
+const MAX_TRACE_ROWS: usize = 500;
+
+#[derive(Properties, PartialEq)]
+pub struct DebugPanelProps {
+    pub evaluation: Rc<Evaluation>,
+    pub timings: Timings,
+}
+
+/// A collapsed panel with numbers and steps of the last evaluation, to find out why a document
+/// is slow or a plan looks wrong.
+#[function_component(DebugPanel)]
+pub fn debug_panel(props: &DebugPanelProps) -> Html {
+    let Statistics {
+        recipes,
+        recipe_runs,
+        items_touched,
+        max_depth,
+    } = props.evaluation.statistics;
+
+    let log_level = use_state(log::max_level);
+    let on_change_log_level = {
+        let log_level = log_level.clone();
+        Callback::from(move |e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            if let Ok(level) = LevelFilter::from_str(&select.value()) {
+                logging::set_level(level);
+                log_level.set(level);
+            }
+        })
+    };
+
+    html! {
+        <details class="debug">
+            <summary>{ "Debug" }</summary>
+            <dl>
+                <dt>{ "Recipes" }</dt>
+                <dd>{ recipes }</dd>
+                <dt>{ "Recipes executed" }</dt>
+                <dd>{ recipe_runs }</dd>
+                <dt>{ "Items touched" }</dt>
+                <dd>{ items_touched }</dd>
+                <dt>{ "Max depth reached" }</dt>
+                <dd>{ max_depth }</dd>
+                <dt>{ "Elapsed time" }</dt>
+                <dd>{ format!("{:.1} ms", props.timings.total_ms()) }</dd>
+                if let Some(imbalances) = &props.evaluation.imbalances {
+                    <dt>{ "Mass balance" }</dt>
+                    <dd>
+                        if imbalances.is_empty() {
+                            { "ok" }
+                        }
+                        { for imbalances.iter().map(|imbalance| html! {
+                            <div class="status-missing">
+                                { format!(
+                                    "{}: {} produced, {} consumed",
+                                    imbalance.item, imbalance.produced, imbalance.consumed
+                                ) }
+                            </div>
+                        }) }
+                    </dd>
+                }
+                <dt>{ "Log level" }</dt>
+                <dd>
+                    <select onchange={on_change_log_level}>
+                        { for logging::LEVELS.into_iter().map(|level| html! {
+                            <option value={level.to_string()} selected={*log_level == level}>
+                                { level.to_string() }
+                            </option>
+                        }) }
+                    </select>
+                </dd>
+            </dl>
+            <Trace evaluation={props.evaluation.clone()} />
+            <ul class="log">
+                { for logging::entries().into_iter().rev().map(|entry| html! {
+                    <li>{ format!("{} {}", entry.level, entry.message) }</li>
+                }) }
+            </ul>
+        </details>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct TraceProps {
+    evaluation: Rc<Evaluation>,
+}
+
+/// The steps of the evaluation as a table, filtered by item name or action.
+#[function_component(Trace)]
+fn trace(props: &TraceProps) -> Html {
+    let filter = use_state(String::new);
+
+    let on_filter_input = {
+        let filter = filter.clone();
+        Callback::from(move |e: InputEvent| {
+            filter.set(e.target_unchecked_into::<HtmlInputElement>().value());
+        })
+    };
+
+    let Ok(report) = &props.evaluation.report else {
+        return html! {};
+    };
+
+    let needle = filter.to_lowercase();
+    let mut events = report.trace.iter().filter(|event| {
+        event.item.to_string().to_lowercase().contains(&needle)
+            || event.action.to_string().contains(&needle)
+    });
+    let rows: Vec<_> = events.by_ref().take(MAX_TRACE_ROWS).collect();
+    let hidden = events.count();
+
+    html! {
+        <div class="trace">
+            <input
+                type="search"
+                placeholder="Filter by item or action"
+                value={(*filter).clone()}
+                oninput={on_filter_input}
+            />
+            <table>
+                <tr>
+                    <th>{ "Depth" }</th>
+                    <th>{ "Item" }</th>
+                    <th>{ "Action" }</th>
+                    <th>{ "Count" }</th>
+                </tr>
+                { for rows.iter().map(|event| html! {
+                    <tr>
+                        <td>{ event.depth }</td>
+                        // indented like the crafting tree
+                        <td style={format!("padding-left: {}em", 0.5 + event.depth as f64)}>
+                            { event.item.to_string() }
+                        </td>
+                        <td>{ event.action.to_string() }</td>
+                        <td>{ event.count }</td>
+                    </tr>
+                }) }
+            </table>
+            if hidden > 0 {
+                <p>{ format!("{hidden} more steps are not shown") }</p>
+            }
+        </div>
+    }
+}
@@ -0,0 +1,47 @@
+use js_sys::{Array, Object};
+use monaco::api::TextModel;
+
+use super::{js::set, location::position_at};
+use crate::logic::{Coverage, Span};
+
+/// Replace the coverage markers in the gutter next to need lines.
+///
+/// `decorations` holds the ids of the decorations that were added previously.
+pub fn set_coverage(
+    model: &TextModel,
+    decorations: &mut Array,
+    text: &str,
+    coverage: &[(Span, Coverage)],
+) {
+    let new_decorations: Array = coverage
+        .iter()
+        .map(|(span, coverage)| {
+            let (line, _) = position_at(text, span.start);
+
+            let range = Object::new();
+            set(&range, "startLineNumber", &line.into());
+            set(&range, "startColumn", &1.into());
+            set(&range, "endLineNumber", &line.into());
+            set(&range, "endColumn", &1.into());
+
+            let class_name = match coverage {
+                Coverage::Full => "coverage-full",
+                Coverage::Partial => "coverage-partial",
+                Coverage::None => "coverage-none",
+            };
+
+            let options = Object::new();
+            set(&options, "isWholeLine", &true.into());
+            set(&options, "linesDecorationsClassName", &class_name.into());
+
+            let decoration = Object::new();
+            set(&decoration, "range", &range);
+            set(&decoration, "options", &options);
+            decoration
+        })
+        .collect();
+
+    *decorations = model
+        .as_ref()
+        .delta_decorations(decorations, &new_decorations, None);
+}
@@ -0,0 +1,26 @@
+use js_sys::Array;
+use wasm_bindgen::JsCast;
+use web_sys::{Blob, BlobPropertyBag, HtmlAnchorElement, Url};
+
+/// Let the user save `contents` as a file named `file_name`.
+pub fn download(file_name: &str, mime_type: &str, contents: &str) {
+    let mut options = BlobPropertyBag::new();
+    options.type_(mime_type);
+
+    let parts = Array::of1(&contents.into());
+    let blob = Blob::new_with_str_sequence_and_options(&parts, &options)
+        .expect("blob should be created from a string");
+    let url = Url::create_object_url_with_blob(&blob).expect("object url should be created");
+
+    let anchor: HtmlAnchorElement = web_sys::window()
+        .and_then(|window| window.document())
+        .expect("page should have a document")
+        .create_element("a")
+        .expect("anchor element should be created")
+        .unchecked_into();
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+
+    Url::revoke_object_url(&url).expect("object url should be revoked");
+}
@@ -0,0 +1,217 @@
+use js_sys::{Array, Object};
+use monaco::{
+    api::{CodeEditor, TextModel},
+    sys::editor::{IActionDescriptor, ICodeEditor},
+};
+use wasm_bindgen::{prelude::*, JsCast};
+use yew::Callback;
+
+use super::{
+    js::set,
+    location::{position_at, range_of},
+};
+use crate::logic::{
+    editing::{self, SortOrder, TextEdit},
+    Program, Span,
+};
+
+/// App-level commands that can be triggered from the editor.
+pub struct Commands {
+    pub evaluate: Callback<()>,
+    pub export: Callback<()>,
+    pub export_checklist: Callback<()>,
+    pub export_html: Callback<()>,
+    pub toggle_theme: Callback<()>,
+}
+
+/// Register our actions on the editor. They show up in the command palette.
+pub fn register_actions(editor: &CodeEditor, model: &TextModel, commands: Commands) {
+    let app_actions = [
+        ("evaluate", "Evaluate", &[CTRL_ENTER][..], commands.evaluate),
+        ("export-report", "Export Report", &[], commands.export),
+        (
+            "export-checklist",
+            "Export Markdown Checklist",
+            &[],
+            commands.export_checklist,
+        ),
+        (
+            "export-html-report",
+            "Export HTML Report",
+            &[],
+            commands.export_html,
+        ),
+        ("toggle-theme", "Toggle Theme", &[], commands.toggle_theme),
+    ];
+
+    for (id, label, keybindings, command) in app_actions {
+        add_action(editor, id, label, keybindings, move |_| command.emit(()));
+    }
+
+    {
+        let model = model.clone();
+        add_action(
+            editor,
+            "format-document",
+            "Format Document",
+            &[],
+            move |editor| {
+                let text = model.get_value();
+                let Ok(program) = Program::parse_from_string(&text) else {
+                    return;
+                };
+
+                let formatted = editing::format(&program, &text);
+                if formatted != text {
+                    replace_document(editor, &text, formatted);
+                }
+            },
+        );
+    }
+
+    {
+        let model = model.clone();
+        add_action(
+            editor,
+            "insert-recipe",
+            "Insert Recipe",
+            &[],
+            move |editor| {
+                let text = model.get_value();
+                let Ok(program) = Program::parse_from_string(&text) else {
+                    return;
+                };
+                let Some(edit) = editing::insert_recipe(&program, &text) else {
+                    return;
+                };
+
+                apply_edits(editor, &text, &[edit.clone()]);
+
+                // select the new recipe so it can be typed over right away
+                let recipe = Span {
+                    start: edit.span.start + edit.text.len() - editing::RECIPE_TEMPLATE.len(),
+                    end: edit.span.start + edit.text.len(),
+                };
+                editor.set_selection(&range_of(&model.get_value(), recipe));
+                editor.focus();
+            },
+        );
+    }
+
+    let sort_actions = [
+        (
+            "sort-sections-alphabetically",
+            "Sort Sections Alphabetically",
+            SortOrder::Alphabetical,
+        ),
+        (
+            "sort-sections-by-count",
+            "Sort Sections by Count",
+            SortOrder::Count,
+        ),
+    ];
+
+    for (id, label, order) in sort_actions {
+        let model = model.clone();
+        add_action(editor, id, label, &[], move |editor| {
+            let text = model.get_value();
+            let Ok(program) = Program::parse_from_string(&text) else {
+                return;
+            };
+
+            apply_edits(
+                editor,
+                &text,
+                &editing::sort_sections(&program, &text, order),
+            );
+        });
+    }
+}
+
+/// `KeyMod.CtrlCmd | KeyCode.Enter` in Monaco's key encoding.
+const CTRL_ENTER: u32 = 2048 | 3;
+
+/// Add an action to the editor that calls `run` when triggered.
+fn add_action(
+    editor: &CodeEditor,
+    id: &str,
+    label: &str,
+    keybindings: &[u32],
+    run: impl Fn(&ICodeEditor) + 'static,
+) {
+    let run = Closure::<dyn Fn(ICodeEditor)>::new(move |editor: ICodeEditor| run(&editor));
+
+    let descriptor = Object::new();
+    set(&descriptor, "id", &id.into());
+    set(&descriptor, "label", &label.into());
+    set(&descriptor, "contextMenuGroupId", &"1_modification".into());
+    set(
+        &descriptor,
+        "keybindings",
+        &keybindings
+            .iter()
+            .map(|&key| JsValue::from(key))
+            .collect::<Array>(),
+    );
+    set(&descriptor, "run", run.as_ref());
+
+    // actions stay registered for as long as the editor exists
+    run.forget();
+
+    editor
+        .as_ref()
+        .add_action(descriptor.unchecked_ref::<IActionDescriptor>());
+}
+
+/// Apply edits to the editor's model as a single undoable operation.
+///
+/// All changes to the document that don't come from typing should go through
+/// here, so they end up on the undo stack instead of replacing it.
+///
+/// `text` must be the model content the edits were computed from.
+pub fn apply_edits(editor: &ICodeEditor, text: &str, edits: &[TextEdit]) {
+    let operations: Array = edits
+        .iter()
+        .map(|edit| {
+            let operation = Object::new();
+            set(&operation, "range", &range_of(text, edit.span));
+            set(&operation, "text", &edit.text.as_str().into());
+            operation
+        })
+        .collect();
+
+    // the stops keep the edits from being merged with whatever was typed around them
+    editor.push_undo_stop();
+    editor.execute_edits(Some("game-recipe-calc"), &operations, None);
+    editor.push_undo_stop();
+}
+
+/// Replace the whole document, such as when importing one.
+///
+/// `text` must be the current model content.
+pub fn replace_document(editor: &ICodeEditor, text: &str, new_text: String) {
+    let edit = TextEdit {
+        span: Span {
+            start: 0,
+            end: text.len(),
+        },
+        text: new_text,
+    };
+    apply_edits(editor, text, &[edit]);
+}
+
+/// Move the cursor to the start of `span` and scroll it into view.
+///
+/// `text` must be the model content the span was computed from.
+pub fn go_to(editor: &CodeEditor, text: &str, span: Span) {
+    let (line, column) = position_at(text, span.start);
+
+    let position = Object::new();
+    set(&position, "lineNumber", &line.into());
+    set(&position, "column", &column.into());
+
+    let editor: &ICodeEditor = editor.as_ref();
+    editor.set_position(position.unchecked_ref());
+    editor.reveal_line_in_center(line, None);
+    editor.focus();
+}
@@ -0,0 +1,65 @@
+use std::rc::Rc;
+
+use web_sys::UrlSearchParams;
+use yew::prelude::*;
+
+use super::{
+    output::{Output, Tab},
+    share,
+    theme::Theme,
+    transform_text, Overrides,
+};
+
+/// The query parameters of the page.
+fn query() -> Option<UrlSearchParams> {
+    let search = web_sys::window()?.location().search().ok()?;
+    UrlSearchParams::new_with_str(&search).ok()
+}
+
+/// Whether the page should only show the plan, which is the case when `?embed` is in the URL.
+pub fn is_embed() -> bool {
+    query().map_or(false, |query| query.has("embed"))
+}
+
+/// A read-only view of a shared document's plan, meant to be shown in an iframe.
+///
+/// The document comes from the share link, and the view can be configured with
+/// the `tab` (`summary`, `steps`, `tree`, `graph` or `raw`) and `theme`
+/// (`dark` or `light`) query parameters.
+#[function_component(Embed)]
+pub fn embed() -> Html {
+    let document = use_memo(|()| share::document_from_url(), ());
+    let (tab, theme) = *use_memo(
+        |()| {
+            let query = query();
+            let param = |name| query.as_ref().and_then(|query| query.get(name));
+
+            let tab = param("tab").and_then(|tab| Tab::from_id(&tab));
+            let theme = match param("theme").as_deref() {
+                Some("light") => Theme::Light,
+                _ => Theme::Dark,
+            };
+            (tab.unwrap_or_default(), theme)
+        },
+        (),
+    );
+
+    let Some(document) = &*document else {
+        return html! {
+            <div class={classes!("embed", theme.class())}>
+                <p>{ "This link does not contain a document." }</p>
+            </div>
+        };
+    };
+
+    let evaluation = Rc::new(transform_text(document, &Overrides::default()));
+
+    html! {
+        <div class={classes!("embed", theme.class())}>
+            <Output {evaluation} initial_tab={tab} />
+            <a class="open-link" href={share::share_url(document, &share::pins_from_url())} target="_blank">
+                { "Open in Game Recipe Calculator" }
+            </a>
+        </div>
+    }
+}
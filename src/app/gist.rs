@@ -0,0 +1,241 @@
+use std::collections::{BTreeMap, HashMap};
+
+use gloo_net::http::Request;
+use gloo_storage::{LocalStorage, Storage};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+/// The local storage key the GitHub token is saved under.
+const TOKEN_STORAGE_KEY: &str = "github-token";
+
+/// The file name documents are saved as inside a gist.
+const FILE_NAME: &str = "recipes.txt";
+
+const API_URL: &str = "https://api.github.com/gists";
+
+#[derive(Deserialize)]
+struct GistResponse {
+    id: String,
+    html_url: String,
+    /// The files by name, sorted so picking one of them always picks the same.
+    files: BTreeMap<String, ResponseFile>,
+}
+
+/// A file of a [GistResponse].
+#[derive(Deserialize)]
+struct ResponseFile {
+    #[serde(default)]
+    content: String,
+    /// Whether `content` was cut off because the file is large, in which case the whole
+    /// file has to be downloaded from `raw_url`.
+    #[serde(default)]
+    truncated: bool,
+    raw_url: Option<String>,
+}
+
+#[derive(Serialize)]
+struct GistFile {
+    content: String,
+}
+
+#[derive(Serialize)]
+struct GistRequest<'a> {
+    description: &'a str,
+    public: bool,
+    files: HashMap<&'a str, GistFile>,
+}
+
+/// Get the gist id from a gist URL, or return the input if it already is an id.
+fn gist_id(input: &str) -> &str {
+    input
+        .trim()
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+}
+
+/// Load a document from the gist with the given id or URL.
+async fn load(id: &str, token: &str) -> Result<String, String> {
+    let mut request = Request::get(&format!("{API_URL}/{}", gist_id(id)))
+        .header("Accept", "application/vnd.github+json");
+    if !token.is_empty() {
+        request = request.header("Authorization", &format!("Bearer {token}"));
+    }
+
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    if !response.ok() {
+        return Err(format!("GitHub responded with {}", response.status()));
+    }
+
+    let gist: GistResponse = response.json().await.map_err(|e| e.to_string())?;
+    let mut files = gist.files;
+
+    // prefer our own file name, but accept gists that were created by hand
+    let file = match files.remove(FILE_NAME) {
+        Some(file) => file,
+        None => files
+            .into_values()
+            .next()
+            .ok_or_else(|| "This gist has no files".to_string())?,
+    };
+    if !file.truncated {
+        return Ok(file.content);
+    }
+
+    let url = file
+        .raw_url
+        .ok_or_else(|| "This gist is too large to load".to_string())?;
+    let response = Request::get(&url).send().await.map_err(|e| e.to_string())?;
+    if !response.ok() {
+        return Err(format!("GitHub responded with {}", response.status()));
+    }
+    response.text().await.map_err(|e| e.to_string())
+}
+
+/// Save the document to a gist, updating it if an id is given.
+///
+/// Returns the id and URL of the gist.
+async fn save(id: Option<&str>, token: &str, content: &str) -> Result<(String, String), String> {
+    let body = GistRequest {
+        description: "Game recipe calculator document",
+        public: false,
+        files: HashMap::from([(
+            FILE_NAME,
+            GistFile {
+                content: content.to_string(),
+            },
+        )]),
+    };
+
+    let request = match id {
+        Some(id) => Request::patch(&format!("{API_URL}/{}", gist_id(id))),
+        None => Request::post(API_URL),
+    };
+
+    let response = request
+        .header("Accept", "application/vnd.github+json")
+        .header("Authorization", &format!("Bearer {token}"))
+        .json(&body)
+        .map_err(|e| e.to_string())?
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.ok() {
+        return Err(format!("GitHub responded with {}", response.status()));
+    }
+
+    let gist: GistResponse = response.json().await.map_err(|e| e.to_string())?;
+    Ok((gist.id, gist.html_url))
+}
+
+#[derive(Properties, PartialEq)]
+pub struct GistPopupProps {
+    /// The current document, which is what gets saved.
+    pub text: AttrValue,
+    /// Called with the id and content of a loaded gist.
+    pub onload: Callback<(String, String)>,
+    pub onclose: Callback<()>,
+}
+
+/// A popup to save the document to a GitHub gist or load it from one.
+#[function_component(GistPopup)]
+pub fn gist_popup(props: &GistPopupProps) -> Html {
+    let token = use_state(|| LocalStorage::get::<String>(TOKEN_STORAGE_KEY).unwrap_or_default());
+    let gist = use_state(String::new);
+    let status = use_state(|| None::<String>);
+
+    let on_token_input = {
+        let token = token.clone();
+        Callback::from(move |e: InputEvent| {
+            let value = e.target_unchecked_into::<HtmlInputElement>().value();
+            if let Err(e) = LocalStorage::set(TOKEN_STORAGE_KEY, &value) {
+                warn!("Could not save GitHub token: {e}");
+            }
+            token.set(value);
+        })
+    };
+
+    let on_gist_input = {
+        let gist = gist.clone();
+        Callback::from(move |e: InputEvent| {
+            gist.set(e.target_unchecked_into::<HtmlInputElement>().value());
+        })
+    };
+
+    let on_load = {
+        let token = token.clone();
+        let gist = gist.clone();
+        let status = status.clone();
+        let onload = props.onload.clone();
+        Callback::from(move |_| {
+            let token = (*token).clone();
+            let id = (*gist).clone();
+            let status = status.clone();
+            let onload = onload.clone();
+            status.set(Some("Loading...".into()));
+            spawn_local(async move {
+                match load(&id, &token).await {
+                    Ok(content) => {
+                        status.set(Some("Loaded the gist".into()));
+                        onload.emit((gist_id(&id).to_string(), content));
+                    }
+                    Err(e) => status.set(Some(format!("Could not load the gist: {e}"))),
+                }
+            });
+        })
+    };
+
+    let on_save = {
+        let token = token.clone();
+        let gist = gist.clone();
+        let status = status.clone();
+        let text = props.text.clone();
+        Callback::from(move |_| {
+            let token = (*token).clone();
+            let id = (*gist).clone();
+            let gist = gist.clone();
+            let status = status.clone();
+            let text = text.clone();
+            status.set(Some("Saving...".into()));
+            spawn_local(async move {
+                let id = (!id.trim().is_empty()).then_some(id.as_str());
+                match save(id, &token, &text).await {
+                    Ok((id, url)) => {
+                        status.set(Some(format!("Saved to {url}")));
+                        gist.set(id);
+                    }
+                    Err(e) => status.set(Some(format!("Could not save the gist: {e}"))),
+                }
+            });
+        })
+    };
+
+    let on_close = props.onclose.reform(|_| ());
+
+    html! {
+        <div class="popup">
+            <div class="popup-content">
+                <label>
+                    { "GitHub token (needs the gist scope, only stored in this browser)" }
+                    <input type="password" value={(*token).clone()} oninput={on_token_input} />
+                </label>
+                <label>
+                    { "Gist URL or id (leave empty to create a new gist)" }
+                    <input type="text" value={(*gist).clone()} oninput={on_gist_input} />
+                </label>
+                <div class="buttons">
+                    <button onclick={on_load} disabled={gist.trim().is_empty()}>{ "Load" }</button>
+                    <button onclick={on_save} disabled={token.is_empty()}>{ "Save" }</button>
+                    <button onclick={on_close}>{ "Close" }</button>
+                </div>
+                if let Some(status) = &*status {
+                    <p>{ status }</p>
+                }
+            </div>
+        </div>
+    }
+}
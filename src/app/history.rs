@@ -0,0 +1,235 @@
+use js_sys::{Array, Date, Function, Object, Promise, Reflect};
+use log::warn;
+use wasm_bindgen::{prelude::*, JsCast};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{
+    IdbDatabase, IdbObjectStore, IdbObjectStoreParameters, IdbRequest, IdbTransactionMode,
+};
+use yew::prelude::*;
+
+use super::js::set;
+use crate::logic::editing::{diff_lines, DiffLine};
+
+const DATABASE_NAME: &str = "game-recipe-calc";
+const STORE_NAME: &str = "snapshots";
+
+/// How many snapshots are kept for each document.
+const MAX_SNAPSHOTS: usize = 50;
+
+/// How often the document is saved to the history, in milliseconds.
+pub const SNAPSHOT_INTERVAL: u32 = 60_000;
+
+/// An older version of a document.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Snapshot {
+    /// The key of the snapshot in the object store.
+    key: JsValue,
+    /// When the snapshot was taken, in milliseconds since the unix epoch.
+    pub time: f64,
+    pub content: String,
+}
+
+/// Turn a pending request into a promise that resolves with its result.
+fn request_promise(request: &IdbRequest) -> Promise {
+    Promise::new(&mut |resolve: Function, reject: Function| {
+        let succeeded = request.clone();
+        let on_success = Closure::once_into_js(move || {
+            let result = succeeded.result().unwrap_or(JsValue::UNDEFINED);
+            resolve.call1(&JsValue::UNDEFINED, &result).unwrap();
+        });
+
+        let failed = request.clone();
+        let on_error = Closure::once_into_js(move || {
+            let error = failed.error().ok().flatten().map(JsValue::from);
+            reject
+                .call1(&JsValue::UNDEFINED, &error.unwrap_or(JsValue::UNDEFINED))
+                .unwrap();
+        });
+
+        request.set_onsuccess(Some(on_success.unchecked_ref()));
+        request.set_onerror(Some(on_error.unchecked_ref()));
+    })
+}
+
+async fn wait(request: &IdbRequest) -> Result<JsValue, JsValue> {
+    JsFuture::from(request_promise(request)).await
+}
+
+async fn open_store(mode: IdbTransactionMode) -> Result<IdbObjectStore, JsValue> {
+    let factory = web_sys::window()
+        .ok_or("page has no window")?
+        .indexed_db()?
+        .ok_or("IndexedDB is not available")?;
+
+    let request = factory.open_with_u32(DATABASE_NAME, 1)?;
+    let upgraded = request.clone();
+    let on_upgrade = Closure::once_into_js(move || {
+        let database: IdbDatabase = upgraded.result().unwrap().unchecked_into();
+        let mut parameters = IdbObjectStoreParameters::new();
+        parameters.auto_increment(true);
+        database
+            .create_object_store_with_optional_parameters(STORE_NAME, &parameters)
+            .unwrap();
+    });
+    request.set_onupgradeneeded(Some(on_upgrade.unchecked_ref()));
+
+    let database: IdbDatabase = wait(&request).await?.unchecked_into();
+    database
+        .transaction_with_str_and_mode(STORE_NAME, mode)?
+        .object_store(STORE_NAME)
+}
+
+/// Get the snapshots of a document, newest first.
+pub async fn snapshots(name: &str) -> Result<Vec<Snapshot>, JsValue> {
+    let store = open_store(IdbTransactionMode::Readonly).await?;
+    // both requests have to be made before the transaction finishes
+    let values = store.get_all()?;
+    let keys = store.get_all_keys()?;
+    let values: Array = wait(&values).await?.unchecked_into();
+    let keys: Array = wait(&keys).await?.unchecked_into();
+
+    let field = |value: &JsValue, key: &str| Reflect::get(value, &key.into()).unwrap_or_default();
+
+    let mut snapshots: Vec<Snapshot> = keys
+        .iter()
+        .zip(values.iter())
+        .filter(|(_, value)| field(value, "name").as_string().as_deref() == Some(name))
+        .map(|(key, value)| Snapshot {
+            key,
+            time: field(&value, "time").as_f64().unwrap_or_default(),
+            content: field(&value, "content").as_string().unwrap_or_default(),
+        })
+        .collect();
+
+    snapshots.reverse();
+    Ok(snapshots)
+}
+
+/// Add a snapshot of a document, unless it didn't change since the last one.
+pub async fn save_snapshot(name: &str, content: &str) -> Result<(), JsValue> {
+    let existing = snapshots(name).await?;
+    if existing.first().map(|s| s.content.as_str()) == Some(content) {
+        return Ok(());
+    }
+
+    let snapshot = Object::new();
+    set(&snapshot, "name", &name.into());
+    set(&snapshot, "time", &Date::now().into());
+    set(&snapshot, "content", &content.into());
+
+    let store = open_store(IdbTransactionMode::Readwrite).await?;
+
+    // the new snapshot is not in `existing`, so keep one less of those
+    for old in existing.iter().skip(MAX_SNAPSHOTS - 1) {
+        store.delete(&old.key)?;
+    }
+
+    wait(&store.add(&snapshot)?).await?;
+    Ok(())
+}
+
+#[derive(Properties, PartialEq)]
+pub struct HistoryPopupProps {
+    /// The name of the document to show the history of.
+    pub name: AttrValue,
+    /// The current content of the document, to compare snapshots with.
+    pub text: AttrValue,
+    /// Called with the content of the snapshot to restore.
+    pub onrestore: Callback<String>,
+    pub onclose: Callback<()>,
+}
+
+/// A popup to look through older versions of the document and restore them.
+#[function_component(HistoryPopup)]
+pub fn history_popup(props: &HistoryPopupProps) -> Html {
+    let snapshots = use_state(|| None::<Vec<Snapshot>>);
+    let selected = use_state(|| 0usize);
+
+    {
+        let snapshots = snapshots.clone();
+        use_effect_with_deps(
+            move |name: &AttrValue| {
+                let name = name.clone();
+                spawn_local(async move {
+                    match self::snapshots(&name).await {
+                        Ok(loaded) => snapshots.set(Some(loaded)),
+                        Err(e) => {
+                            warn!("Could not load the document history: {e:?}");
+                            snapshots.set(Some(vec![]));
+                        }
+                    }
+                });
+                || ()
+            },
+            props.name.clone(),
+        );
+    }
+
+    let content = match &*snapshots {
+        None => html! { <p>{ "Loading..." }</p> },
+        Some(snapshots) if snapshots.is_empty() => {
+            html! { <p>{ "There are no older versions of this document yet." }</p> }
+        }
+        Some(snapshots) => {
+            let entries = snapshots.iter().enumerate().map(|(i, snapshot)| {
+                let time = Date::new(&snapshot.time.into())
+                    .to_locale_string("default", &JsValue::UNDEFINED)
+                    .as_string()
+                    .unwrap_or_default();
+                let onclick = {
+                    let selected = selected.clone();
+                    Callback::from(move |_| selected.set(i))
+                };
+
+                html! {
+                    <li class={classes!((*selected == i).then_some("active"))} {onclick}>
+                        { time }
+                    </li>
+                }
+            });
+
+            let snapshot = &snapshots[(*selected).min(snapshots.len() - 1)];
+            let diff =
+                diff_lines(&props.text, &snapshot.content)
+                    .into_iter()
+                    .map(|line| match line {
+                        DiffLine::Unchanged(line) => html! { <div>{ format!("  {line}") }</div> },
+                        DiffLine::Added(line) => {
+                            html! { <div class="added">{ format!("+ {line}") }</div> }
+                        }
+                        DiffLine::Removed(line) => {
+                            html! { <div class="removed">{ format!("- {line}") }</div> }
+                        }
+                    });
+
+            let onclick = {
+                let onrestore = props.onrestore.clone();
+                let content = snapshot.content.clone();
+                Callback::from(move |_| onrestore.emit(content.clone()))
+            };
+
+            html! {
+                <div class="history">
+                    <ul class="snapshots">{ for entries }</ul>
+                    <div>
+                        <p>{ "Changes compared to the current document:" }</p>
+                        <pre class="diff">{ for diff }</pre>
+                        <button {onclick}>{ "Restore this version" }</button>
+                    </div>
+                </div>
+            }
+        }
+    };
+
+    html! {
+        <div class="popup">
+            <div class="popup-content">
+                <h2>{ format!("History of {}", props.name) }</h2>
+                { content }
+                <div class="buttons">
+                    <button onclick={props.onclose.reform(|_| ())}>{ "Close" }</button>
+                </div>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,154 @@
+use web_sys::{HtmlInputElement, HtmlSelectElement, HtmlTextAreaElement};
+use yew::prelude::*;
+
+use crate::logic::{
+    editing::{parse_tsv, NumberFormat, StackSection, TsvColumns},
+    ItemStack,
+};
+
+#[derive(Properties, PartialEq)]
+pub struct ImportPopupProps {
+    /// Called with the section to add the items to, and the items.
+    pub onimport: Callback<(StackSection, Vec<ItemStack>)>,
+    pub onclose: Callback<()>,
+}
+
+/// A popup to turn rows pasted from a spreadsheet into need or have entries.
+#[function_component(ImportPopup)]
+pub fn import_popup(props: &ImportPopupProps) -> Html {
+    let tsv = use_state(String::new);
+    let columns = use_state(|| TsvColumns { item: 0, count: 1 });
+    let section = use_state(|| StackSection::Have);
+    let numbers = use_state(NumberFormat::default);
+
+    let column_count = tsv
+        .lines()
+        .map(|row| row.split('\t').count())
+        .max()
+        .unwrap_or_default()
+        .max(2);
+    let stacks = parse_tsv(&tsv, *columns, *numbers);
+
+    let on_input = {
+        let tsv = tsv.clone();
+        Callback::from(move |e: InputEvent| {
+            tsv.set(e.target_unchecked_into::<HtmlTextAreaElement>().value());
+        })
+    };
+
+    let column_select = |selected: usize, update: fn(&mut TsvColumns, usize)| {
+        let onchange = {
+            let columns = columns.clone();
+            Callback::from(move |e: Event| {
+                let select: HtmlSelectElement = e.target_unchecked_into();
+                if let Ok(column) = select.value().parse() {
+                    let mut new_columns = *columns;
+                    update(&mut new_columns, column);
+                    columns.set(new_columns);
+                }
+            })
+        };
+
+        html! {
+            <select {onchange}>
+                { for (0..column_count).map(|column| html! {
+                    <option value={column.to_string()} selected={column == selected}>
+                        { format!("Column {}", column + 1) }
+                    </option>
+                }) }
+            </select>
+        }
+    };
+
+    let on_change_section = {
+        let section = section.clone();
+        Callback::from(move |e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            section.set(match select.value().as_str() {
+                "need" => StackSection::Need,
+                "player" => StackSection::Inventory(String::new()),
+                _ => StackSection::Have,
+            });
+        })
+    };
+
+    let on_player_input = {
+        let section = section.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            section.set(StackSection::Inventory(input.value()));
+        })
+    };
+    let player = match &*section {
+        StackSection::Inventory(player) => Some(player.clone()),
+        _ => None,
+    };
+    let can_import = !stacks.is_empty() && !player.as_ref().is_some_and(|p| p.trim().is_empty());
+
+    let on_change_numbers = {
+        let numbers = numbers.clone();
+        Callback::from(move |e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            numbers.set(match select.value().as_str() {
+                "comma" => NumberFormat::DecimalComma,
+                _ => NumberFormat::DecimalPoint,
+            });
+        })
+    };
+
+    let on_import = {
+        let onimport = props.onimport.clone();
+        let section = match &*section {
+            StackSection::Inventory(player) => StackSection::Inventory(player.trim().to_string()),
+            section => section.clone(),
+        };
+        let stacks = stacks.clone();
+        Callback::from(move |_| onimport.emit((section.clone(), stacks.clone())))
+    };
+
+    html! {
+        <div class="popup">
+            <div class="popup-content import">
+                <h2>{ "Import from a spreadsheet" }</h2>
+                <label>
+                    { "Paste rows here. Rows for the same item, such as from different locations, are added up." }
+                    <textarea rows="10" value={(*tsv).clone()} oninput={on_input} />
+                </label>
+                <div class="buttons">
+                    <label>{ "Item" }{ column_select(columns.item, |c, column| c.item = column) }</label>
+                    <label>{ "Count" }{ column_select(columns.count, |c, column| c.count = column) }</label>
+                    <label>
+                        { "Numbers" }
+                        <select onchange={on_change_numbers}>
+                            <option value="point" selected={*numbers == NumberFormat::DecimalPoint}>{ "1,000.5" }</option>
+                            <option value="comma" selected={*numbers == NumberFormat::DecimalComma}>{ "1.000,5" }</option>
+                        </select>
+                    </label>
+                    <label>
+                        { "Add to" }
+                        <select onchange={on_change_section}>
+                            <option value="have" selected={*section == StackSection::Have}>{ "have" }</option>
+                            <option value="need" selected={*section == StackSection::Need}>{ "need" }</option>
+                            <option value="player" selected={player.is_some()}>{ "a player's have" }</option>
+                        </select>
+                    </label>
+                    if let Some(player) = &player {
+                        <label>
+                            { "Player" }
+                            <input type="text" value={player.clone()} oninput={on_player_input} />
+                        </label>
+                    }
+                </div>
+                <pre class="preview">
+                    { for stacks.iter().map(|stack| format!("- {stack}\n")) }
+                </pre>
+                <div class="buttons">
+                    <button onclick={on_import} disabled={!can_import}>
+                        { format!("Import {} items", stacks.len()) }
+                    </button>
+                    <button onclick={props.onclose.reform(|_| ())}>{ "Close" }</button>
+                </div>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,7 @@
+use js_sys::{Object, Reflect};
+use wasm_bindgen::JsValue;
+
+/// Set a property on a plain JavaScript object.
+pub fn set(target: &Object, key: &str, value: &JsValue) {
+    Reflect::set(target, &key.into(), value).expect("target should be a plain object");
+}
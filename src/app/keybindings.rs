@@ -0,0 +1,110 @@
+use gloo_storage::{LocalStorage, Storage};
+use js_sys::Function;
+use log::warn;
+use monaco::{api::CodeEditor, sys::editor::IContextKey};
+use wasm_bindgen::{prelude::*, JsCast};
+
+/// The local storage key the chosen keybindings are saved under.
+const STORAGE_KEY: &str = "keybindings";
+
+/// The context key that is set while the Emacs keybindings are active.
+const EMACS_CONTEXT_KEY: &str = "emacsKeybindings";
+
+/// Monaco's `KeyMod.CtrlCmd`.
+const CTRL: u32 = 2048;
+/// Monaco's `KeyMod.Alt`.
+const ALT: u32 = 512;
+
+/// Emacs-style shortcuts and the built-in editor commands they run.
+///
+/// The numbers are Monaco's `KeyCode` values for the letter keys.
+const EMACS_BINDINGS: [(u32, &str); 10] = [
+    (CTRL | 31, "cursorHome"),         // C-a
+    (CTRL | 35, "cursorEnd"),          // C-e
+    (CTRL | 36, "cursorRight"),        // C-f
+    (CTRL | 32, "cursorLeft"),         // C-b
+    (CTRL | 44, "cursorDown"),         // C-n
+    (CTRL | 46, "cursorUp"),           // C-p
+    (CTRL | 34, "deleteRight"),        // C-d
+    (CTRL | 41, "deleteAllRight"),     // C-k
+    (ALT | 36, "cursorWordEndRight"),  // M-f
+    (ALT | 32, "cursorWordStartLeft"), // M-b
+];
+
+/// A set of keyboard shortcuts for the editor.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum Keybindings {
+    /// Monaco's own shortcuts.
+    #[default]
+    Default,
+    /// Emacs-style cursor movement and editing on top of Monaco's shortcuts.
+    Emacs,
+}
+
+impl Keybindings {
+    pub const ALL: [Keybindings; 2] = [Keybindings::Default, Keybindings::Emacs];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Keybindings::Default => "default",
+            Keybindings::Emacs => "emacs",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Keybindings::Default => "Default",
+            Keybindings::Emacs => "Emacs",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.id() == id)
+    }
+
+    /// Load the keybindings chosen in an earlier session.
+    pub fn load() -> Self {
+        LocalStorage::get::<String>(STORAGE_KEY)
+            .ok()
+            .and_then(|id| Self::from_id(&id))
+            .unwrap_or_default()
+    }
+
+    pub fn save(self) {
+        if let Err(e) = LocalStorage::set(STORAGE_KEY, self.id()) {
+            warn!("Could not save keybindings: {e}");
+        }
+    }
+}
+
+/// The Emacs keybindings, which only apply while they are enabled.
+pub struct EmacsLayer(IContextKey);
+
+impl EmacsLayer {
+    pub fn register(editor: &CodeEditor) -> Self {
+        let editor = editor.as_ref();
+        let context_key = editor.create_context_key(EMACS_CONTEXT_KEY, &false.into());
+
+        for (keybinding, command) in EMACS_BINDINGS {
+            let target = editor.clone();
+            let handler = Closure::<dyn Fn()>::new(move || {
+                target.trigger(Some("keyboard"), command, &JsValue::NULL);
+            });
+
+            editor.add_command(
+                keybinding.into(),
+                handler.as_ref().unchecked_ref::<Function>(),
+                Some(EMACS_CONTEXT_KEY),
+            );
+
+            // commands stay registered for as long as the editor exists
+            handler.forget();
+        }
+
+        Self(context_key)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.0.set(&enabled.into());
+    }
+}
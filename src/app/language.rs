@@ -0,0 +1,252 @@
+use std::{cell::RefCell, rc::Rc};
+
+use js_sys::{Array, Function, Object, Reflect};
+use monaco::{
+    api::TextModel,
+    sys::{
+        languages::{self, ILanguageExtensionPoint},
+        IRange,
+    },
+};
+use wasm_bindgen::{prelude::*, JsCast};
+
+use super::{
+    js::set,
+    location::{offset_at, position_at, range_of},
+};
+use crate::logic::{editing, Annotation, Program};
+
+/// The Monaco language id used for recipe documents.
+pub const LANGUAGE_ID: &str = "recipes";
+
+/// The Monarch tokenizer that highlights recipe documents. Section headers and words such as
+/// `prefer` are keywords, counts are numbers and item names are variables, so each of them
+/// gets its own color in the built-in themes.
+const TOKENIZER: &str = r##"{
+    "tokenizer": {
+        "root": [
+            ["^##.*$", "comment.doc"],
+            ["#.*$", "comment"],
+            ["^(version)(:)", ["keyword", "delimiter"]],
+            ["^(include)(\\s+)([^#]*)", ["keyword", "white", "string"]],
+            ["^(have)(\\s+)([^:#]+)(:)", ["keyword", "white", "type", "delimiter"]],
+            ["^(options|sources|categories|need|have|recipes)(:)", ["keyword", "delimiter"]],
+            ["^(\\s*-\\s*)(prefer|exact)(?=\\s)", ["delimiter", "keyword"]],
+            ["^\\s*-", "delimiter"],
+            ["\\d+", "number"],
+            ["[*/()-]", "number"],
+            ["[=+|@,]", "operator"],
+            ["[^\\s\\d#=+|@,*/()-][^#=+|@,]*", "variable"],
+            ["\\s+", "white"]
+        ]
+    }
+}"##;
+
+/// Register the recipe language with Monaco.
+pub fn register() {
+    let language = ILanguageExtensionPoint::default();
+    language.set_id(LANGUAGE_ID);
+    languages::register(&language);
+
+    // lets the toggle comment command comment out lines
+    let comments = Object::new();
+    set(&comments, "lineComment", &"#".into());
+    let configuration = Object::new();
+    set(&configuration, "comments", &comments);
+    languages::set_language_configuration(LANGUAGE_ID, configuration.unchecked_ref());
+
+    let tokenizer = js_sys::JSON::parse(TOKENIZER).expect("tokenizer should be valid JSON");
+    languages::set_monarch_tokens_provider(LANGUAGE_ID, tokenizer.unchecked_ref());
+}
+
+/// Offer quick-fixes, such as merging duplicate lines, for the given model.
+pub fn register_code_actions(model: TextModel) {
+    let provide = Closure::<dyn Fn(JsValue, IRange) -> JsValue>::new(
+        move |_model: JsValue, range: IRange| code_actions(&model, &range).into(),
+    );
+
+    let provider = Object::new();
+    set(&provider, "provideCodeActions", provide.as_ref());
+
+    // the provider stays registered for as long as the page is open
+    provide.forget();
+
+    languages::register_code_action_provider(&LANGUAGE_ID.into(), provider.unchecked_ref(), None);
+}
+
+/// Show the doc comments of recipes when hovering over them or over entries for the items
+/// they make.
+pub fn register_hover_provider(model: TextModel) {
+    let provide = Closure::<dyn Fn(JsValue, JsValue) -> JsValue>::new(
+        move |_model: JsValue, position: JsValue| {
+            hover(&model, &position).map_or(JsValue::NULL, JsValue::from)
+        },
+    );
+
+    let provider = Object::new();
+    set(&provider, "provideHover", provide.as_ref());
+
+    // the provider stays registered for as long as the page is open
+    provide.forget();
+
+    languages::register_hover_provider(&LANGUAGE_ID.into(), provider.unchecked_ref());
+}
+
+/// Build a `Hover` for `position`, if there is a doc comment to show there.
+fn hover(model: &TextModel, position: &JsValue) -> Option<Object> {
+    let get = |key: &str| Reflect::get(position, &key.into()).ok()?.as_f64();
+    let text = model.get_value();
+    let offset = offset_at(&text, get("lineNumber")?, get("column")?)?;
+
+    let program = Program::parse_from_string(&text).ok()?;
+    let doc = editing::doc_at(&program, offset)?;
+
+    let content = Object::new();
+    set(&content, "value", &doc.into());
+
+    let hover = Object::new();
+    set(&hover, "contents", &Array::of1(&content));
+    Some(hover)
+}
+
+/// Build a `CodeActionList` for the lines in `range`.
+fn code_actions(model: &TextModel, range: &IRange) -> Object {
+    let text = model.get_value();
+    let lines = range.start_line_number()..=range.end_line_number();
+
+    let actions = Array::new();
+    if let Ok(program) = Program::parse_from_string(&text) {
+        for duplicates in editing::find_duplicates(&program) {
+            let in_range = duplicates
+                .entries
+                .iter()
+                .any(|entry| lines.contains(&position_at(&text, entry.span.start).0));
+
+            if in_range {
+                let title = format!(
+                    "Merge {} '{}' lines",
+                    duplicates.entries.len(),
+                    duplicates.item
+                );
+                actions.push(&quick_fix(model, &text, &title, &duplicates.merge(&text)));
+            }
+        }
+    }
+
+    let list = Object::new();
+    set(&list, "actions", &actions);
+    set(&list, "dispose", &Function::new_no_args(""));
+    list
+}
+
+/// Create a quick-fix code action that applies `edits` to the model.
+fn quick_fix(model: &TextModel, text: &str, title: &str, edits: &[editing::TextEdit]) -> Object {
+    let edits: Array = edits
+        .iter()
+        .map(|edit| {
+            let text_edit = Object::new();
+            set(&text_edit, "range", &range_of(text, edit.span));
+            set(&text_edit, "text", &edit.text.as_str().into());
+
+            let workspace_edit = Object::new();
+            set(&workspace_edit, "resource", &model.as_ref().uri());
+            set(&workspace_edit, "edit", &text_edit);
+            workspace_edit
+        })
+        .collect();
+
+    let workspace_edit = Object::new();
+    set(&workspace_edit, "edits", &edits);
+
+    let action = Object::new();
+    set(&action, "title", &title.into());
+    set(&action, "kind", &"quickfix".into());
+    set(&action, "isPreferred", &true.into());
+    set(&action, "edit", &workspace_edit);
+    action
+}
+
+/// Shows evaluation results as inlay hints in the editor.
+#[derive(Clone, Default)]
+pub struct InlayHints(Rc<RefCell<InlayHintsState>>);
+
+#[derive(Default)]
+struct InlayHintsState {
+    /// The text the annotations were computed for.
+    text: String,
+    annotations: Vec<Annotation>,
+    /// Monaco's listeners for `onDidChangeInlayHints`.
+    listeners: Vec<Function>,
+}
+
+impl InlayHints {
+    /// Register an inlay hints provider that shows these hints for `model`.
+    pub fn register(&self, model: TextModel) {
+        let state = self.0.clone();
+        let provide = Closure::<dyn Fn() -> JsValue>::new(move || {
+            let state = state.borrow();
+            let hints = if model.get_value() == state.text {
+                state
+                    .annotations
+                    .iter()
+                    .map(|annotation| inlay_hint(&state.text, annotation))
+                    .collect()
+            } else {
+                // the annotations are outdated and would end up in the wrong place
+                Array::new()
+            };
+
+            let list = Object::new();
+            set(&list, "hints", &hints);
+            set(&list, "dispose", &Function::new_no_args(""));
+            list.into()
+        });
+
+        let state = self.0.clone();
+        let on_did_change = Closure::<dyn Fn(Function) -> JsValue>::new(move |listener| {
+            state.borrow_mut().listeners.push(listener);
+
+            let disposable = Object::new();
+            set(&disposable, "dispose", &Function::new_no_args(""));
+            disposable.into()
+        });
+
+        let provider = Object::new();
+        set(&provider, "provideInlayHints", provide.as_ref());
+        set(&provider, "onDidChangeInlayHints", on_did_change.as_ref());
+        provide.forget();
+        on_did_change.forget();
+
+        languages::register_inlay_hints_provider(&LANGUAGE_ID.into(), provider.unchecked_ref());
+    }
+
+    /// Replace the shown hints with annotations computed for `text`.
+    pub fn update(&self, text: &str, annotations: &[Annotation]) {
+        let listeners = {
+            let mut state = self.0.borrow_mut();
+            state.text = text.to_string();
+            state.annotations = annotations.to_vec();
+            state.listeners.clone()
+        };
+
+        for listener in listeners {
+            listener
+                .call0(&JsValue::UNDEFINED)
+                .expect("inlay hint listener should not throw");
+        }
+    }
+}
+
+fn inlay_hint(text: &str, annotation: &Annotation) -> Object {
+    let (line, column) = position_at(text, annotation.span.end);
+
+    let position = Object::new();
+    set(&position, "lineNumber", &line.into());
+    set(&position, "column", &column.into());
+
+    let hint = Object::new();
+    set(&hint, "label", &annotation.label.as_str().into());
+    set(&hint, "position", &position);
+    set(&hint, "paddingLeft", &true.into());
+    hint
+}
@@ -0,0 +1,47 @@
+use monaco::sys::IRange;
+
+use crate::logic::Span;
+
+/// Convert a byte offset into `text` to a 1-based line and column.
+///
+/// Monaco counts columns in UTF-16 code units, like JavaScript strings do.
+pub fn position_at(text: &str, offset: usize) -> (f64, f64) {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+    let line = before.matches('\n').count() + 1;
+    let column = before[line_start..].encode_utf16().count() + 1;
+
+    (line as f64, column as f64)
+}
+
+/// Convert a 1-based line and column, as Monaco counts them, to a byte offset into `text`.
+pub fn offset_at(text: &str, line: f64, column: f64) -> Option<usize> {
+    let line_start = match line as usize {
+        0 | 1 => 0,
+        line => text.match_indices('\n').nth(line - 2)?.0 + 1,
+    };
+    let line_text = text[line_start..].split('\n').next().unwrap_or_default();
+
+    let mut units = 1;
+    for (i, c) in line_text.char_indices() {
+        if units >= column as usize {
+            return Some(line_start + i);
+        }
+        units += c.len_utf16();
+    }
+    Some(line_start + line_text.len())
+}
+
+/// Convert a span in `text` to a Monaco range.
+pub fn range_of(text: &str, span: Span) -> IRange {
+    let (start_line, start_column) = position_at(text, span.start);
+    let (end_line, end_column) = position_at(text, span.end);
+
+    let range = IRange::default();
+    range.set_start_line_number(start_line);
+    range.set_start_column(start_column);
+    range.set_end_line_number(end_line);
+    range.set_end_column(end_column);
+    range
+}
@@ -0,0 +1,87 @@
+use std::{cell::RefCell, collections::VecDeque, str::FromStr};
+
+use gloo_storage::{LocalStorage, Storage};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// The local storage key the chosen log level is saved under.
+const STORAGE_KEY: &str = "log-level";
+
+/// How many log messages are kept for the debug panel.
+const MAX_ENTRIES: usize = 200;
+
+/// The levels that can be picked, from quietest to most verbose.
+pub const LEVELS: [LevelFilter; 6] = [
+    LevelFilter::Off,
+    LevelFilter::Error,
+    LevelFilter::Warn,
+    LevelFilter::Info,
+    LevelFilter::Debug,
+    LevelFilter::Trace,
+];
+
+thread_local! {
+    static ENTRIES: RefCell<VecDeque<LogEntry>> = RefCell::new(VecDeque::new());
+}
+
+/// A log message that was kept so the UI can show it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LogEntry {
+    pub level: Level,
+    pub message: String,
+}
+
+/// Writes to the browser console, like `console_log` on its own, and keeps the latest
+/// messages for [entries].
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        console_log::log(record);
+        ENTRIES.with(|entries| {
+            let mut entries = entries.borrow_mut();
+            if entries.len() == MAX_ENTRIES {
+                entries.pop_front();
+            }
+            entries.push_back(LogEntry {
+                level: record.level(),
+                message: record.args().to_string(),
+            });
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Logger = Logger;
+
+/// Install the logger with the level chosen in an earlier session, or debug by default.
+pub fn init() {
+    let level = LocalStorage::get::<String>(STORAGE_KEY)
+        .ok()
+        .and_then(|level| LevelFilter::from_str(&level).ok())
+        .unwrap_or(LevelFilter::Debug);
+
+    log::set_logger(&LOGGER).expect("the logger is only installed once");
+    log::set_max_level(level);
+}
+
+/// Change the log level right away and remember it for the next session.
+pub fn set_level(level: LevelFilter) {
+    log::set_max_level(level);
+    if let Err(e) = LocalStorage::set(STORAGE_KEY, level.to_string()) {
+        log::warn!("Could not save log level: {e}");
+    }
+}
+
+/// The latest log messages, oldest first.
+pub fn entries() -> Vec<LogEntry> {
+    ENTRIES.with(|entries| entries.borrow().iter().cloned().collect())
+}
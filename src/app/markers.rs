@@ -0,0 +1,42 @@
+use js_sys::Array;
+use monaco::{
+    api::TextModel,
+    sys::{
+        editor::{set_model_markers, IMarkerData},
+        MarkerSeverity,
+    },
+};
+
+use super::location::position_at;
+use crate::logic::{Diagnostic, Severity};
+
+/// The owner used for all markers we place, so they replace each other.
+const MARKER_OWNER: &str = "game-recipe-calc";
+
+/// Replace the markers on the model with the given diagnostics.
+pub fn set_markers(model: &TextModel, text: &str, diagnostics: &[Diagnostic]) {
+    let markers: Array = diagnostics
+        .iter()
+        .map(|diagnostic| to_marker(text, diagnostic))
+        .collect();
+
+    set_model_markers(model.as_ref(), MARKER_OWNER, &markers);
+}
+
+fn to_marker(text: &str, diagnostic: &Diagnostic) -> IMarkerData {
+    let (start_line, start_column) = position_at(text, diagnostic.span.start);
+    let (end_line, end_column) = position_at(text, diagnostic.span.end);
+
+    let marker = IMarkerData::default();
+    marker.set_severity(match diagnostic.severity {
+        Severity::Error => MarkerSeverity::Error,
+        Severity::Warning => MarkerSeverity::Warning,
+        Severity::Hint => MarkerSeverity::Hint,
+    });
+    marker.set_message(&diagnostic.message);
+    marker.set_start_line_number(start_line);
+    marker.set_start_column(start_column);
+    marker.set_end_line_number(end_line);
+    marker.set_end_column(end_column);
+    marker
+}
@@ -0,0 +1,926 @@
+mod batch;
+mod debug;
+mod decorations;
+mod download;
+mod editor;
+pub mod embed;
+mod gist;
+mod history;
+mod import;
+mod js;
+mod keybindings;
+mod language;
+mod location;
+pub mod logging;
+mod markers;
+mod output;
+mod problems;
+mod progress;
+mod qr;
+mod recent;
+mod recipes;
+pub mod service_worker;
+mod share;
+mod theme;
+mod tree;
+
+use std::{collections::HashSet, rc::Rc};
+
+use gloo_timers::callback::Interval;
+use js_sys::Array;
+use log::warn;
+use monaco::{
+    api::TextModel,
+    sys::editor::{
+        set_theme, IEditorMinimapOptions, IModelContentChangedEvent,
+        IStandaloneEditorConstructionOptions,
+    },
+    yew::{CodeEditor, CodeEditorLink},
+};
+use wasm_bindgen::{prelude::Closure, JsCast};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlSelectElement;
+use yew::prelude::*;
+
+use self::{
+    download::download,
+    keybindings::{EmacsLayer, Keybindings},
+    theme::Theme,
+};
+use crate::logic::{
+    editing::{self, StackSection},
+    migration,
+    progress::Progress,
+    report::Report,
+    Evaluation, Item, ItemStack, Program, Recipe, RecipeSuggestion,
+};
+
+const EXAMPLE_DOCUMENT: &str = include_str!("../logic/parsing/example_input.txt");
+
+/// The name of documents that were not opened from anywhere.
+const UNTITLED_DOCUMENT: &str = "Untitled";
+
+#[function_component(App)]
+pub fn app() -> Html {
+    let shared_document = use_memo(|()| share::document_from_url(), ());
+    // without a link, the document from the last visit is opened again
+    let last_document = use_memo(|()| recent::load().first().cloned(), ());
+    // links and saved documents can be older than the current version of the recipe language
+    let startup_migration = use_memo(
+        |(shared, last)| {
+            let last = (**last).as_ref().map(|document| document.content.as_str());
+            (**shared).as_deref().or(last).and_then(migration::migrate)
+        },
+        (shared_document.clone(), last_document.clone()),
+    );
+
+    let text = use_state(
+        || match (&*startup_migration, &*shared_document, &*last_document) {
+            (Some(migration), _, _) => migration.text.clone(),
+            (None, Some(document), _) => document.clone(),
+            (None, None, Some(document)) => document.content.clone(),
+            (None, None, None) => EXAMPLE_DOCUMENT.to_string(),
+        },
+    );
+
+    // tells the user what was changed when an old document was updated
+    let migration_notice = use_state(|| {
+        let migration = (*startup_migration).as_ref();
+        migration.map(|migration| migration.changes.join(". "))
+    });
+
+    // included documents may have changed since the link was made
+    let pin_notice = use_state(|| {
+        let documents = recent::load();
+        let load = |name: &str| {
+            let document = documents.iter().find(|document| document.name == name)?;
+            Some(document.content.clone())
+        };
+        let pins = share::pins_from_url();
+        let mismatches: Vec<String> = pins.iter().filter_map(|pin| pin.mismatch(&load)).collect();
+        (!mismatches.is_empty()).then(|| mismatches.join(", "))
+    });
+
+    // used to tell documents apart in the recent documents list
+    let document_name = use_state(|| match (&*shared_document, &*last_document) {
+        (Some(_), _) => String::from("Shared link"),
+        (None, Some(document)) => document.name.clone(),
+        (None, None) => String::from(UNTITLED_DOCUMENT),
+    });
+
+    let show_start_screen = use_state(|| shared_document.is_none() && !recent::load().is_empty());
+
+    use_effect_with_deps(
+        |(name, text)| {
+            // there's no need to remember the example before it is changed
+            if name != UNTITLED_DOCUMENT || text != EXAMPLE_DOCUMENT {
+                recent::remember_later(name, text);
+            }
+            || ()
+        },
+        ((*document_name).clone(), (*text).clone()),
+    );
+
+    use_effect_with_deps(
+        |()| {
+            // a change that is still waiting to be saved would be lost when the page is closed
+            let save = Closure::<dyn Fn()>::new(recent::save_pending);
+            // other tabs can save documents that this one includes
+            let reload = Closure::<dyn Fn()>::new(recent::forget_loaded);
+            if let Some(window) = web_sys::window() {
+                for (event, listener) in [("pagehide", &save), ("storage", &reload)] {
+                    let listener = listener.as_ref().unchecked_ref();
+                    if let Err(e) = window.add_event_listener_with_callback(event, listener) {
+                        warn!("Could not listen for {event} events: {e:?}");
+                    }
+                }
+            }
+
+            // the listeners stay registered for as long as the page exists
+            save.forget();
+            reload.forget();
+            || ()
+        },
+        (),
+    );
+
+    let inlay_hints = use_state(language::InlayHints::default);
+
+    let text_model = use_state_eq(|| {
+        language::register();
+        let model = TextModel::create(&text, Some(language::LANGUAGE_ID), None).unwrap();
+        language::register_code_actions(model.clone());
+        language::register_hover_provider(model.clone());
+        inlay_hints.register(model.clone());
+
+        let text = text.clone();
+
+        let model_clone = model.clone();
+        let closure = model.on_did_change_content(move |_: IModelContentChangedEvent| {
+            text.set(model_clone.get_value());
+        });
+
+        // TODO: I can't figure out how to keep it in memory otherwise
+        // perhaps see https://github.com/siku2/rust-monaco/issues/19
+        Box::leak(Box::new(closure));
+
+        model
+    });
+
+    let options = use_state(|| {
+        let minimap_options = IEditorMinimapOptions::default();
+        minimap_options.set_enabled(Some(false));
+
+        let options = IStandaloneEditorConstructionOptions::default();
+        options.set_automatic_layout(Some(true));
+        options.set_folding(Some(false));
+        options.set_line_numbers_min_chars(Some(3.));
+        options.set_minimap(Some(&minimap_options));
+        options.set_scroll_beyond_last_line(Some(false));
+        options.set_theme(Some(Theme::default().monaco_theme()));
+        // options.set_value(Some(include_str!("../logic/parsing/example_input.txt")));
+        options
+    });
+
+    // kept outside of the state so the editor commands always see the current overrides
+    let overrides = use_mut_ref(|| Overrides {
+        progress: progress::load(&document_name),
+        ..Overrides::default()
+    });
+
+    let evaluated = use_state(|| Evaluated::new((*text).clone(), &overrides.borrow()));
+
+    // when turned off, evaluation only happens through the evaluate command
+    let live_evaluation = use_state(|| true);
+
+    {
+        let evaluated = evaluated.clone();
+        let overrides = overrides.clone();
+        use_effect_with_deps(
+            move |(text, live_evaluation)| {
+                if *live_evaluation && evaluated.text != *text {
+                    evaluated.set(Evaluated::new(text.clone(), &overrides.borrow()));
+                }
+                || ()
+            },
+            ((*text).clone(), *live_evaluation),
+        );
+    }
+
+    let on_toggle_live_evaluation = {
+        let live_evaluation = live_evaluation.clone();
+        Callback::from(move |_| live_evaluation.set(!*live_evaluation))
+    };
+
+    let on_toggle_recipe = {
+        let evaluated = evaluated.clone();
+        let overrides = overrides.clone();
+        Callback::from(move |item: Item| {
+            let mut overrides = overrides.borrow_mut();
+            if !overrides.disabled_recipes.remove(&item) {
+                overrides.disabled_recipes.insert(item);
+            }
+            evaluated.set(Evaluated::new(evaluated.text.clone(), &overrides));
+        })
+    };
+
+    let on_toggle_from_scratch = {
+        let evaluated = evaluated.clone();
+        let overrides = overrides.clone();
+        Callback::from(move |_| {
+            let mut overrides = overrides.borrow_mut();
+            overrides.from_scratch = !overrides.from_scratch;
+            evaluated.set(Evaluated::new(evaluated.text.clone(), &overrides));
+        })
+    };
+
+    let on_complete_step = {
+        let evaluated = evaluated.clone();
+        let overrides = overrides.clone();
+        let document_name = document_name.clone();
+        Callback::from(move |step: Recipe| {
+            let mut overrides = overrides.borrow_mut();
+            overrides.progress.completed.push(step);
+            progress::save(&document_name, &overrides.progress);
+            evaluated.set(Evaluated::new(evaluated.text.clone(), &overrides));
+        })
+    };
+
+    let on_undo_step = {
+        let evaluated = evaluated.clone();
+        let overrides = overrides.clone();
+        let document_name = document_name.clone();
+        Callback::from(move |index: usize| {
+            let mut overrides = overrides.borrow_mut();
+            overrides.progress.completed.remove(index);
+            progress::save(&document_name, &overrides.progress);
+            evaluated.set(Evaluated::new(evaluated.text.clone(), &overrides));
+        })
+    };
+
+    let coverage_decorations = use_mut_ref(Array::new);
+
+    {
+        let text_model = text_model.clone();
+        let inlay_hints = inlay_hints.clone();
+        use_effect_with_deps(
+            move |evaluated| {
+                let Evaluated {
+                    text, evaluation, ..
+                } = evaluated;
+                markers::set_markers(&text_model, text, &evaluation.diagnostics);
+                inlay_hints.update(text, &evaluation.annotations);
+                decorations::set_coverage(
+                    &text_model,
+                    &mut coverage_decorations.borrow_mut(),
+                    text,
+                    &evaluation.coverage,
+                );
+                || ()
+            },
+            (*evaluated).clone(),
+        );
+    }
+
+    let theme = use_reducer(Theme::default);
+
+    use_effect_with_deps(
+        |theme| {
+            set_theme(theme.monaco_theme());
+            || ()
+        },
+        *theme,
+    );
+
+    let keybindings = use_state(Keybindings::load);
+    let emacs_layer = use_mut_ref(|| None::<EmacsLayer>);
+
+    {
+        let emacs_layer = emacs_layer.clone();
+        use_effect_with_deps(
+            move |keybindings| {
+                if let Some(layer) = &*emacs_layer.borrow() {
+                    layer.set_enabled(*keybindings == Keybindings::Emacs);
+                }
+                keybindings.save();
+                || ()
+            },
+            *keybindings,
+        );
+    }
+
+    let on_change_keybindings = {
+        let keybindings = keybindings.clone();
+        Callback::from(move |e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            if let Some(chosen) = Keybindings::from_id(&select.value()) {
+                keybindings.set(chosen);
+            }
+        })
+    };
+
+    let on_share_link = {
+        let text = text.clone();
+        Callback::from(move |_| {
+            share::share("Crafting plan", None, Some(&share_link(&text)));
+        })
+    };
+
+    let on_share_plan = {
+        let evaluation = evaluated.evaluation.clone();
+        Callback::from(move |_| {
+            if let Ok(report) = &evaluation.report {
+                share::share("Crafting plan", Some(&report.to_string()), None);
+            }
+        })
+    };
+
+    let qr_code_url = use_state(|| None::<String>);
+
+    let on_show_qr_code = {
+        let text = text.clone();
+        let qr_code_url = qr_code_url.clone();
+        Callback::from(move |_| qr_code_url.set(Some(share_link(&text))))
+    };
+
+    let on_close_qr_code = {
+        let qr_code_url = qr_code_url.clone();
+        Callback::from(move |()| qr_code_url.set(None))
+    };
+
+    let show_gist_popup = use_state(|| false);
+
+    let on_open_gist_popup = {
+        let show_gist_popup = show_gist_popup.clone();
+        Callback::from(move |_| show_gist_popup.set(true))
+    };
+
+    let on_close_gist_popup = {
+        let show_gist_popup = show_gist_popup.clone();
+        Callback::from(move |()| show_gist_popup.set(false))
+    };
+
+    let on_export_html = {
+        let evaluation = evaluated.evaluation.clone();
+        Callback::from(move |_| {
+            if let Ok(report) = &evaluation.report {
+                download("report.html", "text/html", &report.to_html());
+            }
+        })
+    };
+
+    let editor_link = use_mut_ref(|| None::<CodeEditorLink>);
+
+    // switches to another document, keeping the old one on the undo stack
+    let open_document = {
+        let text_model = text_model.clone();
+        let editor_link = editor_link.clone();
+        let document_name = document_name.clone();
+        let migration_notice = migration_notice.clone();
+        let pin_notice = pin_notice.clone();
+        let overrides = overrides.clone();
+        Callback::from(move |(name, content): (String, String)| {
+            if let Some(link) = &*editor_link.borrow() {
+                // the recipes of another document have nothing to do with these
+                overrides.borrow_mut().disabled_recipes.clear();
+                overrides.borrow_mut().progress = progress::load(&name);
+
+                let content = match migration::migrate(&content) {
+                    Some(migration) => {
+                        migration_notice.set(Some(migration.changes.join(". ")));
+                        migration.text
+                    }
+                    None => {
+                        migration_notice.set(None);
+                        content
+                    }
+                };
+
+                pin_notice.set(None);
+                document_name.set(name);
+                link.with_editor(|editor| {
+                    editor::replace_document(editor.as_ref(), &text_model.get_value(), content)
+                });
+            }
+        })
+    };
+
+    let on_dismiss_migration_notice = {
+        let migration_notice = migration_notice.clone();
+        Callback::from(move |_| migration_notice.set(None))
+    };
+
+    let on_dismiss_pin_notice = {
+        let pin_notice = pin_notice.clone();
+        Callback::from(move |_| pin_notice.set(None))
+    };
+
+    let on_load_gist = open_document.reform(|(id, content)| (format!("Gist {id}"), content));
+
+    let on_open_start_screen = {
+        let show_start_screen = show_start_screen.clone();
+        Callback::from(move |_| {
+            // the list should include the latest changes to this document
+            recent::save_pending();
+            show_start_screen.set(true);
+        })
+    };
+
+    let on_close_start_screen = {
+        let show_start_screen = show_start_screen.clone();
+        Callback::from(move |()| show_start_screen.set(false))
+    };
+
+    let on_open_recent = {
+        let open_document = open_document.clone();
+        let on_close_start_screen = on_close_start_screen.clone();
+        Callback::from(move |document: recent::RecentDocument| {
+            open_document.emit((document.name, document.content));
+            on_close_start_screen.emit(());
+        })
+    };
+
+    let on_new_document = {
+        let open_document = open_document.clone();
+        let on_close_start_screen = on_close_start_screen.clone();
+        Callback::from(move |()| {
+            open_document.emit((UNTITLED_DOCUMENT.to_string(), EXAMPLE_DOCUMENT.to_string()));
+            on_close_start_screen.emit(());
+        })
+    };
+
+    {
+        let text_model = text_model.clone();
+        use_effect_with_deps(
+            move |name: &String| {
+                let name = name.clone();
+                let interval = Interval::new(history::SNAPSHOT_INTERVAL, move || {
+                    let name = name.clone();
+                    let content = text_model.get_value();
+                    spawn_local(async move {
+                        if let Err(e) = history::save_snapshot(&name, &content).await {
+                            warn!("Could not save a snapshot of the document: {e:?}");
+                        }
+                    });
+                });
+                move || drop(interval)
+            },
+            (*document_name).clone(),
+        );
+    }
+
+    let show_shopping_list = use_state(|| false);
+
+    let on_open_shopping_list = {
+        let show_shopping_list = show_shopping_list.clone();
+        Callback::from(move |_| show_shopping_list.set(true))
+    };
+
+    let on_close_shopping_list = {
+        let show_shopping_list = show_shopping_list.clone();
+        Callback::from(move |()| show_shopping_list.set(false))
+    };
+
+    let show_import = use_state(|| false);
+
+    let on_open_import = {
+        let show_import = show_import.clone();
+        Callback::from(move |_| show_import.set(true))
+    };
+
+    let on_close_import = {
+        let show_import = show_import.clone();
+        Callback::from(move |()| show_import.set(false))
+    };
+
+    let on_import = {
+        let text_model = text_model.clone();
+        let editor_link = editor_link.clone();
+        let on_close_import = on_close_import.clone();
+        Callback::from(move |(section, stacks): (StackSection, Vec<ItemStack>)| {
+            let text = text_model.get_value();
+            let Ok(program) = Program::parse_from_string(&text) else {
+                return;
+            };
+            let Some(edit) = editing::insert_stacks(&program, &text, section, &stacks) else {
+                return;
+            };
+
+            if let Some(link) = &*editor_link.borrow() {
+                link.with_editor(|editor| editor::apply_edits(editor.as_ref(), &text, &[edit]));
+            }
+            on_close_import.emit(());
+        })
+    };
+
+    let on_add_recipe = {
+        let text_model = text_model.clone();
+        let editor_link = editor_link.clone();
+        Callback::from(move |recipe: Recipe| {
+            let text = text_model.get_value();
+            let Ok(program) = Program::parse_from_string(&text) else {
+                return;
+            };
+            let Some(edit) = editing::add_recipe(&program, &text, &recipe) else {
+                return;
+            };
+
+            if let Some(link) = &*editor_link.borrow() {
+                link.with_editor(|editor| editor::apply_edits(editor.as_ref(), &text, &[edit]));
+            }
+        })
+    };
+
+    let on_select_item = {
+        let text_model = text_model.clone();
+        let editor_link = editor_link.clone();
+        Callback::from(move |item: Item| {
+            let text = text_model.get_value();
+            let Ok(program) = Program::parse_from_string(&text) else {
+                return;
+            };
+            let Some(span) = editing::definition(&program, &item) else {
+                return;
+            };
+
+            if let Some(link) = &*editor_link.borrow() {
+                link.with_editor(|editor| editor::go_to(editor, &text, span));
+            }
+        })
+    };
+
+    let show_history = use_state(|| false);
+
+    let on_open_history = {
+        let show_history = show_history.clone();
+        Callback::from(move |_| show_history.set(true))
+    };
+
+    let on_close_history = {
+        let show_history = show_history.clone();
+        Callback::from(move |()| show_history.set(false))
+    };
+
+    let on_restore_snapshot = {
+        let name = (*document_name).clone();
+        let on_close_history = on_close_history.clone();
+        Callback::from(move |content| {
+            open_document.emit((name.clone(), content));
+            on_close_history.emit(());
+        })
+    };
+
+    let on_editor_created = {
+        let text_model = text_model.clone();
+        let editor_link = editor_link.clone();
+        let evaluated = evaluated.setter();
+        let theme = theme.dispatcher();
+        let initial_keybindings = *keybindings;
+        let overrides = overrides.clone();
+        Callback::from(move |link: CodeEditorLink| {
+            let commands = {
+                let evaluate_model = (*text_model).clone();
+                let evaluated = evaluated.clone();
+                let evaluate_overrides = overrides.clone();
+                let theme = theme.clone();
+                let export = |file_name: &'static str,
+                              mime_type: &'static str,
+                              render: fn(&Report) -> String| {
+                    let model = (*text_model).clone();
+                    let overrides = overrides.clone();
+                    Callback::from(move |()| {
+                        let evaluation = transform_text(&model.get_value(), &overrides.borrow());
+                        if let Ok(report) = evaluation.report {
+                            download(file_name, mime_type, &render(&report));
+                        }
+                    })
+                };
+
+                editor::Commands {
+                    evaluate: Callback::from(move |()| {
+                        evaluated.set(Evaluated::new(
+                            evaluate_model.get_value(),
+                            &evaluate_overrides.borrow(),
+                        ));
+                    }),
+                    export: export("report.txt", "text/plain", |report| report.to_string()),
+                    export_checklist: export(
+                        "checklist.md",
+                        "text/markdown",
+                        Report::to_markdown_checklist,
+                    ),
+                    export_html: export("report.html", "text/html", Report::to_html),
+                    toggle_theme: Callback::from(move |()| theme.dispatch(())),
+                }
+            };
+
+            link.with_editor(|editor| {
+                editor::register_actions(editor, &text_model, commands);
+
+                let layer = EmacsLayer::register(editor);
+                layer.set_enabled(initial_keybindings == Keybindings::Emacs);
+                *emacs_layer.borrow_mut() = Some(layer);
+            });
+            *editor_link.borrow_mut() = Some(link);
+        })
+    };
+
+    let on_select_problem = {
+        let text = evaluated.text.clone();
+        Callback::from(move |span| {
+            if let Some(link) = &*editor_link.borrow() {
+                link.with_editor(|editor| editor::go_to(editor, &text, span));
+            }
+        })
+    };
+
+    html! {
+        <div class={classes!("main-container", theme.class())}>
+            <CodeEditor classes="input" options={(*options).clone()} model={(*text_model).clone()} {on_editor_created} />
+            <div class="side-panel">
+                <div class="toolbar">
+                    <label>
+                        <input type="checkbox" checked={*live_evaluation} onchange={on_toggle_live_evaluation} />
+                        { "Evaluate while typing" }
+                    </label>
+                    <label title="Also show what the needs take without anything from the have section">
+                        <input
+                            type="checkbox"
+                            checked={evaluated.overrides.from_scratch}
+                            onchange={on_toggle_from_scratch}
+                        />
+                        { "From scratch" }
+                    </label>
+                    <label>
+                        { "Keybindings " }
+                        <select onchange={on_change_keybindings}>
+                            { for Keybindings::ALL.into_iter().map(|k| html! {
+                                <option value={k.id()} selected={*keybindings == k}>{ k.label() }</option>
+                            }) }
+                        </select>
+                    </label>
+                    <button onclick={on_export_html} disabled={evaluated.evaluation.report.is_err()}>
+                        { "Export HTML" }
+                    </button>
+                    <button onclick={on_share_link}>{ "Share link" }</button>
+                    <button onclick={on_open_start_screen}>{ "Recent" }</button>
+                    <button onclick={on_new_document.reform(|_| ())}>{ "Reset to example" }</button>
+                    <button onclick={on_open_history}>{ "History" }</button>
+                    <button onclick={on_open_import}>{ "Import" }</button>
+                    <button onclick={on_open_shopping_list}>{ "Shopping list" }</button>
+                    <button onclick={on_open_gist_popup}>{ "Gist" }</button>
+                    <button onclick={on_show_qr_code}>{ "QR code" }</button>
+                    <button onclick={on_share_plan} disabled={evaluated.evaluation.report.is_err()}>
+                        { "Share plan" }
+                    </button>
+                    if !*live_evaluation && evaluated.text != *text {
+                        <span class="outdated">{ "Outdated, press Ctrl+Enter to evaluate" }</span>
+                    }
+                </div>
+                if let Some(notice) = &*migration_notice {
+                    <div class="notice">
+                        { format!("This document was made for an older version. {notice}.") }
+                        <button onclick={on_dismiss_migration_notice}>{ "Dismiss" }</button>
+                    </div>
+                }
+                if let Some(notice) = &*pin_notice {
+                    <div class="notice">
+                        { format!("The included documents changed since this link was made, so the plan may be different: {notice}.") }
+                        <button onclick={on_dismiss_pin_notice}>{ "Dismiss" }</button>
+                    </div>
+                }
+                <output::Output
+                    evaluation={evaluated.evaluation.clone()}
+                    from_scratch={evaluated.from_scratch.clone()}
+                    suggestions={evaluated.suggestions.clone()}
+                    onaddrecipe={on_add_recipe}
+                    onselectitem={on_select_item}
+                    completed_steps={evaluated.overrides.progress.completed.clone()}
+                    oncompletestep={on_complete_step}
+                    onundostep={on_undo_step}
+                />
+                <recipes::RecipeToggles
+                    text={evaluated.text.clone()}
+                    disabled={evaluated.overrides.disabled_recipes.clone()}
+                    ontoggle={on_toggle_recipe}
+                />
+                <problems::Problems
+                    text={evaluated.text.clone()}
+                    diagnostics={evaluated.evaluation.diagnostics.clone()}
+                    onselect={on_select_problem}
+                />
+                <debug::DebugPanel
+                    evaluation={evaluated.evaluation.clone()}
+                    timings={evaluated.timings}
+                />
+                <div class="status-bar">
+                    { format!(
+                        "Parsed in {:.1} ms · evaluated in {:.1} ms · {} recipes",
+                        evaluated.timings.parse_ms,
+                        evaluated.timings.evaluate_ms,
+                        evaluated.evaluation.statistics.recipes,
+                    ) }
+                </div>
+            </div>
+            if *show_start_screen {
+                <recent::StartScreen
+                    onopen={on_open_recent}
+                    onnew={on_new_document}
+                    onclose={on_close_start_screen}
+                />
+            }
+            if *show_history {
+                <history::HistoryPopup
+                    name={(*document_name).clone()}
+                    text={(*text).clone()}
+                    onrestore={on_restore_snapshot}
+                    onclose={on_close_history}
+                />
+            }
+            if *show_shopping_list {
+                <batch::ShoppingListPopup
+                    text={(*text).clone()}
+                    onclose={on_close_shopping_list}
+                />
+            }
+            if *show_import {
+                <import::ImportPopup onimport={on_import} onclose={on_close_import} />
+            }
+            if *show_gist_popup {
+                <gist::GistPopup
+                    text={(*text).clone()}
+                    onload={on_load_gist}
+                    onclose={on_close_gist_popup}
+                />
+            }
+            if let Some(url) = &*qr_code_url {
+                <qr::QrCodePopup data={url.clone()} onclose={on_close_qr_code} />
+            }
+        </div>
+    }
+}
+
+/// Changes to how a document is evaluated that are made in the app instead of in the
+/// document itself.
+#[derive(PartialEq, Clone, Default)]
+struct Overrides {
+    /// The items whose recipes are turned off in the recipe list.
+    disabled_recipes: HashSet<Item>,
+    /// Also evaluate the document as if the have section were empty.
+    from_scratch: bool,
+    /// The crafting steps that were checked off for this document.
+    progress: Progress,
+}
+
+/// An evaluation together with the text and overrides it was computed from.
+#[derive(PartialEq, Clone)]
+struct Evaluated {
+    text: String,
+    overrides: Overrides,
+    evaluation: Rc<Evaluation>,
+    /// The evaluation without the have section, if [Overrides::from_scratch] is set.
+    from_scratch: Option<Rc<Evaluation>>,
+    /// Recipes for the missing items from other saved documents.
+    suggestions: Rc<Vec<RecipeSuggestion>>,
+    timings: Timings,
+}
+
+impl Evaluated {
+    fn new(text: String, overrides: &Overrides) -> Self {
+        let transformed = transform_text_timed(&text, overrides);
+
+        Self {
+            text,
+            overrides: overrides.clone(),
+            evaluation: Rc::new(transformed.evaluation),
+            from_scratch: transformed.from_scratch.map(Rc::new),
+            suggestions: Rc::new(transformed.suggestions),
+            timings: transformed.timings,
+        }
+    }
+}
+
+/// Everything that is worked out from a document, see [transform_text_timed].
+struct Transformed {
+    evaluation: Evaluation,
+    from_scratch: Option<Evaluation>,
+    suggestions: Vec<RecipeSuggestion>,
+    timings: Timings,
+}
+
+/// How long the steps of turning a document into an evaluation took, in milliseconds.
+#[derive(PartialEq, Clone, Copy, Default)]
+pub struct Timings {
+    /// Parsing the document and resolving its includes.
+    pub parse_ms: f64,
+    pub evaluate_ms: f64,
+}
+
+impl Timings {
+    pub fn total_ms(self) -> f64 {
+        self.parse_ms + self.evaluate_ms
+    }
+}
+
+/// The current time in milliseconds.
+///
+/// std::time::Instant isn't available on wasm, so this uses the browser's clock.
+fn now() -> f64 {
+    let performance = web_sys::window().and_then(|window| window.performance());
+    performance.map_or(0.0, |p| p.now())
+}
+
+/// A link that opens `text`, pinning the saved documents it includes.
+fn share_link(text: &str) -> String {
+    let documents = recent::load();
+    let pins = Program::parse_from_string(text).map(|program| {
+        program.include_pins(|name| {
+            let document = documents.iter().find(|document| document.name == name)?;
+            Some(document.content.clone())
+        })
+    });
+    share::share_url(text, &pins.unwrap_or_default())
+}
+
+fn transform_text(text: &str, overrides: &Overrides) -> Evaluation {
+    transform_text_timed(text, overrides).evaluation
+}
+
+/// Evaluate `text` with `overrides`, also finding out how long it took and recipes from other
+/// saved documents for the items that are missing.
+fn transform_text_timed(text: &str, overrides: &Overrides) -> Transformed {
+    let start = now();
+    // lines that can't be read are marked in the editor, the rest is still evaluated
+    let (mut parsed, line_errors) = match Program::parse_leniently(text) {
+        Ok(v) => v,
+        Err(e) => {
+            let evaluation = Evaluation {
+                diagnostics: vec![e.clone()],
+                ..Evaluation::error(format!("Error: {}", e.message))
+            };
+            let timings = Timings {
+                parse_ms: now() - start,
+                evaluate_ms: 0.0,
+            };
+            return Transformed {
+                evaluation,
+                from_scratch: None,
+                suggestions: vec![],
+                timings,
+            };
+        }
+    };
+
+    // included documents are looked up among the saved ones
+    let documents = recent::load();
+    let include_errors = parsed.resolve_includes(|name| {
+        let document = documents.iter().find(|document| document.name == name)?;
+        Some(document.content.clone())
+    });
+    parsed.disable_recipes(&overrides.disabled_recipes);
+    overrides.progress.apply(&mut parsed);
+    let parsed_at = now();
+
+    // debug builds check the evaluator for lost items, which shows up in the debug panel
+    let mut evaluation = if cfg!(debug_assertions) {
+        parsed.evaluate_audited()
+    } else {
+        parsed.evaluate()
+    };
+
+    evaluation
+        .diagnostics
+        .splice(0..0, line_errors.into_iter().chain(include_errors));
+
+    let timings = Timings {
+        parse_ms: parsed_at - start,
+        evaluate_ms: now() - parsed_at,
+    };
+
+    let missing = evaluation
+        .report
+        .as_ref()
+        .map(|report| &report.missing_items[..]);
+    let recipes = recent::recipes(&documents);
+    let mut suggestions = parsed.suggest_recipes(
+        missing.unwrap_or_default(),
+        documents
+            .iter()
+            .zip(&recipes)
+            .map(|(document, recipes)| (document.name.as_str(), &recipes[..])),
+    );
+    // turned off recipes are missing on purpose
+    let disabled = &overrides.disabled_recipes;
+    suggestions.retain(|suggestion| !disabled.contains(&suggestion.recipe.output.item));
+
+    let from_scratch = overrides
+        .from_scratch
+        .then(|| parsed.evaluate_from_scratch(&evaluation));
+
+    Transformed {
+        evaluation,
+        from_scratch,
+        suggestions,
+        timings,
+    }
+}
@@ -0,0 +1,509 @@
+use std::rc::Rc;
+
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+use crate::logic::{
+    analysis::{Analysis, Arbitrage, CraftingLoop},
+    options::NumberFormat,
+    report::{Contribution, DeadEndByproduct, Goal, ItemGroup, Report},
+    Evaluation, Item, ItemStack, Recipe, RecipeSuggestion,
+};
+
+use super::tree::CraftingTree;
+
+#[derive(Properties, PartialEq)]
+pub struct OutputProps {
+    pub evaluation: Rc<Evaluation>,
+    /// The evaluation as if the have section were empty, to show next to the real one.
+    #[prop_or_default]
+    pub from_scratch: Option<Rc<Evaluation>>,
+    /// Recipes for missing items that other documents know about.
+    #[prop_or_default]
+    pub suggestions: Rc<Vec<RecipeSuggestion>>,
+    /// Called with a suggested recipe to add it to the document.
+    #[prop_or_default]
+    pub onaddrecipe: Callback<Recipe>,
+    /// Called with an item in the tree to show where it is defined.
+    #[prop_or_default]
+    pub onselectitem: Callback<Item>,
+    /// The crafting steps that were checked off, which are left out of the evaluation.
+    #[prop_or_default]
+    pub completed_steps: Vec<Recipe>,
+    /// Called with a step when it is checked off.
+    #[prop_or_default]
+    pub oncompletestep: Callback<Recipe>,
+    /// Called with the index of a completed step when it is unchecked again.
+    #[prop_or_default]
+    pub onundostep: Callback<usize>,
+    /// The tab that is shown first.
+    #[prop_or_default]
+    pub initial_tab: Tab,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum Tab {
+    #[default]
+    Summary,
+    Steps,
+    Tree,
+    Graph,
+    Analysis,
+    Raw,
+}
+
+impl Tab {
+    const ALL: [Tab; 6] = [
+        Tab::Summary,
+        Tab::Steps,
+        Tab::Tree,
+        Tab::Graph,
+        Tab::Analysis,
+        Tab::Raw,
+    ];
+
+    /// The name used to pick a tab in the URL.
+    fn id(self) -> &'static str {
+        match self {
+            Tab::Summary => "summary",
+            Tab::Steps => "steps",
+            Tab::Tree => "tree",
+            Tab::Graph => "graph",
+            Tab::Analysis => "analysis",
+            Tab::Raw => "raw",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|tab| tab.id() == id)
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Tab::Summary => "Summary",
+            Tab::Steps => "Steps",
+            Tab::Tree => "Tree",
+            Tab::Graph => "Graph",
+            Tab::Analysis => "Analysis",
+            Tab::Raw => "Raw text",
+        }
+    }
+}
+
+/// The output panel, showing the evaluation report in several tabs.
+#[function_component(Output)]
+pub fn output(props: &OutputProps) -> Html {
+    let tab = use_state(|| props.initial_tab);
+    let filter = use_state(String::new);
+    let by_category = use_state(|| false);
+
+    let on_filter_input = {
+        let filter = filter.clone();
+        Callback::from(move |e: InputEvent| {
+            filter.set(e.target_unchecked_into::<HtmlInputElement>().value());
+        })
+    };
+
+    let report = match &props.evaluation.report {
+        Ok(report) => report,
+        Err(message) => return html! { <pre class="output">{ message }</pre> },
+    };
+
+    let filtering = !filter.trim().is_empty();
+    let filtered;
+    let report = if filtering {
+        filtered = report.filtered(filter.trim());
+        &filtered
+    } else {
+        report
+    };
+
+    let filtered_from_scratch;
+    let from_scratch = match props.from_scratch.as_ref().map(|e| &e.report) {
+        Some(Ok(report)) if filtering => {
+            filtered_from_scratch = report.filtered(filter.trim());
+            Some(&filtered_from_scratch)
+        }
+        Some(Ok(report)) => Some(report),
+        _ => None,
+    };
+
+    let tabs = Tab::ALL.into_iter().map(|t| {
+        let onclick = {
+            let tab = tab.clone();
+            Callback::from(move |_| tab.set(t))
+        };
+
+        html! {
+            <button class={classes!("tab", (*tab == t).then_some("active"))} {onclick}>
+                { t.label() }
+            </button>
+        }
+    });
+
+    let format = props.evaluation.options.number_format;
+    let threshold = props.evaluation.options.tree_threshold;
+    let content = match *tab {
+        Tab::Summary => summary(report, from_scratch, format, filtering, by_category, props),
+        Tab::Steps => steps(report, format, props),
+        Tab::Tree => html! {
+            <CraftingTree
+                nodes={report.crafting_tree.clone()}
+                {format}
+                {threshold}
+                onselect={props.onselectitem.clone()}
+            />
+        },
+        Tab::Graph => html! { <pre>{ report.to_dot() }</pre> },
+        Tab::Analysis => analysis(&props.evaluation.analysis),
+        Tab::Raw => html! { <pre>{ report.to_string() }</pre> },
+    };
+
+    html! {
+        <div class="output">
+            <nav class="tabs">{ for tabs }</nav>
+            <input
+                class="filter"
+                type="search"
+                placeholder="Filter by item"
+                value={(*filter).clone()}
+                oninput={on_filter_input}
+            />
+            <div class="tab-content">{ content }</div>
+        </div>
+    }
+}
+
+/// A list entry for `stack` with its count in `format`, and the exact count on hover.
+fn stack_entry(stack: &ItemStack, format: NumberFormat, class: &'static str) -> Html {
+    html! { <li {class} title={stack.to_string()}>{ format.stack(stack) }</li> }
+}
+
+/// The missing and leftover items, with recipes from other documents for the missing ones
+/// and the raw items it all takes `from_scratch`. While `filtering`, lists without matches
+/// are left out instead of saying that nothing is missing.
+fn summary(
+    report: &Report,
+    from_scratch: Option<&Report>,
+    format: NumberFormat,
+    filtering: bool,
+    by_category: UseStateHandle<bool>,
+    props: &OutputProps,
+) -> Html {
+    let named = |groups: &[ItemGroup]| groups.iter().any(|group| group.name.is_some());
+    let has_sources = named(&report.missing_by_source);
+    let has_categories = named(&report.missing_by_category);
+    let use_categories = has_categories && (*by_category || !has_sources);
+
+    let on_change_grouping = {
+        let by_category = by_category.clone();
+        Callback::from(move |e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            by_category.set(select.value() == "category");
+        })
+    };
+
+    let groups = if use_categories {
+        &report.missing_by_category
+    } else {
+        &report.missing_by_source
+    };
+    let group = |(i, group): (usize, &ItemGroup)| {
+        let heading = match &group.name {
+            Some(name) if use_categories => {
+                Some(format!("{name} ({})", format.count(group.total())))
+            }
+            Some(name) => Some(name.clone()),
+            None if i > 0 && use_categories => {
+                Some(format!("Other ({})", format.count(group.total())))
+            }
+            None if i > 0 => Some("Elsewhere".to_string()),
+            None => None,
+        };
+
+        html! {
+            <>
+                if let Some(heading) = heading {
+                    <h4>{ heading }</h4>
+                }
+                <ul>
+                    { for group.items.iter().map(|stack| stack_entry(stack, format, "status-missing")) }
+                </ul>
+            </>
+        }
+    };
+
+    let operations = report.crafting_operations();
+    let goals = report.goals();
+
+    html! {
+        <>
+            if operations > 0 {
+                <p>
+                    { format!(
+                        "Crafting operations: {}, using {} different recipes",
+                        format.count(operations),
+                        report.recipes_used(),
+                    ) }
+                </p>
+            }
+
+            if !report.dropped_needs.is_empty() {
+                <h3>{ "Dropped needs" }</h3>
+                <ul>
+                    { for report.dropped_needs.iter().map(|stack| stack_entry(stack, format, "status-missing")) }
+                </ul>
+            }
+
+            if report.missing_items.is_empty() {
+                if !filtering {
+                    <p>{ "You have all the required items!" }</p>
+                }
+            } else {
+                <h3>{ "Missing items" }</h3>
+                if has_sources && has_categories {
+                    <select onchange={on_change_grouping}>
+                        <option value="source" selected={!use_categories}>{ "By source" }</option>
+                        <option value="category" selected={use_categories}>{ "By category" }</option>
+                    </select>
+                }
+                { for groups.iter().enumerate().map(group) }
+            }
+
+            if !props.suggestions.is_empty() {
+                <h3>{ "Recipes in other documents" }</h3>
+                <ul>
+                    { for props.suggestions.iter().map(|suggestion| suggested_recipe(suggestion, &props.onaddrecipe)) }
+                </ul>
+            }
+
+            if report.leftover_items.is_empty() {
+                if !filtering {
+                    <p>{ "No items are left over after crafting." }</p>
+                }
+            } else {
+                <h3>{ "Leftover items after crafting" }</h3>
+                <ul>
+                    { for report.leftover_items.iter().map(|stack| stack_entry(stack, format, "status-leftover")) }
+                </ul>
+            }
+
+            if !report.dead_end_byproducts.is_empty() {
+                <h3>{ "Byproducts nothing uses" }</h3>
+                <ul>
+                    { for report.dead_end_byproducts.iter().map(|byproduct| dead_end_entry(byproduct, format)) }
+                </ul>
+            }
+
+            if goals.len() > 1 {
+                <h3>{ "Per goal" }</h3>
+                { for goals.iter().map(|goal| goal_entry(goal, format)) }
+            }
+
+            if !report.contributions.is_empty() {
+                <h3>{ "Per player" }</h3>
+                { for report.contributions.iter().map(|contribution| contribution_entry(contribution, format)) }
+            }
+
+            if let Some(from_scratch) = from_scratch {
+                <h3>{ "From scratch" }</h3>
+                if from_scratch.missing_items.is_empty() {
+                    if !filtering {
+                        <p>{ "Nothing has to be gathered." }</p>
+                    }
+                } else {
+                    <p>
+                        { format!(
+                            "Without the have section, this takes {} crafting operations and these raw items:",
+                            format.count(from_scratch.crafting_operations()),
+                        ) }
+                    </p>
+                    <ul>
+                        { for from_scratch.missing_items.iter().map(|stack| stack_entry(stack, format, "status-missing")) }
+                    </ul>
+                }
+            }
+        </>
+    }
+}
+
+/// What a single need takes, split into what is taken from the inventory and what has to be
+/// gathered.
+fn goal_entry(goal: &Goal, format: NumberFormat) -> Html {
+    html! {
+        <>
+            <h4 title={goal.stack.to_string()}>{ format.stack(&goal.stack) }</h4>
+            if goal.from_inventory.is_empty() && goal.missing.is_empty() {
+                <p>{ "Takes nothing." }</p>
+            }
+            <ul>
+                { for goal.from_inventory.iter().map(|stack| stack_entry(stack, format, "status-full")) }
+                { for goal.missing.iter().map(|stack| stack_entry(stack, format, "status-missing")) }
+            </ul>
+        </>
+    }
+}
+
+/// The items of a player's inventory that the plan uses up.
+fn contribution_entry(contribution: &Contribution, format: NumberFormat) -> Html {
+    html! {
+        <>
+            <h4>{ &contribution.player }</h4>
+            if contribution.consumed.is_empty() {
+                <p>{ "Nothing is used." }</p>
+            }
+            <ul>
+                { for contribution.consumed.iter().map(|stack| stack_entry(stack, format, "status-full")) }
+            </ul>
+        </>
+    }
+}
+
+/// A byproduct that piles up, with how fast it does so.
+fn dead_end_entry(byproduct: &DeadEndByproduct, format: NumberFormat) -> Html {
+    html! {
+        <li class="status-leftover" title={byproduct.stack.to_string()}>
+            { format.stack(&byproduct.stack) }
+            <span class="details">
+                { format!(" ({} every time {} is made)", format.count(byproduct.per_run), byproduct.recipe) }
+            </span>
+        </li>
+    }
+}
+
+fn suggested_recipe(suggestion: &RecipeSuggestion, onaddrecipe: &Callback<Recipe>) -> Html {
+    let recipe = suggestion.recipe.clone();
+    let onclick = onaddrecipe.reform(move |_| recipe.clone());
+
+    html! {
+        <li title={suggestion.recipe.to_string()}>
+            { format!(
+                "A recipe for {} exists in '{}' ",
+                suggestion.recipe.output.item, suggestion.document
+            ) }
+            <button {onclick}>{ "Add it" }</button>
+        </li>
+    }
+}
+
+/// The recipes to run in order, which can be checked off once they are done, followed by the
+/// steps that were done already.
+fn steps(report: &Report, format: NumberFormat, props: &OutputProps) -> Html {
+    let step = |recipe: &Recipe| {
+        let onchange = {
+            let recipe = recipe.clone();
+            props.oncompletestep.reform(move |_| recipe.clone())
+        };
+        html! {
+            <li title={recipe.to_string()}>
+                <label>
+                    <input type="checkbox" checked={false} {onchange} />
+                    { format.recipe(recipe) }
+                </label>
+                if let Some(doc) = &recipe.doc {
+                    <div class="doc">{ doc.to_string() }</div>
+                }
+            </li>
+        }
+    };
+    let completed = props.completed_steps.iter().enumerate().map(|(i, recipe)| {
+        html! {
+            <li title={recipe.to_string()}>
+                <label>
+                    <input type="checkbox" checked={true} onchange={props.onundostep.reform(move |_| i)} />
+                    { format.recipe(recipe) }
+                </label>
+            </li>
+        }
+    });
+
+    html! {
+        <>
+            if report.executed_recipes.is_empty() {
+                <p>{ "No crafting needed." }</p>
+            } else {
+                <ol class="steps">{ for report.executed_recipes.iter().map(step) }</ol>
+            }
+            if !props.completed_steps.is_empty() {
+                <h3>{ "Done" }</h3>
+                <ul class="steps done">{ for completed }</ul>
+            }
+        </>
+    }
+}
+
+fn analysis(analysis: &Analysis) -> Html {
+    let chain = analysis
+        .longest_chain
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(" → ");
+
+    html! {
+        <>
+            <h3>{ "Raw items" }</h3>
+            if analysis.raw_items.is_empty() {
+                <p>{ "Every item can be crafted." }</p>
+            } else {
+                <ul>
+                    { for analysis.raw_items.iter().map(|item| html! { <li>{ item.to_string() }</li> }) }
+                </ul>
+            }
+
+            <h3>{ "Crafting depth" }</h3>
+            if analysis.depths.is_empty() {
+                <p>{ "There are no recipes." }</p>
+            } else {
+                <ul>
+                    { for analysis.depths.iter().map(|(item, depth)| html! { <li>{ format!("{item}: {depth}") }</li> }) }
+                </ul>
+            }
+
+            if !chain.is_empty() {
+                <h3>{ "Longest chain" }</h3>
+                <p>{ chain }</p>
+            }
+
+            if !analysis.loops.is_empty() {
+                <h3>{ "Loops" }</h3>
+                <ul>{ for analysis.loops.iter().map(crafting_loop) }</ul>
+            }
+
+            if !analysis.arbitrages.is_empty() {
+                <h3>{ "Arbitrage" }</h3>
+                <ul>{ for analysis.arbitrages.iter().map(arbitrage) }</ul>
+            }
+        </>
+    }
+}
+
+fn crafting_loop(crafting_loop: &CraftingLoop) -> Html {
+    let first = &crafting_loop.items[0];
+    let items = crafting_loop
+        .items
+        .iter()
+        .chain([first])
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(" → ");
+
+    html! {
+        <li class={crafting_loop.is_net_positive().then_some("status-missing")}>
+            { format!("{items}: turns {} {first} into {} {first}", crafting_loop.consumed, crafting_loop.produced) }
+        </li>
+    }
+}
+
+fn arbitrage(arbitrage: &Arbitrage) -> Html {
+    html! {
+        <li>
+            <span class="status-leftover">
+                { format!("+{} {} per round", arbitrage.profit, arbitrage.currency) }
+            </span>
+            <ol>
+                { for arbitrage.trades.iter().map(|trade| html! { <li>{ trade.to_string() }</li> }) }
+            </ol>
+        </li>
+    }
+}
@@ -0,0 +1,54 @@
+use yew::prelude::*;
+
+use super::location::position_at;
+use crate::logic::{Diagnostic, Severity, Span};
+
+#[derive(Properties, PartialEq)]
+pub struct ProblemsProps {
+    /// The text the diagnostics were computed for.
+    pub text: AttrValue,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Called with the location of a problem when it is clicked.
+    pub onselect: Callback<Span>,
+}
+
+/// A list of all problems in the document, ordered by their location.
+#[function_component(Problems)]
+pub fn problems(props: &ProblemsProps) -> Html {
+    let mut diagnostics: Vec<&Diagnostic> = props.diagnostics.iter().collect();
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.span.start, diagnostic.severity));
+
+    let entries = diagnostics.into_iter().map(|diagnostic| {
+        let (line, _) = position_at(&props.text, diagnostic.span.start);
+        let (class, label) = match diagnostic.severity {
+            Severity::Error => ("error", "Error"),
+            Severity::Warning => ("warning", "Warning"),
+            Severity::Hint => ("hint", "Hint"),
+        };
+
+        let onclick = {
+            let onselect = props.onselect.clone();
+            let span = diagnostic.span;
+            Callback::from(move |_| onselect.emit(span))
+        };
+
+        html! {
+            <li class={classes!("problem", class)} {onclick}>
+                <span class="severity">{ label }</span>
+                { &diagnostic.message }
+                <span class="line">{ format!("line {line}") }</span>
+            </li>
+        }
+    });
+
+    html! {
+        <div class="problems">
+            <h3>{ format!("Problems ({})", props.diagnostics.len()) }</h3>
+            if props.diagnostics.is_empty() {
+                <p>{ "No problems found." }</p>
+            } else {
+                <ul>{ for entries }</ul>
+            }
+        </div>
+    }
+}
@@ -0,0 +1,32 @@
+use std::collections::HashMap;
+
+use gloo_storage::{LocalStorage, Storage};
+use log::warn;
+
+use crate::logic::progress::Progress;
+
+/// The local storage key the progress of all documents is saved under.
+const STORAGE_KEY: &str = "document-progress";
+
+/// Get the completed steps of the document called `name`.
+pub fn load(name: &str) -> Progress {
+    let all: HashMap<String, String> = LocalStorage::get(STORAGE_KEY).unwrap_or_default();
+    all.get(name)
+        .map(|text| Progress::decode(text))
+        .unwrap_or_default()
+}
+
+/// Remember the completed steps of the document called `name`, forgetting them once there
+/// are none.
+pub fn save(name: &str, progress: &Progress) {
+    let mut all: HashMap<String, String> = LocalStorage::get(STORAGE_KEY).unwrap_or_default();
+    if progress.completed.is_empty() {
+        all.remove(name);
+    } else {
+        all.insert(name.to_string(), progress.encode());
+    }
+
+    if let Err(e) = LocalStorage::set(STORAGE_KEY, all) {
+        warn!("Could not save the progress of the document: {e}");
+    }
+}
@@ -0,0 +1,36 @@
+use qrcode::{render::svg, QrCode};
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct QrCodeProps {
+    /// The text to encode, usually a link.
+    pub data: AttrValue,
+    pub onclose: Callback<()>,
+}
+
+/// A popup showing a QR code, so links can be opened on another device.
+#[function_component(QrCodePopup)]
+pub fn qr_code_popup(props: &QrCodeProps) -> Html {
+    let content = match QrCode::new(props.data.as_bytes()) {
+        Ok(code) => {
+            let image = code
+                .render::<svg::Color>()
+                .min_dimensions(256, 256)
+                .quiet_zone(true)
+                .build();
+            Html::from_html_unchecked(image.into())
+        }
+        Err(_) => html! { <p>{ "This document is too large to fit in a QR code." }</p> },
+    };
+
+    let onclick = props.onclose.reform(|_| ());
+
+    html! {
+        <div class="popup">
+            <div class="popup-content">
+                { content }
+                <button {onclick}>{ "Close" }</button>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,178 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use gloo_storage::{LocalStorage, Storage};
+use gloo_timers::callback::Timeout;
+use js_sys::Date;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use yew::prelude::*;
+
+use crate::logic::{Program, Recipe};
+
+/// The local storage key the recent documents are saved under.
+const STORAGE_KEY: &str = "recent-documents";
+
+/// How many documents are remembered.
+const MAX_RECENT_DOCUMENTS: usize = 10;
+
+/// How long [remember_later] waits for more changes before saving, in milliseconds.
+const SAVE_DELAY: u32 = 1_000;
+
+/// A document that was opened or edited before.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct RecentDocument {
+    /// Where the document came from, such as a gist.
+    pub name: String,
+    pub content: String,
+    /// When the document was last used, in milliseconds since the unix epoch.
+    pub last_used: f64,
+}
+
+/// Get the remembered documents, most recently used first.
+///
+/// They are only read from the local storage again after [forget_loaded], since every
+/// evaluation looks up included documents here.
+pub fn load() -> Rc<Vec<RecentDocument>> {
+    LOADED.with(|loaded| {
+        let mut loaded = loaded.borrow_mut();
+        let documents = loaded.get_or_insert_with(|| Rc::new(read()));
+        Rc::clone(documents)
+    })
+}
+
+/// Read the remembered documents again the next time they are loaded, such as when another
+/// tab saved a document.
+pub fn forget_loaded() {
+    LOADED.with(|loaded| loaded.borrow_mut().take());
+}
+
+fn read() -> Vec<RecentDocument> {
+    LocalStorage::get(STORAGE_KEY).unwrap_or_default()
+}
+
+thread_local! {
+    /// The documents [load] read last, which [remember] keeps up to date.
+    static LOADED: RefCell<Option<Rc<Vec<RecentDocument>>>> = RefCell::default();
+
+    /// The recipes of each saved document by name, with the content they were read from.
+    static RECIPES: RefCell<HashMap<String, (String, Rc<Vec<Recipe>>)>> = RefCell::default();
+
+    /// The change [remember_later] is waiting to save, with the timer that saves it.
+    static PENDING: RefCell<Option<(String, String, Timeout)>> = RefCell::default();
+}
+
+/// The recipes of each of `documents`, which are only read again once a document changed.
+/// A document that can't be read has no recipes.
+pub fn recipes(documents: &[RecentDocument]) -> Vec<Rc<Vec<Recipe>>> {
+    RECIPES.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        cache.retain(|name, _| documents.iter().any(|document| document.name == *name));
+        documents
+            .iter()
+            .map(|document| match cache.get(&document.name) {
+                Some((content, recipes)) if *content == document.content => Rc::clone(recipes),
+                _ => {
+                    let program = Program::parse_from_string(&document.content);
+                    let recipes = program.map(|p| p.recipes().cloned().collect());
+                    let recipes = Rc::new(recipes.unwrap_or_default());
+                    let entry = (document.content.clone(), Rc::clone(&recipes));
+                    cache.insert(document.name.clone(), entry);
+                    recipes
+                }
+            })
+            .collect()
+    })
+}
+
+/// Remember the latest content of a document, moving it to the top of the list.
+pub fn remember(name: &str, content: &str) {
+    // another tab may have saved documents in the meantime
+    let mut documents = read();
+    documents.retain(|document| document.name != name);
+    documents.insert(
+        0,
+        RecentDocument {
+            name: name.to_string(),
+            content: content.to_string(),
+            last_used: Date::now(),
+        },
+    );
+    documents.truncate(MAX_RECENT_DOCUMENTS);
+
+    if let Err(e) = LocalStorage::set(STORAGE_KEY, &documents) {
+        warn!("Could not save recent documents: {e}");
+    }
+    LOADED.with(|loaded| *loaded.borrow_mut() = Some(Rc::new(documents)));
+}
+
+/// Like [remember], but only saves once there were no changes for a moment, since saving
+/// writes out every recent document. A pending change to another document is saved first.
+pub fn remember_later(name: &str, content: &str) {
+    let previous = PENDING.with(|pending| pending.borrow_mut().take());
+    if let Some((previous, content, _)) = previous.filter(|(previous, ..)| previous != name) {
+        remember(&previous, &content);
+    }
+
+    let timeout = Timeout::new(SAVE_DELAY, save_pending);
+    PENDING.with(|pending| {
+        *pending.borrow_mut() = Some((name.to_string(), content.to_string(), timeout));
+    });
+}
+
+/// Save the change [remember_later] is waiting with, if there is one.
+pub fn save_pending() {
+    if let Some((name, content, _)) = PENDING.with(|pending| pending.borrow_mut().take()) {
+        remember(&name, &content);
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct StartScreenProps {
+    /// Called with the document to switch to.
+    pub onopen: Callback<RecentDocument>,
+    /// Called to start a new document from the example.
+    pub onnew: Callback<()>,
+    pub onclose: Callback<()>,
+}
+
+/// A list of recent documents to switch between.
+#[function_component(StartScreen)]
+pub fn start_screen(props: &StartScreenProps) -> Html {
+    let documents = use_state(load);
+
+    let entries = documents.iter().map(|document| {
+        let last_used = Date::new(&document.last_used.into())
+            .to_locale_string("default", &Default::default())
+            .as_string()
+            .unwrap_or_default();
+        let onclick = {
+            let onopen = props.onopen.clone();
+            let document = document.clone();
+            Callback::from(move |_| onopen.emit(document.clone()))
+        };
+
+        html! {
+            <li>
+                <button {onclick}>{ &document.name }</button>
+                <span class="last-used">{ last_used }</span>
+            </li>
+        }
+    });
+
+    html! {
+        <div class="popup">
+            <div class="popup-content start-screen">
+                <h2>{ "Recent documents" }</h2>
+                if documents.is_empty() {
+                    <p>{ "Documents you open or edit show up here." }</p>
+                } else {
+                    <ul>{ for entries }</ul>
+                }
+                <div class="buttons">
+                    <button onclick={props.onnew.reform(|_| ())}>{ "New from example" }</button>
+                    <button onclick={props.onclose.reform(|_| ())}>{ "Continue editing" }</button>
+                </div>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::HashSet;
+
+use yew::prelude::*;
+
+use crate::logic::{Item, Program, Recipe};
+
+#[derive(Properties, PartialEq)]
+pub struct RecipeTogglesProps {
+    /// The document to list the recipes of.
+    pub text: AttrValue,
+    /// The items whose recipes are turned off.
+    pub disabled: HashSet<Item>,
+    /// Called with the output of a recipe when it is turned on or off.
+    pub ontoggle: Callback<Item>,
+}
+
+/// A list of the recipes in the document, which can be turned off without editing it.
+#[function_component(RecipeToggles)]
+pub fn recipe_toggles(props: &RecipeTogglesProps) -> Html {
+    let program = use_memo(
+        |text| Program::parse_from_string(text).ok(),
+        props.text.clone(),
+    );
+    let Some(program) = &*program else {
+        return html! {};
+    };
+
+    let is_enabled = |recipe: &&Recipe| !props.disabled.contains(&recipe.output.item);
+    let turned_off = program
+        .recipes()
+        .filter(|recipe| !is_enabled(recipe))
+        .count();
+
+    let entries = program.recipes().map(|recipe| {
+        let item = recipe.output.item.clone();
+        let onchange = props.ontoggle.reform(move |_| item.clone());
+
+        html! {
+            <li>
+                <label>
+                    <input
+                        type="checkbox"
+                        checked={is_enabled(&recipe)}
+                        {onchange}
+                    />
+                    { recipe.to_string() }
+                </label>
+            </li>
+        }
+    });
+
+    html! {
+        <details class="recipe-toggles">
+            <summary>
+                { format!("Recipes ({turned_off} turned off)") }
+            </summary>
+            <ul>{ for entries }</ul>
+        </details>
+    }
+}
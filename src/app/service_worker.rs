@@ -0,0 +1,24 @@
+use log::{debug, info, warn};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+
+/// Register the service worker that makes the app available offline.
+///
+/// This is skipped in debug builds, where cached files would hide changes.
+pub fn register() {
+    if cfg!(debug_assertions) {
+        debug!("Not registering the service worker in a debug build");
+        return;
+    }
+
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+
+    let registration = window.navigator().service_worker().register("sw.js");
+    spawn_local(async move {
+        match JsFuture::from(registration).await {
+            Ok(_) => info!("Registered service worker"),
+            Err(e) => warn!("Could not register service worker: {e:?}"),
+        }
+    });
+}
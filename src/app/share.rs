@@ -0,0 +1,105 @@
+use js_sys::{Function, Object, Promise, Reflect};
+use log::warn;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+
+use super::js::set;
+use crate::logic::IncludePin;
+
+/// Create a link to this page that opens `text` as the document, with `pins` for the
+/// documents it includes.
+///
+/// The document is compressed into the URL fragment, so it never gets sent to a server.
+pub fn share_url(text: &str, pins: &[IncludePin]) -> String {
+    let location = web_sys::window()
+        .expect("page should have a window")
+        .location();
+    let origin = location.origin().expect("location should have an origin");
+    let path = location.pathname().expect("location should have a path");
+
+    // the query is left out, so links from an embed open the full calculator
+    let mut url = format!(
+        "{origin}{path}#{}",
+        lz_str::compress_to_encoded_uri_component(text)
+    );
+    // the compressed text never contains `&`, so it can separate the pins
+    if !pins.is_empty() {
+        url.push('&');
+        url.push_str(&lz_str::compress_to_encoded_uri_component(
+            &IncludePin::encode(pins),
+        ));
+    }
+    url
+}
+
+/// Get the document from a link created by [share_url], if the page was opened through one.
+pub fn document_from_url() -> Option<String> {
+    let (document, _) = fragment()?;
+    let document = decompress(&document);
+    if document.is_none() {
+        warn!("Could not read the document from the URL");
+    }
+    document
+}
+
+/// Get the pins of the included documents from a link created by [share_url].
+pub fn pins_from_url() -> Vec<IncludePin> {
+    let pins = fragment().and_then(|(_, pins)| decompress(&pins?));
+    pins.map(|pins| IncludePin::decode(&pins))
+        .unwrap_or_default()
+}
+
+/// The compressed document and pins in the URL fragment.
+fn fragment() -> Option<(String, Option<String>)> {
+    let hash = web_sys::window()?.location().hash().ok()?;
+    let encoded = hash.strip_prefix('#').filter(|h| !h.is_empty())?;
+    Some(match encoded.split_once('&') {
+        Some((document, pins)) => (document.to_string(), Some(pins.to_string())),
+        None => (encoded.to_string(), None),
+    })
+}
+
+fn decompress(encoded: &str) -> Option<String> {
+    let decompressed = lz_str::decompress_from_encoded_uri_component(encoded);
+    decompressed.and_then(|utf16| String::from_utf16(&utf16).ok())
+}
+
+/// Share a link or text through the system's share sheet.
+///
+/// Browsers without the Web Share API show the text in a prompt to copy it from instead.
+pub fn share(title: &str, text: Option<&str>, url: Option<&str>) {
+    let window = web_sys::window().expect("page should have a window");
+    let navigator = window.navigator();
+
+    let share = Reflect::get(&navigator, &"share".into())
+        .ok()
+        .and_then(|share| share.dyn_into::<Function>().ok());
+
+    let Some(share) = share else {
+        let fallback = url.or(text).unwrap_or_default();
+        // the prompt only exists to let the user copy the text
+        let _ = window.prompt_with_message_and_default("Copy this to share it", fallback);
+        return;
+    };
+
+    let data = Object::new();
+    set(&data, "title", &title.into());
+    if let Some(text) = text {
+        set(&data, "text", &text.into());
+    }
+    if let Some(url) = url {
+        set(&data, "url", &url.into());
+    }
+
+    let Ok(shared) = share.call1(&navigator, &data) else {
+        warn!("Could not open the share sheet");
+        return;
+    };
+
+    spawn_local(async move {
+        // this also fails when the user closes the share sheet, which is fine
+        if let Err(e) = JsFuture::from(Promise::from(shared)).await {
+            warn!("Sharing failed: {e:?}");
+        }
+    });
+}
@@ -0,0 +1,41 @@
+use std::rc::Rc;
+
+use yew::prelude::*;
+
+/// The color theme of the whole page, including the editor.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Theme {
+    /// The name of the matching built-in Monaco theme.
+    pub fn monaco_theme(self) -> &'static str {
+        match self {
+            Theme::Dark => "vs-dark",
+            Theme::Light => "vs",
+        }
+    }
+
+    /// The CSS class applied to the page.
+    pub fn class(self) -> &'static str {
+        match self {
+            Theme::Dark => "theme-dark",
+            Theme::Light => "theme-light",
+        }
+    }
+}
+
+impl Reducible for Theme {
+    /// Toggles between the dark and light theme.
+    type Action = ();
+
+    fn reduce(self: Rc<Self>, _: ()) -> Rc<Self> {
+        Rc::new(match *self {
+            Theme::Dark => Theme::Light,
+            Theme::Light => Theme::Dark,
+        })
+    }
+}
@@ -0,0 +1,327 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+};
+
+use wasm_bindgen::JsCast;
+use web_sys::{Element, HtmlElement};
+use yew::prelude::*;
+
+use crate::logic::{options::NumberFormat, report::CraftingNode, Coverage, Item};
+
+#[derive(Properties, PartialEq)]
+pub struct CraftingTreeProps {
+    pub nodes: Vec<CraftingNode>,
+    pub format: NumberFormat,
+    /// Crafted ingredients of fewer items than this are folded together.
+    pub threshold: u64,
+    /// Called with the item of a node when Enter is pressed on it.
+    #[prop_or_default]
+    pub onselect: Callback<Item>,
+}
+
+/// A row of the tree as it is shown, from top to bottom.
+struct Row<'a> {
+    /// Where the row is in the tree, made from the key of its parent and its index, which
+    /// stays the same while other rows are opened and closed.
+    key: u64,
+    level: usize,
+    /// The index of the row this one is inside of.
+    parent: Option<usize>,
+    /// Whether the row is open, or [None] if there is nothing inside it.
+    expanded: Option<bool>,
+    kind: RowKind<'a>,
+}
+
+enum RowKind<'a> {
+    Node(&'a CraftingNode),
+    /// A number of minor intermediates that are folded together.
+    Minor(usize),
+}
+
+/// The part of a [Row] that the keyboard handler needs.
+struct NavRow {
+    key: u64,
+    parent: Option<usize>,
+    expanded: Option<bool>,
+    item: Option<Item>,
+}
+
+/// The crafting tree, which can be walked with the arrow keys.
+///
+/// Up and down move between the rows, right opens a row or moves into it, left closes it
+/// or moves to the row it is in, and Enter selects the item of a row.
+#[function_component(CraftingTree)]
+pub fn crafting_tree(props: &CraftingTreeProps) -> Html {
+    // rows are open by default, folded minor intermediates are closed by default
+    let toggled = use_state(HashSet::<u64>::new);
+    let focused = use_state(|| None::<u64>);
+    let container = use_node_ref();
+
+    let coverages = coverages(&props.nodes);
+    let mut rows = vec![];
+    push_rows(
+        &mut rows,
+        &props.nodes,
+        props.threshold,
+        &coverages,
+        &toggled,
+    );
+    let focused_index = (*focused)
+        .and_then(|key| rows.iter().position(|row| row.key == key))
+        .unwrap_or(0);
+
+    let nav: Vec<NavRow> = rows
+        .iter()
+        .map(|row| NavRow {
+            key: row.key,
+            parent: row.parent,
+            expanded: row.expanded,
+            item: match row.kind {
+                RowKind::Node(node) => Some(node.stack.item.clone()),
+                RowKind::Minor(_) => None,
+            },
+        })
+        .collect();
+
+    let onkeydown = {
+        let toggled = toggled.clone();
+        let container = container.clone();
+        let onselect = props.onselect.clone();
+        Callback::from(move |e: KeyboardEvent| {
+            let Some(row) = nav.get(focused_index) else {
+                return;
+            };
+            let toggle = || {
+                let mut new = (*toggled).clone();
+                if !new.remove(&row.key) {
+                    new.insert(row.key);
+                }
+                toggled.set(new);
+            };
+            let focus = |index: usize| {
+                if let Some(row) = nav.get(index) {
+                    focus_row(&container, row.key);
+                }
+            };
+
+            match e.key().as_str() {
+                "ArrowDown" => focus(focused_index + 1),
+                "ArrowUp" => focus(focused_index.saturating_sub(1)),
+                "Home" => focus(0),
+                "End" => focus(nav.len().saturating_sub(1)),
+                "ArrowRight" => match row.expanded {
+                    Some(false) => toggle(),
+                    Some(true) => focus(focused_index + 1),
+                    None => {}
+                },
+                "ArrowLeft" => match (row.expanded, row.parent) {
+                    (Some(true), _) => toggle(),
+                    (_, Some(parent)) => focus(parent),
+                    _ => {}
+                },
+                "Enter" => match &row.item {
+                    Some(item) => onselect.emit(item.clone()),
+                    None => toggle(),
+                },
+                " " if row.expanded.is_some() => toggle(),
+                _ => return,
+            }
+            e.prevent_default();
+        })
+    };
+
+    let entries = rows.iter().enumerate().map(|(i, row)| {
+        let onfocus = {
+            let focused = focused.clone();
+            let key = row.key;
+            Callback::from(move |_| focused.set(Some(key)))
+        };
+        let onclick = row.expanded.is_some().then(|| {
+            let toggled = toggled.clone();
+            let key = row.key;
+            Callback::from(move |_| {
+                let mut new = (*toggled).clone();
+                if !new.remove(&key) {
+                    new.insert(key);
+                }
+                toggled.set(new);
+            })
+        });
+
+        let marker = match row.expanded {
+            Some(true) => "▾ ",
+            Some(false) => "▸ ",
+            None => "",
+        };
+        let content = match row.kind {
+            RowKind::Node(node) => {
+                let status = match coverages[&(node as *const CraftingNode)] {
+                    Coverage::Full => "status-full",
+                    Coverage::Partial => "status-partial",
+                    Coverage::None => "status-missing",
+                };
+                html! {
+                    <>
+                        <span class={status} title={node.stack.to_string()}>
+                            { props.format.stack(&node.stack) }
+                        </span>
+                        <span class="details">{ format!(" ({})", node.details(props.format)) }</span>
+                    </>
+                }
+            }
+            RowKind::Minor(count) => html! {
+                <span class="details">{ format!("… and {count} minor intermediates") }</span>
+            },
+        };
+
+        html! {
+            <li
+                role="treeitem"
+                data-key={row.key.to_string()}
+                aria-level={(row.level + 1).to_string()}
+                aria-expanded={row.expanded.map(|expanded| expanded.to_string())}
+                tabindex={if i == focused_index { "0" } else { "-1" }}
+                style={format!("padding-left: {}em", row.level as f64 * 1.5)}
+                {onfocus}
+            >
+                <span class="marker" onclick={onclick}>{ marker }</span>
+                { content }
+            </li>
+        }
+    });
+
+    html! {
+        <ul class="tree" role="tree" ref={container} {onkeydown}>
+            { for entries }
+        </ul>
+    }
+}
+
+/// A row that [push_rows] still has to add.
+enum Pending<'a> {
+    Node(&'a CraftingNode),
+    Minor(Vec<&'a CraftingNode>),
+}
+
+/// Add the rows for `nodes` and, if they are open, the rows inside of them.
+fn push_rows<'a>(
+    rows: &mut Vec<Row<'a>>,
+    nodes: &'a [CraftingNode],
+    threshold: u64,
+    coverages: &HashMap<*const CraftingNode, Coverage>,
+    toggled: &HashSet<u64>,
+) {
+    // trees can be thousands of levels deep, so this keeps its own stack instead of recursing
+    let mut pending: Vec<_> = nodes
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(i, node)| (Pending::Node(node), child_key(0, i), None))
+        .collect();
+    while let Some((entry, key, parent)) = pending.pop() {
+        let level = parent.map_or(0, |parent: usize| rows[parent].level + 1);
+        let index = rows.len();
+        match entry {
+            Pending::Node(node) => {
+                let expanded = !toggled.contains(&key);
+                rows.push(Row {
+                    key,
+                    level,
+                    parent,
+                    expanded: (!node.inputs.is_empty()).then_some(expanded),
+                    kind: RowKind::Node(node),
+                });
+                if node.inputs.is_empty() || !expanded {
+                    continue;
+                }
+
+                let (minor, shown): (Vec<_>, Vec<_>) = node.inputs.iter().partition(|&input| {
+                    let coverage = coverages[&(input as *const CraftingNode)];
+                    input.is_minor(threshold, coverage)
+                });
+                // the folded row comes after the rows that are shown
+                if !minor.is_empty() {
+                    let minor_key = child_key(key, usize::MAX);
+                    pending.push((Pending::Minor(minor), minor_key, Some(index)));
+                }
+                push_inside(&mut pending, shown, key, index);
+            }
+            Pending::Minor(minor) => {
+                let expanded = toggled.contains(&key);
+                rows.push(Row {
+                    key,
+                    level,
+                    parent,
+                    expanded: Some(expanded),
+                    kind: RowKind::Minor(minor.len()),
+                });
+                if expanded {
+                    push_inside(&mut pending, minor, key, index);
+                }
+            }
+        }
+    }
+}
+
+/// Queue `nodes` as the rows inside of the row at `parent`, so the first one is added next.
+fn push_inside<'a>(
+    pending: &mut Vec<(Pending<'a>, u64, Option<usize>)>,
+    nodes: Vec<&'a CraftingNode>,
+    key: u64,
+    parent: usize,
+) {
+    for (i, node) in nodes.into_iter().enumerate().rev() {
+        pending.push((Pending::Node(node), child_key(key, i), Some(parent)));
+    }
+}
+
+/// The key of the row at `index` inside of the row with `key`, where the folded minor
+/// intermediates are at [usize::MAX]. The rows at the top are inside of key 0.
+fn child_key(key: u64, index: usize) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (key, index).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The [CraftingNode::coverage] of every node in `nodes` and below by address, worked out
+/// from the bottom up so a deep tree isn't walked again for every row in it.
+fn coverages(nodes: &[CraftingNode]) -> HashMap<*const CraftingNode, Coverage> {
+    // whether anything below a node is missing, and whether anything is taken
+    let mut found = HashMap::<*const CraftingNode, (bool, bool)>::new();
+    let mut pending: Vec<_> = nodes.iter().map(|node| (node, false)).collect();
+    while let Some((node, visited)) = pending.pop() {
+        if !visited {
+            pending.push((node, true));
+            pending.extend(node.inputs.iter().map(|input| (input, false)));
+            continue;
+        }
+
+        let mut flags = (node.missing > 0, node.taken_from_inventory > 0);
+        for input in &node.inputs {
+            let (missing, taken) = found[&(input as *const CraftingNode)];
+            flags = (flags.0 || missing, flags.1 || taken);
+        }
+        found.insert(node, flags);
+    }
+
+    let coverage = |(missing, taken)| match (missing, taken) {
+        (false, _) => Coverage::Full,
+        (true, true) => Coverage::Partial,
+        (true, false) => Coverage::None,
+    };
+    found
+        .into_iter()
+        .map(|(node, flags)| (node, coverage(flags)))
+        .collect()
+}
+
+/// Move the keyboard focus to the row with `key`.
+fn focus_row(container: &NodeRef, key: u64) {
+    let Some(container) = container.cast::<Element>() else {
+        return;
+    };
+    if let Ok(Some(row)) = container.query_selector(&format!("[data-key=\"{key}\"]")) {
+        let _ = row.unchecked_into::<HtmlElement>().focus();
+    }
+}
@@ -0,0 +1,589 @@
+use indexmap::{IndexMap, IndexSet};
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use super::{Diagnostic, Item, Program, Recipe, Severity};
+
+/// Facts about the recipes in a document, independent of what is needed or available.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Analysis {
+    /// Items that are used but can't be crafted, in the order they first appear.
+    pub raw_items: Vec<Item>,
+    /// How many recipes it takes to craft each item from raw items through its shallowest
+    /// recipe, in the order of the recipe section. Items that depend on a recipe loop are
+    /// left out.
+    pub depths: Vec<(Item, usize)>,
+    /// The longest chain of recipes, from a raw item to the item it ends in.
+    pub longest_chain: Vec<Item>,
+    /// Chains of recipes that use their own output as an ingredient.
+    pub loops: Vec<CraftingLoop>,
+    /// Loops that gain items every round, such as buying and selling for a profit.
+    pub arbitrages: Vec<Arbitrage>,
+}
+
+/// A chain of recipes where the first item is made from the second, the second from the
+/// third and so on, until the last item is made from the first one again.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CraftingLoop {
+    pub items: Vec<Item>,
+    /// The recipe each item is made with, since an item can have several.
+    pub recipes: Vec<Recipe>,
+    /// How often the recipe for each item runs in one round, so none of the items after the
+    /// first one are left over or missing.
+    pub runs: Vec<u64>,
+    /// How many of the first item one round through the loop creates.
+    pub produced: u64,
+    /// How many of the first item one round through the loop uses up.
+    pub consumed: u64,
+}
+
+impl CraftingLoop {
+    /// Whether going through the loop leaves more items than it started with.
+    pub fn is_net_positive(&self) -> bool {
+        self.produced > self.consumed
+    }
+}
+
+/// A net-positive loop, told from the point of view of the item it is most likely to be
+/// run for.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Arbitrage {
+    /// The item in the loop that is used by the most recipes, such as a currency.
+    pub currency: Item,
+    /// How much of the currency one round gains.
+    pub profit: u64,
+    /// The recipes to run for one round, starting with the one that creates the currency.
+    pub trades: Vec<Recipe>,
+}
+
+/// How many steps the search for loops may take, since big documents can have a huge
+/// amount of them.
+const MAX_LOOP_SEARCH_STEPS: usize = 100_000;
+
+/// Analyze the recipe graph of `program`.
+///
+/// This looks at every recipe that evaluation can use, so an item with several alternatives
+/// can be part of several chains and loops.
+pub fn analyze(program: &Program) -> Analysis {
+    let recipes: Vec<&Recipe> = program.usable_recipes().collect();
+    // the indices in `recipes` of the alternatives for each item, in order
+    let mut alternatives: IndexMap<&Item, Vec<usize>> = IndexMap::new();
+    for (index, recipe) in recipes.iter().enumerate() {
+        alternatives
+            .entry(&recipe.output.item)
+            .or_default()
+            .push(index);
+    }
+
+    let used_items = program
+        .need_section
+        .0
+        .iter()
+        .flat_map(|need| need.stacks().map(|stack| &stack.item))
+        .chain(
+            recipes
+                .iter()
+                .flat_map(|r| r.inputs.iter().map(|i| &i.item)),
+        );
+    let raw_items: IndexSet<&Item> = used_items
+        .filter(|item| !alternatives.contains_key(item))
+        .collect();
+
+    // work from the raw items up, so long chains don't need deep recursion. Each recipe
+    // waits for its craftable ingredients, and an item gets the depth of its shallowest
+    // recipe, which is found first since shallower recipes are handled first.
+    let mut pending = Vec::with_capacity(recipes.len());
+    let mut dependents: IndexMap<&Item, Vec<usize>> = IndexMap::new();
+    for (index, recipe) in recipes.iter().enumerate() {
+        let inputs: IndexSet<&Item> = recipe
+            .inputs
+            .iter()
+            .map(|input| &input.item)
+            .filter(|item| alternatives.contains_key(item))
+            .collect();
+
+        pending.push(inputs.len());
+        for input in inputs {
+            dependents.entry(input).or_default().push(index);
+        }
+    }
+
+    // the depth of each item with the index of the recipe it has it through
+    let mut depths: IndexMap<&Item, (usize, usize)> = IndexMap::new();
+    let mut ready: BinaryHeap<Reverse<(usize, usize)>> = pending
+        .iter()
+        .enumerate()
+        .filter(|(_, &count)| count == 0)
+        .map(|(index, _)| Reverse((1, index)))
+        .collect();
+
+    while let Some(Reverse((depth, index))) = ready.pop() {
+        let item = &recipes[index].output.item;
+        if depths.contains_key(item) {
+            continue;
+        }
+        depths.insert(item, (depth, index));
+
+        for &dependent in dependents.get(item).into_iter().flatten() {
+            pending[dependent] -= 1;
+            if pending[dependent] == 0 {
+                let inputs = recipes[dependent].inputs.iter();
+                let deepest = inputs.filter_map(|input| depths.get(&input.item)).max();
+                let depth = deepest.map_or(0, |&(depth, _)| depth) + 1;
+                ready.push(Reverse((depth, dependent)));
+            }
+        }
+    }
+
+    let chains = find_loops(&recipes, &alternatives);
+
+    let depth_of = |item: &Item| depths.get(item).map_or(0, |&(depth, _)| depth);
+
+    // the first deepest item in the recipe section, followed down to a raw item
+    let mut longest_chain = vec![];
+    let deepest = alternatives
+        .keys()
+        .filter(|item| depths.contains_key(*item))
+        .rev()
+        .max_by_key(|item| depth_of(item));
+    if let Some(&deepest) = deepest {
+        let mut item = deepest;
+        longest_chain.push(item.clone());
+        while let Some(&(_, index)) = depths.get(item) {
+            let input = recipes[index]
+                .inputs
+                .iter()
+                .rev()
+                .max_by_key(|input| depth_of(&input.item));
+            let Some(input) = input else {
+                break;
+            };
+            item = &input.item;
+            longest_chain.push(item.clone());
+        }
+        longest_chain.reverse();
+    }
+
+    Analysis {
+        raw_items: raw_items.into_iter().cloned().collect(),
+        depths: alternatives
+            .keys()
+            .filter_map(|&item| Some((item.clone(), depths.get(item)?.0)))
+            .collect(),
+        longest_chain,
+        loops: chains
+            .iter()
+            .filter_map(|chain| crafting_loop(&recipes, chain))
+            .collect(),
+        arbitrages: chains
+            .iter()
+            .filter_map(|chain| arbitrage(&recipes, chain))
+            .collect(),
+    }
+}
+
+/// Warnings for the recipes in loops that create items out of nothing. Evaluation would
+/// keep going around them, so they are likely mistakes in the document.
+pub fn loop_warnings(program: &Program, analysis: &Analysis) -> Vec<Diagnostic> {
+    let net_positive = analysis.loops.iter().filter(|l| l.is_net_positive());
+
+    net_positive
+        .flat_map(|crafting_loop| {
+            let first = &crafting_loop.items[0];
+            let message = format!(
+                "this recipe is part of a loop that turns {} {first} into {} {first}",
+                crafting_loop.consumed, crafting_loop.produced
+            );
+
+            crafting_loop.recipes.iter().filter_map(move |recipe| {
+                // included recipes can't be marked in this document
+                let recipe = program
+                    .recipe_section
+                    .0
+                    .iter()
+                    .find(|entry| entry.value == *recipe)?;
+
+                Some(Diagnostic {
+                    span: recipe.span,
+                    severity: Severity::Warning,
+                    message: message.clone(),
+                })
+            })
+        })
+        .collect()
+}
+
+/// Find all loops in the recipe graph, each starting at the recipe that comes first in
+/// `recipes`. A loop goes through every item at most once, but may use any of the
+/// `alternatives` for it.
+///
+/// Each step in a loop is given as the index of its recipe in `recipes`, with how many of
+/// the next item that recipe uses.
+fn find_loops(
+    recipes: &[&Recipe],
+    alternatives: &IndexMap<&Item, Vec<usize>>,
+) -> Vec<Vec<(usize, u64)>> {
+    // the recipes for the craftable ingredients of each recipe, with how many it uses
+    let ingredients: Vec<Vec<(usize, u64)>> = recipes
+        .iter()
+        .map(|recipe| {
+            let mut counts = IndexMap::<&Item, u64>::new();
+            for input in recipe.inputs.iter().filter(|input| input.count > 0) {
+                let count = counts.entry(&input.item).or_default();
+                *count = count.saturating_add(input.count);
+            }
+            counts
+                .into_iter()
+                .flat_map(|(item, count)| {
+                    let indices = alternatives.get(item).into_iter().flatten();
+                    indices.map(move |&index| (index, count))
+                })
+                .collect()
+        })
+        .collect();
+    let item_of = |index: usize| {
+        let item = &recipes[index].output.item;
+        alternatives
+            .get_index_of(item)
+            .expect("every recipe is an alternative")
+    };
+
+    let mut loops = vec![];
+    let mut steps = 0;
+
+    // only visit items after the start, so every loop is found exactly once
+    'search: for start in 0..recipes.len() {
+        // by item, so a loop doesn't go around twice through different alternatives
+        let mut on_path = vec![false; alternatives.len()];
+        // each recipe on the path with the index of the next ingredient to visit
+        let mut path = vec![(start, 0)];
+        on_path[item_of(start)] = true;
+
+        while let Some((recipe, next)) = path.last_mut() {
+            let Some(&(ingredient, _)) = ingredients[*recipe].get(*next) else {
+                on_path[item_of(*recipe)] = false;
+                path.pop();
+                continue;
+            };
+            *next += 1;
+
+            steps += 1;
+            if steps > MAX_LOOP_SEARCH_STEPS {
+                log::warn!("Stopped looking for recipe loops after {steps} steps");
+                break 'search;
+            }
+
+            if ingredient == start {
+                let chain: Vec<_> = path
+                    .iter()
+                    .map(|&(recipe, next)| (recipe, ingredients[recipe][next - 1].1))
+                    .collect();
+                loops.push(chain);
+            } else if ingredient > start && !on_path[item_of(ingredient)] {
+                on_path[item_of(ingredient)] = true;
+                path.push((ingredient, 0));
+            }
+        }
+    }
+
+    loops
+}
+
+/// Work out how often each recipe in a loop has to run so none of the items in between
+/// are left over or missing.
+///
+/// `chain` is a loop from [find_loops]. Returns [None] if the counts get too large.
+fn crafting_loop(recipes: &[&Recipe], chain: &[(usize, u64)]) -> Option<CraftingLoop> {
+    let output = |index: usize| u128::from(recipes[index].output.count);
+
+    // the runs of each recipe as fractions of the runs of the first one
+    let mut runs = vec![(1u128, 1u128)];
+    for window in chain.windows(2) {
+        let (_, used) = window[0];
+        let (numerator, denominator) = runs[runs.len() - 1];
+        runs.push(reduce(
+            numerator.checked_mul(u128::from(used))?,
+            denominator.checked_mul(output(window[1].0))?,
+        )?);
+    }
+
+    // scale the fractions up to the smallest whole numbers
+    let multiple = runs
+        .iter()
+        .try_fold(1, |multiple, &(_, denominator)| lcm(multiple, denominator))?;
+    let runs: Vec<u128> = runs
+        .iter()
+        .map(|&(numerator, denominator)| numerator.checked_mul(multiple / denominator))
+        .collect::<Option<_>>()?;
+
+    let (first, _) = chain[0];
+    let (_, last_used) = chain[chain.len() - 1];
+    let produced = runs[0].checked_mul(output(first))?;
+    let consumed = runs[runs.len() - 1].checked_mul(u128::from(last_used))?;
+
+    Some(CraftingLoop {
+        items: chain
+            .iter()
+            .map(|&(index, _)| recipes[index].output.item.clone())
+            .collect(),
+        recipes: chain
+            .iter()
+            .map(|&(index, _)| recipes[index].clone())
+            .collect(),
+        runs: runs
+            .iter()
+            .map(|&runs| runs.try_into().ok())
+            .collect::<Option<_>>()?,
+        produced: produced.try_into().ok()?,
+        consumed: consumed.try_into().ok()?,
+    })
+}
+
+/// Look at a loop from the point of view of its currency, if it is net-positive.
+fn arbitrage(recipes: &[&Recipe], chain: &[(usize, u64)]) -> Option<Arbitrage> {
+    let uses = |index: usize| {
+        let item = &recipes[index].output.item;
+        recipes
+            .iter()
+            .filter(|recipe| {
+                recipe.output.item == *item || recipe.inputs.iter().any(|i| i.item == *item)
+            })
+            .count()
+    };
+
+    // the first item with the most uses, so ties keep the order of the recipe section
+    let (start, _) = chain
+        .iter()
+        .enumerate()
+        .rev()
+        .max_by_key(|(_, &(index, _))| uses(index))?;
+    let mut chain = chain.to_vec();
+    chain.rotate_left(start);
+
+    let crafting_loop = crafting_loop(recipes, &chain)?;
+    if !crafting_loop.is_net_positive() {
+        return None;
+    }
+
+    let trades = chain
+        .iter()
+        .zip(&crafting_loop.runs)
+        .map(|(&(index, _), &runs)| recipes[index].checked_multiplied_by(runs))
+        .collect::<Option<_>>()?;
+
+    Some(Arbitrage {
+        currency: crafting_loop.items[0].clone(),
+        profit: crafting_loop.produced - crafting_loop.consumed,
+        trades,
+    })
+}
+
+/// Reduce a fraction, or [None] if it is divided by 0.
+fn reduce(numerator: u128, denominator: u128) -> Option<(u128, u128)> {
+    if denominator == 0 {
+        return None;
+    }
+    let divisor = gcd(numerator, denominator);
+    Some((numerator / divisor, denominator / divisor))
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn lcm(a: u128, b: u128) -> Option<u128> {
+    (a / gcd(a, b)).checked_mul(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logic::{Diagnostic, Item, ItemStack, Program, Recipe, Severity, Span};
+
+    use super::{analyze, loop_warnings, Arbitrage, CraftingLoop};
+
+    #[test]
+    fn test_analyze() {
+        let input = include_str!("../parsing/example_input.txt");
+        let program = Program::parse_from_string(input).unwrap();
+
+        let analysis = analyze(&program);
+        assert_eq!(analysis.raw_items, [Item::new("diamond"), Item::new("log")]);
+        assert_eq!(
+            analysis.depths,
+            [
+                (Item::new("diamond pickaxe"), 3),
+                (Item::new("stick"), 2),
+                (Item::new("plank"), 1),
+            ]
+        );
+        assert_eq!(
+            analysis.longest_chain,
+            [
+                Item::new("log"),
+                Item::new("plank"),
+                Item::new("stick"),
+                Item::new("diamond pickaxe"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_analyze_recipe_loop() {
+        let input = "need:\nhave:\nrecipes:\n- 1 a = 1 b\n- 1 b = 1 a\n- 1 c = 1 d\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        let analysis = analyze(&program);
+        let recipe = |index: usize| program.recipe_section.0[index].value.clone();
+        assert_eq!(analysis.raw_items, [Item::new("d")]);
+        assert_eq!(analysis.depths, [(Item::new("c"), 1)]);
+        assert_eq!(analysis.longest_chain, [Item::new("d"), Item::new("c")]);
+        assert_eq!(
+            analysis.loops,
+            [CraftingLoop {
+                items: vec![Item::new("a"), Item::new("b")],
+                recipes: vec![recipe(0), recipe(1)],
+                runs: vec![1, 1],
+                produced: 1,
+                consumed: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_analyze_alternatives() {
+        let input = "need:\nhave:\nrecipes:\n- 1 a = 1 b\n- 1 a = 1 c\n- 2 b = 1 a\n- 1 c = 1 d\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        // a has a depth through its second recipe, and b through a
+        let analysis = analyze(&program);
+        assert_eq!(analysis.raw_items, [Item::new("d")]);
+        assert_eq!(
+            analysis.depths,
+            [
+                (Item::new("a"), 2),
+                (Item::new("b"), 3),
+                (Item::new("c"), 1),
+            ]
+        );
+        assert_eq!(
+            analysis.longest_chain,
+            [
+                Item::new("d"),
+                Item::new("c"),
+                Item::new("a"),
+                Item::new("b")
+            ]
+        );
+
+        // the loop goes through the first recipe for a, which is the one that is marked
+        let [crafting_loop] = &analysis.loops[..] else {
+            panic!("expected one loop");
+        };
+        assert_eq!(crafting_loop.items, [Item::new("a"), Item::new("b")]);
+        let marked: Vec<_> = loop_warnings(&program, &analysis)
+            .iter()
+            .map(|warning| &input[warning.span.start..warning.span.end])
+            .collect();
+        assert_eq!(marked, ["1 a = 1 b", "2 b = 1 a"]);
+    }
+
+    #[test]
+    fn test_loop_runs() {
+        let input =
+            "need:\nhave:\nrecipes:\n- 1 sword = 10 coin\n- 12 coin = 1 sword\n- 1 a = 2 a\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        let analysis = analyze(&program);
+        let recipe = |index: usize| program.recipe_section.0[index].value.clone();
+        assert_eq!(
+            analysis.loops,
+            [
+                CraftingLoop {
+                    items: vec![Item::new("sword"), Item::new("coin")],
+                    recipes: vec![recipe(0), recipe(1)],
+                    runs: vec![6, 5],
+                    produced: 6,
+                    consumed: 5,
+                },
+                CraftingLoop {
+                    items: vec![Item::new("a")],
+                    recipes: vec![recipe(2)],
+                    runs: vec![1],
+                    produced: 1,
+                    consumed: 2,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_arbitrage() {
+        let input = "need:\nhave:\nrecipes:\n\
+            - 1 sword = 10 coin\n\
+            - 12 coin = 1 sword\n\
+            - 1 potion = 3 coin\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        let analysis = analyze(&program);
+        assert_eq!(
+            analysis.arbitrages,
+            [Arbitrage {
+                currency: Item::new("coin"),
+                profit: 2,
+                trades: vec![
+                    Recipe {
+                        doc: None,
+                        preferred: false,
+                        byproducts: vec![],
+                        output: ItemStack {
+                            count: 12,
+                            item: Item::new("coin"),
+                        },
+                        inputs: vec![ItemStack {
+                            count: 1,
+                            item: Item::new("sword"),
+                        }],
+                    },
+                    Recipe {
+                        doc: None,
+                        preferred: false,
+                        byproducts: vec![],
+                        output: ItemStack {
+                            count: 1,
+                            item: Item::new("sword"),
+                        },
+                        inputs: vec![ItemStack {
+                            count: 10,
+                            item: Item::new("coin"),
+                        }],
+                    },
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_loop_warnings() {
+        let input = "need:\nhave:\nrecipes:\n- 2 a = 1 b\n- 1 b = 1 a\n- 1 c = 1 c\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        let message = "this recipe is part of a loop that turns 1 a into 2 a";
+        assert_eq!(
+            loop_warnings(&program, &analyze(&program)),
+            [
+                Diagnostic {
+                    span: Span { start: 23, end: 32 },
+                    severity: Severity::Warning,
+                    message: message.into(),
+                },
+                Diagnostic {
+                    span: Span { start: 35, end: 44 },
+                    severity: Severity::Warning,
+                    message: message.into(),
+                },
+            ]
+        );
+    }
+}
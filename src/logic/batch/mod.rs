@@ -0,0 +1,91 @@
+use indexmap::IndexMap;
+
+use super::{options, report::Report, Item, ItemStack, Program};
+
+/// The result of [evaluate_batch].
+#[derive(Debug, PartialEq, Eq)]
+pub struct BatchReport {
+    /// The name of each document with its report, or why it couldn't be evaluated.
+    pub documents: Vec<(String, Result<Report, String>)>,
+    /// The missing items of all documents added together, such as for buying them in one
+    /// go.
+    pub shopping_list: Vec<ItemStack>,
+}
+
+/// Evaluate several `documents`, as pairs of their name and content, with the recipes of
+/// `pack` on top of their own. Each document is evaluated on its own, with its own need and
+/// have sections, such as the personal lists of the members of a guild project.
+///
+/// Like included recipes, a recipe in a document replaces the pack's recipes for the same
+/// item.
+pub fn evaluate_batch<'d>(
+    pack: &Program,
+    documents: impl IntoIterator<Item = (&'d str, &'d str)>,
+) -> BatchReport {
+    let mut shopping_list = IndexMap::<Item, u64>::new();
+    let documents = documents
+        .into_iter()
+        .map(|(name, content)| {
+            let report = evaluate_with_pack(pack, content);
+            if let Ok(report) = &report {
+                for stack in &report.missing_items {
+                    let count = shopping_list.entry(stack.item.clone()).or_default();
+                    *count = count.saturating_add(stack.count);
+                }
+            }
+            (name.to_string(), report)
+        })
+        .collect();
+
+    BatchReport {
+        documents,
+        shopping_list: shopping_list
+            .into_iter()
+            .map(|(item, count)| ItemStack { count, item })
+            .collect(),
+    }
+}
+
+fn evaluate_with_pack(pack: &Program, content: &str) -> Result<Report, String> {
+    let mut program = Program::parse_from_string(content).map_err(|e| e.message)?;
+    program.included_recipes = pack.usable_recipes().cloned().collect();
+    // the pack may spell items differently
+    options::normalize_item_names(&mut program);
+    super::evaluate(&program).map_err(|e| format!("Error during evaluation: {e:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logic::Program;
+
+    use super::evaluate_batch;
+
+    #[test]
+    fn test_evaluate_batch() {
+        let pack = "need:\nhave:\nrecipes:\n- 4 plank = 1 log\n- 4 stick = 2 plank\n\
+            - 1 pickaxe = 2 stick + 3 plank\n";
+        let pack = Program::parse_from_string(pack).unwrap();
+        let documents = [
+            ("alice", "need:\n- 1 pickaxe\nhave:\nrecipes:\n"),
+            ("bob", "need:\n- 16 stick\nhave:\n- 1 log\nrecipes:\n"),
+            (
+                "carol",
+                "need:\n- 1 stick\nhave:\nrecipes:\n- 4 stick = 1 bamboo\n",
+            ),
+            ("dave", "oops"),
+        ];
+
+        let batch = evaluate_batch(&pack, documents);
+        let shopping_list: Vec<_> = batch.shopping_list.iter().map(|s| s.to_string()).collect();
+        // alice needs 2 logs and bob 1 more, while carol uses her own stick recipe
+        assert_eq!(shopping_list, ["3 log", "1 bamboo"]);
+
+        let names: Vec<_> = batch
+            .documents
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, ["alice", "bob", "carol", "dave"]);
+        assert!(batch.documents[3].1.is_err());
+    }
+}
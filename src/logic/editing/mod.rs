@@ -0,0 +1,1092 @@
+use indexmap::IndexMap;
+
+use std::{cmp::Ordering, collections::BTreeMap};
+
+use super::{Category, Item, ItemStack, Program, Recipe, Setting, Source, Span, Spanned};
+
+/// A change to the source document: the text in `span` gets replaced by `text`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TextEdit {
+    pub span: Span,
+    pub text: String,
+}
+
+/// Multiple entries for the same item inside a single section.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DuplicateEntries<'p> {
+    pub item: &'p Item,
+    /// Whether the entries are `exact` needs, which are only merged with each other.
+    pub exact: bool,
+    pub entries: Vec<Spanned<&'p ItemStack>>,
+}
+
+impl DuplicateEntries<'_> {
+    pub fn total_count(&self) -> u64 {
+        self.entries
+            .iter()
+            .fold(0, |total, e| total.saturating_add(e.count))
+    }
+
+    /// Create the edits that merge all entries into the first one.
+    pub fn merge(&self, source: &str) -> Vec<TextEdit> {
+        let (first, rest) = self
+            .entries
+            .split_first()
+            .expect("duplicates have at least 2 entries");
+
+        let exact = if self.exact { "exact " } else { "" };
+        let mut edits = vec![TextEdit {
+            span: first.span,
+            text: format!("{exact}{} {}", self.total_count(), self.item.0),
+        }];
+
+        edits.extend(rest.iter().map(|entry| TextEdit {
+            span: line_span(source, entry.span),
+            text: String::new(),
+        }));
+
+        edits
+    }
+}
+
+/// Find items that are listed more than once in the need or have section.
+///
+/// Needs with alternatives are left out, since they can't be merged with other entries, and
+/// `exact` needs are only duplicates of other `exact` needs.
+pub fn find_duplicates(program: &Program) -> Vec<DuplicateEntries<'_>> {
+    let spanned = |value, span| Spanned { value, span };
+    let needs = program
+        .need_section
+        .0
+        .iter()
+        .filter(|need| need.alternatives.is_empty())
+        .map(|need| (need.exact, spanned(&need.stack, need.span)))
+        .collect();
+    let haves = program
+        .have_section
+        .0
+        .iter()
+        .map(|have| (false, spanned(&have.value, have.span)))
+        .collect();
+
+    [needs, haves]
+        .into_iter()
+        .flat_map(|entries: Vec<(bool, Spanned<&ItemStack>)>| {
+            let mut by_item: IndexMap<(&Item, bool), Vec<Spanned<&ItemStack>>> = IndexMap::new();
+            for (exact, entry) in entries {
+                let key = (&entry.value.item, exact);
+                by_item.entry(key).or_default().push(entry);
+            }
+
+            by_item
+                .into_iter()
+                .filter(|(_, entries)| entries.len() > 1)
+                .map(|((item, exact), entries)| DuplicateEntries {
+                    item,
+                    exact,
+                    entries,
+                })
+        })
+        .collect()
+}
+
+/// How [sort_sections] orders entries.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SortOrder {
+    /// By item name, ignoring case.
+    Alphabetical,
+    /// By count, largest first.
+    Count,
+}
+
+/// Create the edits that sort the entries within each section.
+///
+/// Only the entries themselves and their trailing comments are moved around, so
+/// anything else on their lines stays where it is.
+pub fn sort_sections(program: &Program, source: &str, order: SortOrder) -> Vec<TextEdit> {
+    let stack_key = |stack: &ItemStack| (stack.item.clone(), stack.count);
+    let needs = with_comments(source, &program.need_section.0);
+    let mut edits = sort_entries(&needs, source, order, |need| stack_key(need));
+    let haves = with_comments(source, &program.have_section.0);
+    edits.extend(sort_entries(&haves, source, order, |have| stack_key(have)));
+    // doc comments move along with their recipe
+    let recipes: Vec<_> = program
+        .recipe_section
+        .0
+        .iter()
+        .map(|recipe| Spanned {
+            value: &recipe.value,
+            span: documented_span(source, commented_span(source, recipe.span)),
+        })
+        .collect();
+    edits.extend(sort_entries(&recipes, source, order, |recipe| {
+        stack_key(&recipe.output)
+    }));
+    edits
+}
+
+/// The entries with their spans extended by [commented_span].
+fn with_comments<'p, T>(source: &str, entries: &'p [Spanned<T>]) -> Vec<Spanned<&'p T>> {
+    let entries = entries.iter().map(|entry| Spanned {
+        value: &entry.value,
+        span: commented_span(source, entry.span),
+    });
+    entries.collect()
+}
+
+/// Extend the span of an entry to the end of its line, so it includes its trailing comment.
+fn commented_span(source: &str, span: Span) -> Span {
+    let line_end = source[span.end..]
+        .find('\n')
+        .map_or(source.len(), |i| span.end + i);
+    let comment = source[span.end..line_end].trim_end();
+    Span {
+        start: span.start,
+        end: span.end + comment.len(),
+    }
+}
+
+/// Extend the span of a recipe to the start of its line, and to the `## ` doc comment
+/// lines above it along with the plain comments between them, like the parser reads them.
+fn documented_span(source: &str, span: Span) -> Span {
+    let mut start = source[..span.start].rfind('\n').map_or(0, |i| i + 1);
+    let mut line_end = start;
+    while line_end > 0 {
+        let line_start = source[..line_end - 1].rfind('\n').map_or(0, |i| i + 1);
+        let line = &source[line_start..line_end];
+        if line.starts_with("##") {
+            start = line_start;
+        } else if !line.trim_start().starts_with('#') {
+            break;
+        }
+        line_end = line_start;
+    }
+
+    Span {
+        start,
+        end: span.end,
+    }
+}
+
+fn sort_entries<T>(
+    entries: &[Spanned<T>],
+    source: &str,
+    order: SortOrder,
+    key: impl Fn(&T) -> (Item, u64),
+) -> Vec<TextEdit> {
+    let mut sorted: Vec<_> = entries.iter().collect();
+    sorted.sort_by(|a, b| compare(&key(a), &key(b), order));
+
+    entries
+        .iter()
+        .zip(sorted)
+        .filter(|(entry, sorted)| entry.span != sorted.span)
+        .map(|(entry, sorted)| TextEdit {
+            span: entry.span,
+            text: source[sorted.span.start..sorted.span.end].to_string(),
+        })
+        .collect()
+}
+
+fn compare(
+    (a_item, a_count): &(Item, u64),
+    (b_item, b_count): &(Item, u64),
+    order: SortOrder,
+) -> Ordering {
+    let by_name = || a_item.0.to_lowercase().cmp(&b_item.0.to_lowercase());
+
+    match order {
+        SortOrder::Alphabetical => by_name().then(a_count.cmp(b_count)),
+        SortOrder::Count => b_count.cmp(a_count).then_with(by_name),
+    }
+}
+
+/// Write the program in its canonical layout, with the sections separated by
+/// an empty line.
+///
+/// Plain comments in `source` are kept with the line below them, and comments at the end of
+/// a line stay there. Comments that don't belong to any line that is written, such as those
+/// at the end of the document, are written at the end.
+pub fn format(program: &Program, source: &str) -> String {
+    let mut comments = Comments::new(source);
+    let mut output = String::new();
+    if let Some(version) = &program.version {
+        let line = format!("version: {}", version.value);
+        push_line(&mut output, comments.entry(version.span), &line);
+        output.push('\n');
+    }
+
+    if !program.includes.is_empty() {
+        for include in &program.includes {
+            let line = format!("include {}", include.value);
+            push_line(&mut output, comments.entry(include.span), &line);
+        }
+        output.push('\n');
+    }
+
+    if !program.option_section.0.is_empty() {
+        push_line(&mut output, comments.header("options:"), "options:");
+        for setting in &program.option_section.0 {
+            let Setting { key, value } = &setting.value;
+            let line = format!("- {key} = {value}");
+            push_line(&mut output, comments.entry(setting.span), &line);
+        }
+        output.push('\n');
+    }
+
+    if !program.source_section.0.is_empty() {
+        push_line(&mut output, comments.header("sources:"), "sources:");
+        for entry in &program.source_section.0 {
+            let Source { item, source } = &entry.value;
+            let line = format!("- {item} @ {source}");
+            push_line(&mut output, comments.entry(entry.span), &line);
+        }
+        output.push('\n');
+    }
+
+    if !program.category_section.0.is_empty() {
+        push_line(&mut output, comments.header("categories:"), "categories:");
+        for category in &program.category_section.0 {
+            let Category { name, items } = &category.value;
+            let items: Vec<String> = items.iter().map(Item::to_string).collect();
+            let line = format!("- {name}: {}", items.join(", "));
+            push_line(&mut output, comments.entry(category.span), &line);
+        }
+        output.push('\n');
+    }
+
+    push_line(&mut output, comments.header("need:"), "need:");
+    for need in &program.need_section.0 {
+        let line = format!("- {}", need.value);
+        push_line(&mut output, comments.entry(need.span), &line);
+    }
+
+    output.push('\n');
+    push_line(&mut output, comments.header("have:"), "have:");
+    for stack in &program.have_section.0 {
+        let line = format!("- {}", stack.value);
+        push_line(&mut output, comments.entry(stack.span), &line);
+    }
+
+    for inventory in &program.inventories {
+        output.push('\n');
+        let line = format!("have {}:", inventory.player.value);
+        push_line(&mut output, comments.entry(inventory.player.span), &line);
+        for stack in &inventory.items {
+            let line = format!("- {}", stack.value);
+            push_line(&mut output, comments.entry(stack.span), &line);
+        }
+    }
+
+    output.push('\n');
+    push_line(&mut output, comments.header("recipes:"), "recipes:");
+    for recipe in &program.recipe_section.0 {
+        let (mut above, after) = comments.entry(recipe.span);
+        // the doc comment is part of the recipe, so it comes from recipe_lines instead
+        above.retain(|comment| !comment.starts_with("##"));
+        let lines = recipe_lines(recipe);
+        let (line, doc) = lines
+            .split_last()
+            .expect("the recipe itself is the last line");
+        above.extend(doc.iter().map(String::as_str));
+        push_line(&mut output, (above, after), line);
+    }
+
+    for comment in comments.rest() {
+        output.push_str(comment);
+        output.push('\n');
+    }
+
+    output
+}
+
+/// Write `line` to `output`, with the comment lines above it and the comment after it.
+fn push_line(output: &mut String, (above, after): (Vec<&str>, &str), line: &str) {
+    for comment in above {
+        output.push_str(comment);
+        output.push('\n');
+    }
+    output.push_str(line);
+    if !after.is_empty() {
+        output.push(' ');
+        output.push_str(after);
+    }
+    output.push('\n');
+}
+
+/// The plain comments of a document, for [format] to write next to the lines they belong to.
+struct Comments<'s> {
+    source: &'s str,
+    /// The comment lines above each line, by the offset where that line starts. Taken out
+    /// once they are written.
+    above: BTreeMap<usize, Vec<&'s str>>,
+}
+
+impl<'s> Comments<'s> {
+    fn new(source: &'s str) -> Self {
+        let mut above = BTreeMap::new();
+        let mut comments = vec![];
+        let mut start = 0;
+        for line in source.split_inclusive('\n') {
+            let text = line.trim_end();
+            if text.trim_start().starts_with('#') {
+                comments.push(text);
+            } else if !text.is_empty() && !comments.is_empty() {
+                above.insert(start, std::mem::take(&mut comments));
+            }
+            start += line.len();
+        }
+        if !comments.is_empty() {
+            above.insert(source.len(), comments);
+        }
+
+        Self { source, above }
+    }
+
+    /// The comments above the line with the entry at `span`, and the comment after it.
+    fn entry(&mut self, span: Span) -> (Vec<&'s str>, &'s str) {
+        let start = self.source[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        let rest = self.source[span.end..].lines().next().unwrap_or_default();
+        let after = rest.find('#').map_or("", |i| rest[i..].trim_end());
+        (self.above.remove(&start).unwrap_or_default(), after)
+    }
+
+    /// The comments above and after the first line that reads `header`, such as `need:`.
+    fn header(&mut self, header: &str) -> (Vec<&'s str>, &'s str) {
+        let mut start = 0;
+        for line in self.source.split_inclusive('\n') {
+            let text = line.split('#').next().unwrap_or_default();
+            if text.trim() == header {
+                let after = line[text.len()..].trim_end();
+                return (self.above.remove(&start).unwrap_or_default(), after);
+            }
+            start += line.len();
+        }
+        Default::default()
+    }
+
+    /// The comments that weren't written yet, in order.
+    fn rest(self) -> impl Iterator<Item = &'s str> {
+        self.above.into_values().flatten()
+    }
+}
+
+/// The lines of the recipe section for `recipe`: its notes, then the recipe itself.
+fn recipe_lines(recipe: &Recipe) -> Vec<String> {
+    let doc = recipe.doc.iter().flat_map(|doc| doc.lines());
+    let outputs: Vec<String> = recipe.outputs().map(ItemStack::to_string).collect();
+    let inputs: Vec<String> = recipe.inputs.iter().map(ItemStack::to_string).collect();
+    let prefer = if recipe.preferred { "prefer " } else { "" };
+    doc.map(|line| format!("## {line}"))
+        .chain([format!(
+            "- {prefer}{} = {}",
+            outputs.join(" + "),
+            inputs.join(" + ")
+        )])
+        .collect()
+}
+
+/// The doc comment to show when hovering over `offset`: that of the recipe there, or that
+/// of the recipe for the item in the need or have entry there.
+pub fn doc_at(program: &Program, offset: usize) -> Option<&str> {
+    let contains = |span: Span| (span.start..=span.end).contains(&offset);
+    if let Some(recipe) = program.recipe_section.0.iter().find(|r| contains(r.span)) {
+        return recipe.doc.as_deref();
+    }
+
+    let needs = program.need_section.0.iter();
+    let needs = needs
+        .filter(|need| contains(need.span))
+        .map(|need| &need.stack);
+    let haves = program.haves();
+    let haves = haves
+        .filter(|have| contains(have.span))
+        .map(|have| &have.value);
+    let item = &needs.chain(haves).next()?.item;
+    program.default_recipe(item)?.doc.as_deref()
+}
+
+/// Where `item` is defined in the document: its default recipe, or else the first have or
+/// need entry for it. Recipes from included documents are not in this one.
+pub fn definition(program: &Program, item: &Item) -> Option<Span> {
+    let default = program.default_recipe(item);
+    let recipe = program
+        .recipe_section
+        .0
+        .iter()
+        .find(|recipe| Some(&recipe.value) == default);
+    let have = || {
+        let mut haves = program.haves();
+        haves.find(|have| have.item == *item)
+    };
+    let need = || {
+        let mut needs = program.need_section.0.iter();
+        needs.find(|need| need.stacks().any(|stack| stack.item == *item))
+    };
+
+    recipe
+        .map(|recipe| recipe.span)
+        .or_else(|| have().map(|have| have.span))
+        .or_else(|| need().map(|need| need.span))
+}
+
+/// The recipe that is added by [insert_recipe], meant to be filled in by the user.
+pub const RECIPE_TEMPLATE: &str = "1 output = 1 input";
+
+/// Create the edit that adds [RECIPE_TEMPLATE] to the end of the recipe section.
+pub fn insert_recipe(program: &Program, source: &str) -> Option<TextEdit> {
+    let last = program.recipe_section.0.last().map(|recipe| recipe.span);
+    let start = section_end(source, "recipes", last)?;
+
+    Some(TextEdit {
+        span: Span { start, end: start },
+        text: format!("\n- {RECIPE_TEMPLATE}"),
+    })
+}
+
+/// Create the edit that adds `recipe`, with its notes, to the end of the recipe section.
+pub fn add_recipe(program: &Program, source: &str, recipe: &Recipe) -> Option<TextEdit> {
+    let last = program.recipe_section.0.last().map(|recipe| recipe.span);
+    let start = section_end(source, "recipes", last)?;
+
+    Some(TextEdit {
+        span: Span { start, end: start },
+        text: recipe_lines(recipe)
+            .iter()
+            .map(|line| format!("\n{line}"))
+            .collect(),
+    })
+}
+
+/// A section that lists item stacks.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum StackSection {
+    Need,
+    Have,
+    /// The `have <player>:` section of a player, which is added if there is none yet.
+    Inventory(String),
+}
+
+/// Create the edit that adds `stacks` to the end of a section.
+pub fn insert_stacks(
+    program: &Program,
+    source: &str,
+    section: StackSection,
+    stacks: &[ItemStack],
+) -> Option<TextEdit> {
+    let entries: String = stacks.iter().map(|stack| format!("\n- {stack}")).collect();
+    let have_end = || {
+        section_end(
+            source,
+            "have",
+            program.have_section.0.last().map(|h| h.span),
+        )
+    };
+    let (start, text) = match section {
+        StackSection::Need => {
+            let last = program.need_section.0.last().map(|n| n.span);
+            (section_end(source, "need", last)?, entries)
+        }
+        StackSection::Have => (have_end()?, entries),
+        StackSection::Inventory(player) => {
+            let inventories = &program.inventories;
+            match inventories.iter().find(|i| i.player.value == player) {
+                Some(inventory) => {
+                    let header_end = inventory.player.span.end;
+                    let header_end = header_end + source[header_end..].find(':')? + 1;
+                    let header = Span {
+                        start: header_end,
+                        end: header_end,
+                    };
+                    let last = inventory.items.last().map(|item| item.span);
+                    let last = commented_span(source, last.unwrap_or(header));
+                    (last.end, entries)
+                }
+                None => {
+                    // new inventories go after the other ones
+                    let last = inventories.last().map(|inventory| {
+                        let last = inventory.items.last().map(|item| item.span);
+                        last.unwrap_or(inventory.player.span).end
+                    });
+                    let start = last.or_else(have_end)?;
+                    (start, format!("\nhave {player}:{entries}"))
+                }
+            }
+        }
+    };
+
+    Some(TextEdit {
+        span: Span { start, end: start },
+        text,
+    })
+}
+
+/// The offset right after the last entry of a section, or after its header if it is empty.
+///
+/// Comments at the end of that line stay where they are.
+fn section_end(source: &str, head: &str, last_entry: Option<Span>) -> Option<usize> {
+    if let Some(last) = last_entry {
+        return Some(commented_span(source, last).end);
+    }
+
+    // the header is always on its own line, after which the entries start
+    let header = format!("{head}:");
+    let mut offset = 0;
+    source.split_inclusive('\n').find_map(|line| {
+        let content = line.split('#').next().unwrap_or_default();
+        let end = (content.trim_end() == header).then_some(offset + line.trim_end().len());
+        offset += line.len();
+        end
+    })
+}
+
+/// Which columns of tab-separated rows hold what, counting from 0.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TsvColumns {
+    pub item: usize,
+    pub count: usize,
+}
+
+/// How numbers are written, which depends on the locale of the spreadsheet they come from.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum NumberFormat {
+    /// `1,000.5`
+    #[default]
+    DecimalPoint,
+    /// `1.000,5`, as used in most of Europe.
+    DecimalComma,
+}
+
+impl NumberFormat {
+    /// Read a count, ignoring thousands separators. Fractions are rounded up, since a part
+    /// of an item still takes a whole one.
+    pub fn parse_count(self, text: &str) -> Option<u64> {
+        let (thousands, decimal) = match self {
+            NumberFormat::DecimalPoint => (',', '.'),
+            NumberFormat::DecimalComma => ('.', ','),
+        };
+
+        let text = text.replace([thousands, '_', ' ', '\u{a0}'], "");
+        let (whole, fraction) = text.split_once(decimal).unwrap_or((&text, ""));
+        let whole = whole.parse::<u64>().ok()?;
+        if !fraction.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        let partial = fraction.chars().any(|c| c != '0');
+        whole.checked_add(u64::from(partial))
+    }
+}
+
+/// Read item stacks from tab-separated rows, such as ones pasted from a spreadsheet.
+///
+/// Rows for the same item are added up, so a list of items per storage location
+/// turns into one total per item. Rows without a valid count, such as headers,
+/// are skipped.
+pub fn parse_tsv(tsv: &str, columns: TsvColumns, numbers: NumberFormat) -> Vec<ItemStack> {
+    let mut totals: IndexMap<String, u64> = IndexMap::new();
+
+    for row in tsv.lines() {
+        let cells: Vec<&str> = row.split('\t').map(str::trim).collect();
+        let (Some(item), Some(count)) = (cells.get(columns.item), cells.get(columns.count)) else {
+            continue;
+        };
+
+        // these characters can't be part of an item name, and `#` would start a comment
+        let item = item.replace(['+', '=', '|', '#'], " ");
+        let item = item.split_whitespace().collect::<Vec<_>>().join(" ");
+        let count = numbers.parse_count(count);
+
+        if let (false, Some(count)) = (item.is_empty(), count) {
+            let total = totals.entry(item).or_default();
+            *total = count.saturating_add(*total);
+        }
+    }
+
+    totals
+        .into_iter()
+        .map(|(item, count)| ItemStack {
+            count,
+            item: Item::new(item),
+        })
+        .collect()
+}
+
+/// A line in the comparison of two versions of a document.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DiffLine<'a> {
+    Unchanged(&'a str),
+    Added(&'a str),
+    Removed(&'a str),
+}
+
+/// How many cells the table of [diff_lines] may have, so it takes a few megabytes at most.
+/// Versions with too many changed lines for that are shown as replaced completely.
+const MAX_DIFF_CELLS: usize = 1_000_000;
+
+/// Compare two versions of a document line by line.
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // most changes are small, so only the part between the unchanged start and end needs work
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut diff: Vec<DiffLine> = old[..prefix]
+        .iter()
+        .map(|l| DiffLine::Unchanged(l))
+        .collect();
+    let unchanged_end = old[old.len() - suffix..]
+        .iter()
+        .map(|l| DiffLine::Unchanged(l));
+    if old_middle.len().saturating_mul(new_middle.len()) > MAX_DIFF_CELLS {
+        diff.extend(old_middle.iter().map(|l| DiffLine::Removed(l)));
+        diff.extend(new_middle.iter().map(|l| DiffLine::Added(l)));
+        diff.extend(unchanged_end);
+        return diff;
+    }
+
+    // lcs[i][j] is the length of the longest common subsequence of old_middle[i..] and new_middle[j..]
+    let mut lcs = vec![vec![0usize; new_middle.len() + 1]; old_middle.len() + 1];
+    for i in (0..old_middle.len()).rev() {
+        for j in (0..new_middle.len()).rev() {
+            lcs[i][j] = if old_middle[i] == new_middle[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < old_middle.len() || j < new_middle.len() {
+        if i < old_middle.len() && j < new_middle.len() && old_middle[i] == new_middle[j] {
+            diff.push(DiffLine::Unchanged(old_middle[i]));
+            i += 1;
+            j += 1;
+        } else if j < new_middle.len() && (i == old_middle.len() || lcs[i][j + 1] >= lcs[i + 1][j])
+        {
+            diff.push(DiffLine::Added(new_middle[j]));
+            j += 1;
+        } else {
+            diff.push(DiffLine::Removed(old_middle[i]));
+            i += 1;
+        }
+    }
+    diff.extend(unchanged_end);
+
+    diff
+}
+
+/// Expand a span to cover the full line(s) it is on, including the line ending.
+///
+/// If the span is on the last line, the preceding line ending is included
+/// instead so removing the span doesn't leave an empty line behind.
+fn line_span(source: &str, span: Span) -> Span {
+    let start = source[..span.start].rfind('\n').map_or(0, |i| i + 1);
+
+    match source[span.end..].find('\n') {
+        Some(i) => Span {
+            start,
+            end: span.end + i + 1,
+        },
+        None => {
+            let before = &source[..start];
+            let ending_len = if before.ends_with("\r\n") {
+                2
+            } else if before.ends_with('\n') {
+                1
+            } else {
+                0
+            };
+
+            Span {
+                start: start - ending_len,
+                end: source.len(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logic::{Item, Program, Span};
+
+    use super::{
+        add_recipe, definition, diff_lines, doc_at, find_duplicates, format, insert_recipe,
+        insert_stacks, line_span, parse_tsv, sort_sections, DiffLine, NumberFormat, SortOrder,
+        StackSection, TextEdit, TsvColumns,
+    };
+
+    #[test]
+    fn test_find_duplicates() {
+        let input =
+            "need:\n- 10 iron\n- 1 gold\n- 5 iron\n- 1 iron | 1 gold\nhave:\n- 1 iron\nrecipes:\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        let duplicates = find_duplicates(&program);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(&*duplicates[0].item.0, "iron");
+        assert_eq!(duplicates[0].total_count(), 15);
+    }
+
+    #[test]
+    fn test_merge_duplicates() {
+        let input = "need:\n- 10 iron\n- 1 gold\n- 5 iron\nhave:\nrecipes:\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        let duplicates = find_duplicates(&program);
+        assert_eq!(
+            duplicates[0].merge(input),
+            vec![
+                TextEdit {
+                    span: Span { start: 8, end: 15 },
+                    text: "15 iron".into(),
+                },
+                TextEdit {
+                    span: Span { start: 25, end: 34 },
+                    text: "".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_exact_duplicates() {
+        let input = "need:\n- exact 4 stick\n- 4 stick\n- exact 2 stick\nhave:\nrecipes:\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        // the plain need is not merged into the exact ones
+        let duplicates = find_duplicates(&program);
+        assert_eq!(duplicates.len(), 1);
+        assert!(duplicates[0].exact);
+        assert_eq!(duplicates[0].entries.len(), 2);
+        assert_eq!(duplicates[0].merge(input)[0].text, "exact 6 stick");
+    }
+
+    #[test]
+    fn test_sort_sections() {
+        let input = "need:\n- 2 b\n- 1 a\n- 3 C\nhave:\nrecipes:\n- 4 y = 1 a\n- 1 x = 1 b\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        let apply = |edits: Vec<TextEdit>| {
+            let mut output = input.to_string();
+            for edit in edits.iter().rev() {
+                output.replace_range(edit.span.start..edit.span.end, &edit.text);
+            }
+            output
+        };
+
+        assert_eq!(
+            apply(sort_sections(&program, input, SortOrder::Alphabetical)),
+            "need:\n- 1 a\n- 2 b\n- 3 C\nhave:\nrecipes:\n- 1 x = 1 b\n- 4 y = 1 a\n"
+        );
+        assert_eq!(
+            apply(sort_sections(&program, input, SortOrder::Count)),
+            "need:\n- 3 C\n- 2 b\n- 1 a\nhave:\nrecipes:\n- 4 y = 1 a\n- 1 x = 1 b\n"
+        );
+
+        let input =
+            "need:\nhave:\nrecipes:\n## from the forge\n## at night\n- 1 y = 1 a\n- 1 x = 1 b\n";
+        let program = Program::parse_from_string(input).unwrap();
+        let mut output = input.to_string();
+        for edit in sort_sections(&program, input, SortOrder::Alphabetical)
+            .iter()
+            .rev()
+        {
+            output.replace_range(edit.span.start..edit.span.end, &edit.text);
+        }
+        assert_eq!(
+            output,
+            "need:\nhave:\nrecipes:\n- 1 x = 1 b\n## from the forge\n## at night\n- 1 y = 1 a\n"
+        );
+
+        // plain comments between doc comment lines move along, the ones above them don't
+        let input = "need:\nhave:\nrecipes:\n# smelting\n## from the forge\n  # todo\n\
+            ## at night\n- 1 y = 1 a\n- 1 x = 1 b\n";
+        let program = Program::parse_from_string(input).unwrap();
+        let mut output = input.to_string();
+        for edit in sort_sections(&program, input, SortOrder::Alphabetical)
+            .iter()
+            .rev()
+        {
+            output.replace_range(edit.span.start..edit.span.end, &edit.text);
+        }
+        assert_eq!(
+            output,
+            "need:\nhave:\nrecipes:\n# smelting\n- 1 x = 1 b\n## from the forge\n  # todo\n\
+            ## at night\n- 1 y = 1 a\n"
+        );
+
+        let input = "need:\n- 1 b # for the base\n- 1 a\r\nhave:\nrecipes:\n";
+        let program = Program::parse_from_string(input).unwrap();
+        let mut output = input.to_string();
+        for edit in sort_sections(&program, input, SortOrder::Alphabetical)
+            .iter()
+            .rev()
+        {
+            output.replace_range(edit.span.start..edit.span.end, &edit.text);
+        }
+        assert_eq!(
+            output,
+            "need:\n- 1 a\n- 1 b # for the base\r\nhave:\nrecipes:\n"
+        );
+    }
+
+    #[test]
+    fn test_doc_at() {
+        let input = "need:\n- 1 x\nhave:\n- 1 y\nrecipes:\n## from the forge\n- 1 x = 1 y\n\
+            - 1 y = 1 z\n";
+        let program = Program::parse_from_string(input).unwrap();
+        let at = |text: &str| doc_at(&program, input.find(text).unwrap());
+
+        assert_eq!(at("1 x = "), Some("from the forge"));
+        assert_eq!(at("1 x\n"), Some("from the forge"));
+        assert_eq!(at("1 y\n"), None);
+        assert_eq!(at("recipes"), None);
+    }
+
+    #[test]
+    fn test_definition() {
+        let input = "need:\n- 1 x | 1 w\nhave:\n- 1 y\nrecipes:\n- 1 x = 1 y\n- prefer 1 x = 1 z\n";
+        let program = Program::parse_from_string(input).unwrap();
+        let at = |item: &str| {
+            let span = definition(&program, &Item::new(item))?;
+            Some(&input[span.start..span.end])
+        };
+
+        assert_eq!(at("x"), Some("prefer 1 x = 1 z"));
+        assert_eq!(at("y"), Some("1 y"));
+        assert_eq!(at("w"), Some("1 x | 1 w"));
+        assert_eq!(at("z"), None);
+    }
+
+    #[test]
+    fn test_format_options() {
+        let input =
+            "version:2\ninclude  base \nneed:\nhave:\nrecipes:\noptions:\n-need mode=best effort\n\
+            sources:\n- iron ore@ mines\ncategories:\n- ores:iron ore,  coal\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        assert_eq!(
+            format(&program, input),
+            "version: 2\n\ninclude base\n\noptions:\n- need mode = best effort\n\nsources:\n- iron ore @ mines\n\n\
+            categories:\n- ores: iron ore, coal\n\n\
+            need:\n\nhave:\n\nrecipes:\n"
+        );
+    }
+
+    #[test]
+    fn test_format() {
+        let input = "recipes:\n##  by hand \n- 1 x  =  2 a+1 b  \nneed:\n-   1 x\n- 1 a|2 b\nhave:\n- 5 a\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        assert_eq!(
+            format(&program, input),
+            "need:\n- 1 x\n- 1 a | 2 b\n\nhave:\n- 5 a\n\nrecipes:\n## by hand\n- 1 x = 2 a + 1 b\n"
+        );
+    }
+
+    #[test]
+    fn test_format_comments() {
+        let input = "# my notes about this plan\nneed:  # what to make\n-   1 x # for the base\n\
+            # - 2 z\nhave:\n# - 3 y disabled\nrecipes:\n## by hand\n# checked in game\n\
+            - 1 x = 1 y\n\n# todo: more recipes\n";
+        let program = Program::parse_from_string(input).unwrap();
+        let formatted = format(&program, input);
+
+        // formatting again changes nothing
+        let again = Program::parse_from_string(&formatted).unwrap();
+        assert_eq!(format(&again, &formatted), formatted);
+        assert_eq!(
+            formatted,
+            "# my notes about this plan\nneed: # what to make\n- 1 x # for the base\n\n\
+            # - 2 z\nhave:\n\n# - 3 y disabled\nrecipes:\n# checked in game\n## by hand\n\
+            - 1 x = 1 y\n# todo: more recipes\n"
+        );
+    }
+
+    #[test]
+    fn test_add_recipe() {
+        let other = Program::parse_from_string(
+            "need:\nhave:\nrecipes:\n## from the furnace\n- 1 iron = 1 ore + 1 coal\n",
+        )
+        .unwrap();
+        let recipe = other.recipes().next().unwrap();
+
+        let input = "need:\n- 1 iron\nhave:\nrecipes:\n- 1 x = 1 y\n";
+        let program = Program::parse_from_string(input).unwrap();
+        let edit = add_recipe(&program, input, recipe).unwrap();
+
+        let mut output = input.to_string();
+        output.replace_range(edit.span.start..edit.span.end, &edit.text);
+        assert_eq!(
+            output,
+            "need:\n- 1 iron\nhave:\nrecipes:\n- 1 x = 1 y\n## from the furnace\n- 1 iron = 1 ore + 1 coal\n"
+        );
+    }
+
+    #[test]
+    fn test_insert_recipe() {
+        let apply = |input: &str| {
+            let program = Program::parse_from_string(input).unwrap();
+            let edit = insert_recipe(&program, input).unwrap();
+
+            let mut output = input.to_string();
+            output.replace_range(edit.span.start..edit.span.end, &edit.text);
+            output
+        };
+
+        assert_eq!(
+            apply("need:\nhave:\nrecipes:\n- 1 x = 1 y\n"),
+            "need:\nhave:\nrecipes:\n- 1 x = 1 y\n- 1 output = 1 input\n"
+        );
+        assert_eq!(
+            apply("recipes:\nneed:\n- 1 x\nhave:\n"),
+            "recipes:\n- 1 output = 1 input\nneed:\n- 1 x\nhave:\n"
+        );
+
+        // comments stay on the line they are on
+        assert_eq!(
+            apply("need:\nhave:\nrecipes: # mine\n"),
+            "need:\nhave:\nrecipes: # mine\n- 1 output = 1 input\n"
+        );
+        assert_eq!(
+            apply("need:\nhave:\nrecipes:\n- 1 x = 1 y # mine\r\n"),
+            "need:\nhave:\nrecipes:\n- 1 x = 1 y # mine\n- 1 output = 1 input\r\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_tsv() {
+        let tsv = "Location\tItem\tCount\nchest\tiron ingot\t1,024\nbarrel\tgold\t3\n\
+            barrel\tiron ingot\t6\nbroken row\n";
+        let columns = TsvColumns { item: 1, count: 2 };
+
+        let stacks: Vec<String> = parse_tsv(tsv, columns, NumberFormat::DecimalPoint)
+            .iter()
+            .map(|stack| stack.to_string())
+            .collect();
+        assert_eq!(stacks, ["1030 iron ingot", "3 gold"]);
+
+        let tsv = "chest\tScrew #8\t4\n";
+        let stacks: Vec<String> = parse_tsv(tsv, columns, NumberFormat::DecimalPoint)
+            .iter()
+            .map(|stack| stack.to_string())
+            .collect();
+        assert_eq!(stacks, ["4 Screw 8"]);
+    }
+
+    #[test]
+    fn test_parse_count() {
+        let point = NumberFormat::DecimalPoint;
+        assert_eq!(point.parse_count("1,024"), Some(1024));
+        assert_eq!(point.parse_count("1,000.5"), Some(1001));
+        assert_eq!(point.parse_count("2.00"), Some(2));
+
+        let comma = NumberFormat::DecimalComma;
+        assert_eq!(comma.parse_count("1.000,5"), Some(1001));
+        assert_eq!(comma.parse_count("1.024"), Some(1024));
+        assert_eq!(comma.parse_count("1 024"), Some(1024));
+        assert_eq!(comma.parse_count("1,5"), Some(2));
+        assert_eq!(comma.parse_count("Count"), None);
+    }
+
+    #[test]
+    fn test_insert_stacks() {
+        let input = "need:\nhave:\n- 1 a\nrecipes:\n";
+        let program = Program::parse_from_string(input).unwrap();
+        let stacks = parse_tsv(
+            "b\t2\nc\t3",
+            TsvColumns { item: 0, count: 1 },
+            NumberFormat::default(),
+        );
+
+        let apply = |edit: TextEdit| {
+            let mut output = input.to_string();
+            output.replace_range(edit.span.start..edit.span.end, &edit.text);
+            output
+        };
+
+        assert_eq!(
+            apply(insert_stacks(&program, input, StackSection::Need, &stacks).unwrap()),
+            "need:\n- 2 b\n- 3 c\nhave:\n- 1 a\nrecipes:\n"
+        );
+        assert_eq!(
+            apply(insert_stacks(&program, input, StackSection::Have, &stacks).unwrap()),
+            "need:\nhave:\n- 1 a\n- 2 b\n- 3 c\nrecipes:\n"
+        );
+
+        let alice = || StackSection::Inventory("alice".to_string());
+        let input = "need:\nhave:\n- 1 a\nrecipes:\n";
+        let with_alice = apply(insert_stacks(&program, input, alice(), &stacks).unwrap());
+        assert_eq!(
+            with_alice,
+            "need:\nhave:\n- 1 a\nhave alice:\n- 2 b\n- 3 c\nrecipes:\n"
+        );
+        let program = Program::parse_from_string(&with_alice).unwrap();
+        let edit = insert_stacks(&program, &with_alice, alice(), &stacks[..1]).unwrap();
+        assert_eq!(edit.span.start, with_alice.find("\nrecipes").unwrap());
+        let bob = StackSection::Inventory("bob".to_string());
+        let edit = insert_stacks(&program, &with_alice, bob, &stacks[..1]).unwrap();
+        assert_eq!(edit.text, "\nhave bob:\n- 2 b");
+        assert_eq!(edit.span.start, with_alice.find("\nrecipes").unwrap());
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        let old = "need:\n- 1 a\n- 2 b\nhave:\nrecipes:\n";
+        let new = "need:\n- 2 b\n- 3 c\nhave:\nrecipes:\n";
+
+        assert_eq!(
+            diff_lines(old, new),
+            vec![
+                DiffLine::Unchanged("need:"),
+                DiffLine::Removed("- 1 a"),
+                DiffLine::Unchanged("- 2 b"),
+                DiffLine::Added("- 3 c"),
+                DiffLine::Unchanged("have:"),
+                DiffLine::Unchanged("recipes:"),
+            ]
+        );
+        assert_eq!(diff_lines("", "a"), vec![DiffLine::Added("a")]);
+        assert_eq!(diff_lines("a", ""), vec![DiffLine::Removed("a")]);
+
+        // too many changed lines to compare are replaced as a whole
+        let old: String = (0..2000).map(|i| format!("- {i} a\n")).collect();
+        let new: String = (0..2000).map(|i| format!("- {i} b\n")).collect();
+        let old = format!("need:\n{old}have:\n");
+        let new = format!("need:\n{new}have:\n");
+        let diff = diff_lines(&old, &new);
+        assert_eq!(diff.len(), 4002);
+        assert_eq!(diff[0], DiffLine::Unchanged("need:"));
+        assert_eq!(diff[1], DiffLine::Removed("- 0 a"));
+        assert_eq!(diff[2001], DiffLine::Added("- 0 b"));
+        assert_eq!(diff[4001], DiffLine::Unchanged("have:"));
+    }
+
+    #[test]
+    fn test_line_span() {
+        let input = "a\n- b\n- c";
+        assert_eq!(
+            line_span(input, Span { start: 4, end: 5 }),
+            Span { start: 2, end: 6 }
+        );
+        assert_eq!(
+            line_span(input, Span { start: 8, end: 9 }),
+            Span { start: 5, end: 9 }
+        );
+        assert_eq!(
+            line_span("a\r\n- c", Span { start: 5, end: 6 }),
+            Span { start: 1, end: 6 }
+        );
+    }
+}
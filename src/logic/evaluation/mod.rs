@@ -1,123 +1,545 @@
 use indexmap::IndexMap;
-use std::collections::HashMap;
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
-use super::{Item, ItemStack, Program, Recipe};
+use super::{
+    options::{NeedMode, Options, RecipeLoops, SurplusSharing},
+    report::{CraftingNode, Report, TraceAction, TraceEvent},
+    Annotation, Coverage, Diagnostic, Imbalance, Item, ItemStack, Need, Program, Recipe, Severity,
+    Statistics,
+};
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum EvaluationError {
     MaxDepthExceeded,
+    /// A count got too large to be represented.
+    CountOverflow,
+    /// The recipe for this item creates 0 of it, so it can never craft enough.
+    RecipeWithoutOutput(Item),
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Context {
     /// Items that can be used for crafting
     items_available: IndexMap<Item, u64>,
+    /// Surplus of earlier needs that later needs may not use, with [SurplusSharing::Off].
+    /// Added back to [Context::items_available] at the end.
+    items_set_aside: IndexMap<Item, u64>,
+    /// What the recipes of the current need added to [Context::items_available], as
+    /// surplus or byproducts.
+    items_made_spare: IndexMap<Item, u64>,
     /// Items that are required to craft the item but are missing
     items_missing: IndexMap<Item, u64>,
+    /// The total amount of each item that was asked for, whether it was
+    /// available, crafted or missing.
+    items_requested: IndexMap<Item, u64>,
+
+    /// The recipe for each item we can craft.
+    recipes: Rc<Recipes>,
+    /// How many more recipes may be tried for items that have several, shared between all
+    /// copies of the context. Every try works out everything below the item again, so
+    /// nested alternatives would take exponential time without a limit.
+    choices_left: Rc<Cell<usize>>,
+
+    options: Options,
+
+    /// The recipes that are being run, from the need down to the current item. A recipe
+    /// that runs again in a loop is only added once, by its outermost run.
+    crafting: HashSet<usize>,
+
+    /// How often each recipe in [Recipes::list] ran, by index.
+    executed_recipes: IndexMap<usize, u64>,
+
+    /// The amount of items that were taken from [Context::items_available].
+    items_taken: u64,
+    /// The amount of items that were added to [Context::items_missing].
+    items_missing_total: u64,
+
+    /// How well each need entry could be covered, in order.
+    need_coverage: Vec<Coverage>,
 
-    /// A map with a recipe for each item we can craft.
-    recipes: HashMap<Item, Recipe>,
+    /// How each need entry was created, in order.
+    crafting_tree: Vec<CraftingNode>,
 
-    executed_recipes: IndexMap<Recipe, u64>,
+    /// The alternative that was made for each need entry, in order.
+    chosen_needs: Vec<ItemStack>,
 
-    /// The current recursion depth. Limited to [Context::MAX_DEPTH].
+    /// The indices of the need entries that were dropped in [NeedMode::BestEffort].
+    dropped_needs: Vec<usize>,
+
+    /// Every step that was taken, in order.
+    trace: Vec<TraceEvent>,
+
+    /// How many recipes deep the current item is, from the need down.
     depth: usize,
+    /// The deepest [Context::depth] that was reached.
+    max_depth: usize,
+    /// How many of the recipes in [Context::crafting] were already running further up,
+    /// which only happens in recipe loops. Limited to [Context::MAX_DEPTH].
+    loop_depth: usize,
+}
+
+/// The recipes of a program, indexed once so evaluating doesn't have to copy or hash them.
+///
+/// This is shared between copies of a [Context], which are made for alternatives and
+/// [NeedMode::BestEffort].
+#[derive(Debug, Default)]
+struct Recipes {
+    list: Vec<Recipe>,
+    /// The indices in [Recipes::list] of the recipes that craft each item, in order.
+    by_output: HashMap<Item, Vec<usize>>,
+}
+
+impl Recipes {
+    /// The recipes that craft `item`, with their index in [Recipes::list].
+    fn get(&self, item: &Item) -> impl Iterator<Item = (usize, &Recipe)> {
+        let indices = self
+            .by_output
+            .get(item)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        indices.iter().map(|&index| (index, &self.list[index]))
+    }
+}
+
+/// Something [Context::create_items] still has to do.
+enum Task {
+    /// Take or craft a stack of an item.
+    Request(ItemStack),
+    /// Run a recipe, waiting for its next input.
+    Craft(Craft),
+    /// Try several recipes for an item, waiting for the one that is being tried.
+    Choose(Box<Choice>),
+}
+
+/// A recipe run that is waiting for its inputs, see [Context::craft].
+struct Craft {
+    /// The node of the item being crafted, with the inputs that are done so far.
+    node: CraftingNode,
+    /// How many of the item had to be crafted, the rest is surplus.
+    count_needed: u64,
+    /// The index of the recipe in [Recipes::list].
+    recipe_index: usize,
+    /// The recipe multiplied by how often it runs.
+    recipe: Recipe,
+    /// Whether the recipe was already running further up, see [Context::loop_depth].
+    looped: bool,
+}
+
+/// The recipes for an item that are tried one after another, each on a copy of the context
+/// from before the first one.
+struct Choice {
+    base: Context,
+    /// The node of the item before any recipe ran.
+    node: CraftingNode,
+    count_needed: u64,
+    /// The indices of the recipes in [Recipes::list], and how many of them were started.
+    candidates: Vec<usize>,
+    tried: usize,
+    /// The context and node of the recipe that left the fewest items missing so far.
+    best: Option<(Context, CraftingNode)>,
+    first_error: Option<EvaluationError>,
 }
 
 impl Context {
+    /// How often a recipe can run again inside itself before the evaluation fails.
     pub const MAX_DEPTH: usize = 128;
 
+    /// How many recipes are tried on top of the first one, for all items together, before
+    /// the remaining items with several recipes just use the first one.
+    pub const MAX_CHOICES: usize = 64;
+
     /// Create a new context for a given program
-    pub fn new(program: &Program) -> Self {
-        let mut ctx: Self = Default::default();
+    pub fn new(program: &Program, options: &Options) -> Result<Self, EvaluationError> {
+        let mut ctx = Self {
+            options: options.clone(),
+            ..Default::default()
+        };
 
-        for have in &program.have_section.0 {
-            *ctx.items_available.entry(have.item.clone()).or_default() += have.count;
+        for have in program.haves() {
+            add(counter(&mut ctx.items_available, &have.item), have.count)?;
         }
 
-        for recipe in &program.recipe_section.0 {
-            let already_existed = ctx
-                .recipes
-                .insert(recipe.output.item.clone(), recipe.clone());
-
-            if already_existed.is_some() {
-                log::error!("tried to add recipe for {:?} but there already was one. old one gets overwritten.", recipe.output);
-            }
+        let mut recipes = Recipes::default();
+        for recipe in program.usable_recipes() {
+            let alternatives = recipes.by_output.entry(recipe.output.item.clone());
+            alternatives.or_default().push(recipes.list.len());
+            recipes.list.push(recipe.clone());
         }
+        ctx.recipes = Rc::new(recipes);
+        ctx.choices_left = Rc::new(Cell::new(Self::MAX_CHOICES));
 
-        ctx
+        Ok(ctx)
+    }
+
+    /// Take `item_needed` from the available items, crafting whatever is not there.
+    ///
+    /// Recipe trees can be much deeper than the call stack allows, especially on wasm, so
+    /// this works through an explicit stack of [Task]s instead of calling itself for the
+    /// inputs of a recipe.
+    fn create_items(&mut self, item_needed: &ItemStack) -> Result<CraftingNode, EvaluationError> {
+        let mut tasks = vec![Task::Request(item_needed.clone())];
+        // the result of the task that was finished last, for the task below it
+        let mut finished = None;
+        while let Some(task) = tasks.pop() {
+            finished = match (task, finished.take()) {
+                (Task::Request(stack), None) => self.request(&stack, &mut tasks).transpose(),
+                (Task::Craft(craft), Some(input)) => self.resume_craft(craft, input, &mut tasks),
+                (Task::Choose(choice), Some(attempt)) => {
+                    self.resume_choice(*choice, attempt, &mut tasks)
+                }
+                _ => unreachable!("only tasks below the one that finished wait for a result"),
+            };
+        }
+        finished.expect("the first task always finishes")
     }
 
-    fn create_items(&mut self, item_needed: &ItemStack) -> Result<(), EvaluationError> {
+    /// Take `item_needed` from the available items and start crafting the rest, returning
+    /// the node if that is already enough or [None] if it has to wait for other tasks.
+    fn request(
+        &mut self,
+        item_needed: &ItemStack,
+        tasks: &mut Vec<Task>,
+    ) -> Result<Option<CraftingNode>, EvaluationError> {
         let mut item_count_needed = item_needed.count;
-        log::debug!("Need {item_count_needed} of {}", &item_needed.item.0);
+        self.trace(&item_needed.item, TraceAction::Requested, item_count_needed);
+
+        add(
+            counter(&mut self.items_requested, &item_needed.item),
+            item_count_needed,
+        )?;
 
         // try to take items from our existing stash
         {
-            let count_available = self
-                .items_available
-                .entry(item_needed.item.clone())
-                .or_default();
+            let count_available = counter(&mut self.items_available, &item_needed.item);
             let count_available_to_use = item_count_needed.min(*count_available);
-            log::debug!(
-                "{count_available} of {} is already available, will use {count_available_to_use}",
-                &item_needed.item.0
-            );
             *count_available -= count_available_to_use;
             item_count_needed -= count_available_to_use;
+            add(&mut self.items_taken, count_available_to_use)?;
+
+            if count_available_to_use > 0 {
+                self.trace(
+                    &item_needed.item,
+                    TraceAction::Taken,
+                    count_available_to_use,
+                );
+            }
         }
 
+        let mut node = CraftingNode {
+            stack: item_needed.clone(),
+            taken_from_inventory: item_needed.count - item_count_needed,
+            recipe_runs: 0,
+            crafted: 0,
+            missing: 0,
+            inputs: vec![],
+        };
+
         // early return if we already have everything
         if item_count_needed == 0 {
-            return Ok(());
+            return Ok(Some(node));
         }
 
         // find a recipe to craft the remaining items needed
-        // this currently only supports recipes that return 1 item kind
-        let Some(recipe) = self.recipes.get(&item_needed.item).cloned() else {
+        let recipes = Rc::clone(&self.recipes);
+        let (running, free): (Vec<_>, Vec<_>) = recipes
+            .get(&item_needed.item)
+            .partition(|(index, _)| self.crafting.contains(index));
+        // a recipe that is already running further up is a loop, and this is its seed
+        let candidates = if free.is_empty() && self.options.recipe_loops == RecipeLoops::Error {
+            running
+        } else {
+            free
+        };
+        let candidates: Vec<usize> = match candidates.iter().find(|(_, recipe)| recipe.preferred) {
+            Some(&(preferred, _)) => vec![preferred],
+            None => candidates.into_iter().map(|(index, _)| index).collect(),
+        };
+
+        let Some(&first) = candidates.first() else {
             // if no recipe is found, add these items to the missing items pile
-            log::info!("Could not find recipe to create {}, adding it to items required", item_needed.item.0);
-            *self.items_missing.entry(item_needed.item.clone()).or_default() += item_count_needed;
+            self.trace(&item_needed.item, TraceAction::Missing, item_count_needed);
+            add(
+                counter(&mut self.items_missing, &item_needed.item),
+                item_count_needed,
+            )?;
+            add(&mut self.items_missing_total, item_count_needed)?;
 
-            return Ok(());
+            node.missing = item_count_needed;
+            return Ok(Some(node));
         };
+        if candidates.len() == 1 || self.choices_left.get() == 0 {
+            return self.craft(node, item_needed, item_count_needed, first, tasks);
+        }
 
-        // we have a known recipe, now execute it until we have all the items we need
-        // we create a pseudo-recipe
-        let mut item_count_created = 0;
+        // every recipe starts from the same inventory
+        tasks.push(Task::Choose(Box::new(Choice {
+            base: self.clone(),
+            node: node.clone(),
+            count_needed: item_count_needed,
+            candidates,
+            tried: 1,
+            best: None,
+            first_error: None,
+        })));
+        self.craft(node, item_needed, item_count_needed, first, tasks)
+    }
 
-        // integer div that rounds up. see also https://github.com/rust-lang/rfcs/issues/2844
-        let iterations_needed = (item_count_needed + recipe.output.count - 1) / recipe.output.count;
-        let multiplied_recipe = recipe.multiplied_by(iterations_needed);
-        {
-            self.depth += 1;
+    /// Start running the recipe at `recipe_index` in [Recipes::list] until
+    /// `item_count_needed` more of `item_needed` are made, returning the node if the recipe
+    /// has no inputs to wait for.
+    fn craft(
+        &mut self,
+        mut node: CraftingNode,
+        item_needed: &ItemStack,
+        item_count_needed: u64,
+        recipe_index: usize,
+        tasks: &mut Vec<Task>,
+    ) -> Result<Option<CraftingNode>, EvaluationError> {
+        let recipes = Rc::clone(&self.recipes);
+        let recipe = &recipes.list[recipe_index];
+        if recipe.output.count == 0 {
+            return Err(EvaluationError::RecipeWithoutOutput(
+                recipe.output.item.clone(),
+            ));
+        }
 
-            if self.depth > Self::MAX_DEPTH {
+        let iterations_needed = item_count_needed.div_ceil(recipe.output.count);
+        let multiplied_recipe = recipe
+            .checked_multiplied_by(iterations_needed)
+            .ok_or(EvaluationError::CountOverflow)?;
+        self.trace(
+            &item_needed.item,
+            TraceAction::Crafted {
+                runs: iterations_needed,
+            },
+            multiplied_recipe.output.count,
+        );
+
+        self.depth += 1;
+        self.max_depth = self.max_depth.max(self.depth);
+
+        // only a loop runs a recipe again further down, long chains of recipes are fine
+        let looped = self.crafting.contains(&recipe_index);
+        if looped {
+            self.loop_depth += 1;
+            if self.loop_depth > Self::MAX_DEPTH {
                 return Err(EvaluationError::MaxDepthExceeded);
             }
+        }
 
-            // mark this recipe as being executed
-            *self.executed_recipes.entry(recipe).or_default() += iterations_needed;
+        // mark this recipe as being executed
+        let total_runs = self.executed_recipes.entry(recipe_index).or_default();
+        add(total_runs, iterations_needed)?;
+        // the report multiplies each recipe by its total runs, so that has to fit too
+        let mut counts = recipe.outputs().chain(&recipe.inputs);
+        if counts.any(|stack| stack.count.checked_mul(*total_runs).is_none()) {
+            return Err(EvaluationError::CountOverflow);
+        }
+        node.recipe_runs = iterations_needed;
+        if !looped {
+            self.crafting.insert(recipe_index);
+        }
 
-            for input in &multiplied_recipe.inputs {
-                self.create_items(input)?;
+        let craft = Craft {
+            node,
+            count_needed: item_count_needed,
+            recipe_index,
+            recipe: multiplied_recipe,
+            looped,
+        };
+        match craft.recipe.inputs.first() {
+            Some(input) => {
+                let input = input.clone();
+                tasks.push(Task::Craft(craft));
+                tasks.push(Task::Request(input));
+                Ok(None)
             }
+            None => self.finish_craft(craft).map(Some),
+        }
+    }
+
+    /// Add a finished `input` to `craft`, and request the next input or finish the craft if
+    /// that was the last one.
+    fn resume_craft(
+        &mut self,
+        mut craft: Craft,
+        input: Result<CraftingNode, EvaluationError>,
+        tasks: &mut Vec<Task>,
+    ) -> Option<Result<CraftingNode, EvaluationError>> {
+        let input = match input {
+            Ok(input) => input,
+            Err(e) => return Some(Err(e)),
+        };
+        craft.node.inputs.push(input);
 
-            self.depth -= 1;
-            item_count_created += multiplied_recipe.output.count;
+        match craft.recipe.inputs.get(craft.node.inputs.len()) {
+            Some(next) => {
+                let next = next.clone();
+                tasks.push(Task::Craft(craft));
+                tasks.push(Task::Request(next));
+                None
+            }
+            None => Some(self.finish_craft(craft)),
         }
+    }
 
-        let items_created_too_many = item_count_created - item_count_needed;
+    /// Add what `craft` made now that its inputs are there, with whatever is left over going
+    /// to [Context::items_available].
+    fn finish_craft(&mut self, craft: Craft) -> Result<CraftingNode, EvaluationError> {
+        let Craft {
+            mut node,
+            count_needed,
+            recipe_index,
+            recipe,
+            looped,
+        } = craft;
 
-        *self
-            .items_available
-            .entry(item_needed.item.clone())
-            .or_default() += items_created_too_many;
+        if looped {
+            self.loop_depth -= 1;
+        } else {
+            self.crafting.remove(&recipe_index);
+        }
+        self.depth -= 1;
 
-        Ok(())
+        for byproduct in &recipe.byproducts {
+            self.trace(&byproduct.item, TraceAction::Byproduct, byproduct.count);
+            add(
+                counter(&mut self.items_available, &byproduct.item),
+                byproduct.count,
+            )?;
+            let spare = counter(&mut self.items_made_spare, &byproduct.item);
+            *spare = spare.saturating_add(byproduct.count);
+        }
+
+        let items_created_too_many = recipe.output.count - count_needed;
+        add(
+            counter(&mut self.items_available, &recipe.output.item),
+            items_created_too_many,
+        )?;
+        // no more than what is available is set aside, so this can't be too little
+        let spare = counter(&mut self.items_made_spare, &recipe.output.item);
+        *spare = spare.saturating_add(items_created_too_many);
+        node.crafted = recipe.output.count;
+
+        Ok(node)
+    }
+
+    /// Remember how the last recipe of `choice` went and try the next one, or pick the one
+    /// that left the fewest items missing once they are all tried or no more may be, see
+    /// [Context::MAX_CHOICES]. Earlier ones are preferred if they are equally good.
+    fn resume_choice(
+        &mut self,
+        mut choice: Choice,
+        attempt: Result<CraftingNode, EvaluationError>,
+        tasks: &mut Vec<Task>,
+    ) -> Option<Result<CraftingNode, EvaluationError>> {
+        match attempt {
+            Ok(node) => {
+                let better = choice
+                    .best
+                    .as_ref()
+                    .is_none_or(|(best, _)| self.items_missing_total < best.items_missing_total);
+                if better {
+                    choice.best = Some((std::mem::take(self), node));
+                }
+            }
+            Err(e) => {
+                choice.first_error.get_or_insert(e);
+            }
+        }
+
+        // the context may have been moved into the best attempt, but the base shares the limit
+        let choices_left = Rc::clone(&choice.base.choices_left);
+        let next = choice.candidates.get(choice.tried);
+        if let Some(&next) = next.filter(|_| choices_left.get() > 0) {
+            choices_left.set(choices_left.get() - 1);
+            choice.tried += 1;
+            *self = choice.base.clone();
+            let node = choice.node.clone();
+            let item_needed = node.stack.clone();
+            let count_needed = choice.count_needed;
+            tasks.push(Task::Choose(Box::new(choice)));
+            return self
+                .craft(node, &item_needed, count_needed, next, tasks)
+                .transpose();
+        }
+
+        // only fail if none of the recipes can be evaluated
+        match choice.best {
+            Some((context, node)) => {
+                *self = context;
+                Some(Ok(node))
+            }
+            None => Some(Err(choice.first_error.expect("there are recipes"))),
+        }
+    }
+
+    /// Add a step at the current depth to [Context::trace].
+    fn trace(&mut self, item: &Item, action: TraceAction, count: u64) {
+        self.trace.push(TraceEvent {
+            item: item.clone(),
+            depth: self.depth,
+            action,
+            count,
+        });
+    }
+
+    /// Create whichever alternative of `need` leaves the fewest items missing, preferring
+    /// earlier alternatives if they are equally good.
+    fn create_need(&mut self, need: &Need) -> Result<CraftingNode, EvaluationError> {
+        if need.alternatives.is_empty() {
+            self.chosen_needs.push(need.stack.clone());
+            return self.create_items(&need.stack);
+        }
+
+        let mut best: Option<(Context, CraftingNode)> = None;
+        let mut first_error = None;
+        for stack in need.stacks() {
+            // every alternative starts from the same inventory
+            let mut attempt = self.clone();
+            let node = match attempt.create_items(stack) {
+                Ok(node) => node,
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                    continue;
+                }
+            };
+
+            if let Some((best, _)) = &best {
+                if attempt.items_missing_total >= best.items_missing_total {
+                    continue;
+                }
+            }
+            attempt.chosen_needs.push(stack.clone());
+            best = Some((attempt, node));
+        }
+
+        // only fail if none of the alternatives can be evaluated
+        let (context, node) = best.ok_or_else(|| first_error.expect("needs have a stack"))?;
+        *self = context;
+        Ok(node)
+    }
+
+    /// Set aside the surplus and byproducts of the last need that are still available,
+    /// counting what the need used of an item as coming from what was there before it.
+    fn set_aside_surplus(&mut self) {
+        for (item, made) in self.items_made_spare.drain(..) {
+            let available = counter(&mut self.items_available, &item);
+            let spare = made.min(*available);
+            *available -= spare;
+            *counter(&mut self.items_set_aside, &item) += spare;
+        }
     }
 
     fn cleanup(&mut self) {
+        // these all came out of items_available, so adding them back can't overflow
+        for (item, count) in self.items_set_aside.drain(..) {
+            *self.items_available.entry(item).or_default() += count;
+        }
         self.items_available.retain(|_, v| *v != 0);
         self.items_missing.retain(|_, v| *v != 0);
     }
@@ -142,46 +564,391 @@ impl Context {
             .collect()
     }
 
+    /// How well each need entry could be covered, in the order of the need section.
+    pub fn get_need_coverage(&self) -> &[Coverage] {
+        &self.need_coverage
+    }
+
+    /// How each need entry was created, in the order of the need section.
+    pub fn get_crafting_tree(&self) -> &[CraftingNode] {
+        &self.crafting_tree
+    }
+
+    /// The total amount of `item` that was asked for during evaluation.
+    pub fn get_requested_count(&self, item: &Item) -> u64 {
+        self.items_requested.get(item).copied().unwrap_or_default()
+    }
+
+    /// How many times `recipe` was executed.
+    pub fn get_recipe_runs(&self, recipe: &Recipe) -> u64 {
+        // recipes that were replaced by one in the document itself never run
+        self.recipes
+            .get(&recipe.output.item)
+            .find(|(_, used)| *used == recipe)
+            .and_then(|(index, _)| self.executed_recipes.get(&index))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Every step that was taken during evaluation, in order.
+    pub fn get_trace(&self) -> &[TraceEvent] {
+        &self.trace
+    }
+
+    /// Counters that show how much work the evaluation took.
+    pub fn get_statistics(&self) -> Statistics {
+        Statistics {
+            recipes: self.recipes.list.len(),
+            recipe_runs: self
+                .executed_recipes
+                .values()
+                .fold(0, |total, &runs| total.saturating_add(runs)),
+            items_touched: self.items_requested.len(),
+            max_depth: self.max_depth,
+        }
+    }
+
+    /// The needs that were dropped in [NeedMode::BestEffort], in order.
+    /// The indices of the need entries that were dropped, see [Context::get_dropped_needs].
+    pub fn get_dropped_need_indices(&self) -> &[usize] {
+        &self.dropped_needs
+    }
+
+    pub fn get_dropped_needs(&self) -> Vec<ItemStack> {
+        self.dropped_needs
+            .iter()
+            .map(|&index| self.chosen_needs[index].clone())
+            .collect()
+    }
+
+    /// The recipes that were executed, each with how often it ran, in the same order as
+    /// [Context::get_executed_recipes].
+    pub fn get_recipe_run_counts(&self) -> Vec<(&Recipe, u64)> {
+        let executed = self.executed_recipes.iter().rev();
+        executed
+            .map(|(&index, &runs)| (&self.recipes.list[index], runs))
+            .collect()
+    }
+
     pub fn get_executed_recipes(&self) -> Vec<Recipe> {
         self.executed_recipes
             .iter()
             .rev()
-            .map(|(recipe, count)| recipe.multiplied_by(*count))
+            .map(|(&index, count)| {
+                self.recipes.list[index]
+                    .checked_multiplied_by(*count)
+                    .expect("recipe runs are checked for overflow during evaluation")
+            })
             .collect()
     }
 }
 
-/// Calculate the crafting path for the current program.
-pub fn evaluate(program: &Program) -> Result<Context, EvaluationError> {
-    let mut ctx = Context::new(program);
+/// Calculate the crafting path for `program` with the settings of its options section.
+pub fn evaluate(program: &Program) -> Result<Report, EvaluationError> {
+    evaluate_with_options(program, &super::options::read(program).0)
+}
+
+/// Calculate the crafting path for `program`, with `options` instead of the ones in its
+/// options section.
+///
+/// [Program::evaluate_with_options] makes its report the same way, so callers that read or
+/// change the options themselves get the same report as the app.
+pub fn evaluate_with_options(
+    program: &Program,
+    options: &Options,
+) -> Result<Report, EvaluationError> {
+    evaluate_in_context(program, options).map(|(_, report)| report)
+}
+
+/// Like [evaluate_with_options], but also keeping the whole context for the diagnostics and
+/// annotations that [Program::evaluate_with_options] adds to the report.
+pub(super) fn evaluate_in_context(
+    program: &Program,
+    options: &Options,
+) -> Result<(Context, Report), EvaluationError> {
+    let context = run(program, options)?;
+    let report = Report::new(&context, program);
+    Ok((context, report))
+}
+
+/// Evaluate `program` with `options`, without making a report.
+fn run(program: &Program, options: &Options) -> Result<Context, EvaluationError> {
+    let mut ctx = Context::new(program, options)?;
+    create_needs(&mut ctx, program)?;
+    Ok(ctx)
+}
+
+/// Create every need of `program` in order, with what is available in `ctx`.
+fn create_needs(ctx: &mut Context, program: &Program) -> Result<(), EvaluationError> {
+    for (index, need) in program.need_section.0.iter().enumerate() {
+        let taken_before = ctx.items_taken;
+        let missing_before = ctx.items_missing_total;
+
+        // needs that can't be made from what is there get undone again
+        let undo = (ctx.options.need_mode == NeedMode::BestEffort).then(|| ctx.clone());
+        ctx.items_made_spare.clear();
+
+        let node = ctx.create_need(need)?;
 
-    for need in &program.need_section.0 {
-        ctx.create_items(need)?;
+        if let Some(mut undo) = undo {
+            if ctx.items_missing_total > missing_before {
+                undo.chosen_needs.extend(ctx.chosen_needs.pop());
+                undo.dropped_needs.push(index);
+                undo.need_coverage.push(Coverage::None);
+                *ctx = undo;
+                continue;
+            }
+        }
+
+        ctx.crafting_tree.push(node);
+        if ctx.options.surplus_sharing == SurplusSharing::Off {
+            ctx.set_aside_surplus();
+        }
+
+        let coverage = if ctx.items_missing_total == missing_before {
+            Coverage::Full
+        } else if ctx.items_taken == taken_before {
+            Coverage::None
+        } else {
+            Coverage::Partial
+        };
+        ctx.need_coverage.push(coverage);
     }
     ctx.cleanup();
-    log::debug!("context after calculations: {ctx:#?}");
+    log::trace!("context after calculations: {ctx:#?}");
 
-    Ok(ctx)
+    Ok(())
+}
+
+/// Check that every item that was used during evaluation also came from somewhere.
+///
+/// For each item, the have section, crafted items and missing items must add up to the
+/// needed items, items used as ingredients and leftover items. Anything else means the
+/// evaluator lost or made up items.
+pub fn audit(program: &Program, context: &Context) -> Vec<Imbalance> {
+    // u128 so sums of valid u64 counts can't overflow
+    let mut balance = IndexMap::<&Item, (u128, u128)>::new();
+    let mut produce = |item, count| balance.entry(item).or_default().0 += count as u128;
+
+    for have in program.haves() {
+        produce(&have.item, have.count);
+    }
+    let executed = || {
+        let executed = context.executed_recipes.iter();
+        executed.map(|(&index, runs)| (&context.recipes.list[index], runs))
+    };
+    for (recipe, runs) in executed() {
+        for output in recipe.outputs() {
+            produce(&output.item, output.count * runs);
+        }
+    }
+    for (item, count) in &context.items_missing {
+        produce(item, *count);
+    }
+
+    let mut consume = |item, count| balance.entry(item).or_default().1 += count as u128;
+
+    // only the needs that were made, which for alternatives can be any of the stacks
+    for (index, need) in context.chosen_needs.iter().enumerate() {
+        if !context.dropped_needs.contains(&index) {
+            consume(&need.item, need.count);
+        }
+    }
+    for (recipe, runs) in executed() {
+        for input in &recipe.inputs {
+            consume(&input.item, input.count * runs);
+        }
+    }
+    for (item, count) in &context.items_available {
+        consume(item, *count);
+    }
+
+    balance
+        .into_iter()
+        .filter(|(_, (produced, consumed))| produced != consumed)
+        .map(|(item, (produced, consumed))| Imbalance {
+            item: item.clone(),
+            produced,
+            consumed,
+        })
+        .collect()
+}
+
+/// The counter for `item` in `map`, only copying the item name if it isn't in there yet.
+fn counter<'m>(map: &'m mut IndexMap<Item, u64>, item: &Item) -> &'m mut u64 {
+    let index = match map.get_index_of(item) {
+        Some(index) => index,
+        None => map.insert_full(item.clone(), 0).0,
+    };
+    map.get_index_mut(index)
+        .expect("the index was just looked up")
+        .1
+}
+
+/// Add `count` to `total`, failing instead of overflowing.
+fn add(total: &mut u64, count: u64) -> Result<(), EvaluationError> {
+    *total = total
+        .checked_add(count)
+        .ok_or(EvaluationError::CountOverflow)?;
+    Ok(())
+}
+
+/// Find need entries that can never be produced, because no recipe creates
+/// them and they are not in the have section.
+///
+/// For needs with alternatives, this looks at the alternative that was made.
+pub fn unproducible_needs(program: &Program, context: &Context) -> Vec<Diagnostic> {
+    program
+        .need_section
+        .0
+        .iter()
+        .zip(&context.chosen_needs)
+        .filter(|(_, chosen)| context.items_missing.contains_key(&chosen.item))
+        .filter(|(_, chosen)| context.recipes.get(&chosen.item).next().is_none())
+        .filter(|(_, chosen)| !program.haves().any(|h| h.item == chosen.item))
+        .map(|(need, chosen)| Diagnostic {
+            span: need.span,
+            severity: Severity::Warning,
+            message: format!("no recipe known for '{}'", chosen.item.0),
+        })
+        .collect()
+}
+
+/// Point out the need entries that were dropped in [NeedMode::BestEffort].
+pub fn dropped_needs(program: &Program, context: &Context) -> Vec<Diagnostic> {
+    context
+        .dropped_needs
+        .iter()
+        .filter_map(|&index| program.need_section.0.get(index))
+        .map(|need| Diagnostic {
+            span: need.span,
+            severity: Severity::Warning,
+            message: "dropped, because not everything for it is available".into(),
+        })
+        .collect()
+}
+
+/// Warn about `exact` need entries that are made more of than needed, because their recipe
+/// makes more at a time.
+pub fn overproduced_exact_needs(program: &Program, context: &Context) -> Vec<Diagnostic> {
+    // dropped needs have no crafting tree
+    let made = program
+        .need_section
+        .0
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !context.dropped_needs.contains(index))
+        .map(|(_, need)| need);
+
+    made.zip(context.get_crafting_tree())
+        .filter(|(need, _)| need.exact)
+        .filter_map(|(need, node)| {
+            // needs that can't be covered fully have no surplus
+            let surplus = (node.taken_from_inventory + node.crafted)
+                .checked_sub(node.stack.count)
+                .filter(|&surplus| surplus > 0)?;
+            // whichever recipe was used, it made the same amount each run
+            let per_run = node.crafted.checked_div(node.recipe_runs)?;
+            Some(Diagnostic {
+                span: need.span,
+                severity: Severity::Warning,
+                message: format!(
+                    "{surplus} more {} than needed will be made, because its recipe makes {per_run} at a time",
+                    node.stack.item.0
+                ),
+            })
+        })
+        .collect()
+}
+
+/// Warn about have entries for items that no need or recipe mentions, which are often
+/// typos such as `iron ignot`.
+pub fn unused_haves(program: &Program) -> Vec<Diagnostic> {
+    let needs = program.need_section.0.iter().flat_map(|need| {
+        let need = &need.value;
+        std::iter::once(&need.stack).chain(&need.alternatives)
+    });
+    let recipes = program
+        .recipes()
+        .flat_map(|recipe| recipe.outputs().chain(&recipe.inputs));
+    let used: HashSet<&Item> = needs.chain(recipes).map(|stack| &stack.item).collect();
+
+    program
+        .haves()
+        .filter(|have| !used.contains(&have.item))
+        .map(|have| Diagnostic {
+            span: have.span,
+            severity: Severity::Warning,
+            message: format!("'{}' is not used by any need or recipe", have.item.0),
+        })
+        .collect()
+}
+
+/// Annotate need entries with their total requested count and recipes with
+/// how often they run.
+pub fn annotations(program: &Program, context: &Context) -> Vec<Annotation> {
+    let needs = program.need_section.0.iter().zip(&context.chosen_needs);
+    let needs = needs.filter_map(|(need, chosen)| {
+        let total = context.get_requested_count(&chosen.item);
+        (total > chosen.count).then(|| Annotation {
+            span: need.span,
+            label: format!("→ needs {total} {} total", chosen.item.0),
+        })
+    });
+
+    let recipes = program.recipe_section.0.iter().filter_map(|recipe| {
+        let runs = context.get_recipe_runs(recipe);
+        (runs > 0).then(|| Annotation {
+            span: recipe.span,
+            label: format!("runs ×{runs}"),
+        })
+    });
+
+    needs.chain(recipes).collect()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::logic::{evaluation::EvaluationError, *};
+    use crate::logic::{
+        evaluation::EvaluationError,
+        report::{CraftingNode, TraceAction, TraceEvent},
+        *,
+    };
 
-    use super::evaluate;
+    use super::{
+        annotations, audit, dropped_needs, evaluate_with_options, overproduced_exact_needs, run,
+        unproducible_needs, unused_haves, Context,
+    };
+
+    /// Evaluate `program` with the options from its options section.
+    fn evaluate(program: &Program) -> Result<Context, EvaluationError> {
+        run(program, &options::read(program).0)
+    }
 
     #[test]
     fn test_single_recipe_has_everything() {
         let program = Program {
+            version: None,
+            includes: vec![],
+            included_recipes: vec![],
+            inventories: vec![],
+            option_section: OptionSection(vec![]),
+            source_section: SourceSection(vec![]),
+            category_section: CategorySection(vec![]),
             need_section: NeedSection(vec![ItemStack {
                 count: 1,
                 item: Item("output".into()),
-            }]),
+            }
+            .into()]),
             have_section: HaveSection(vec![ItemStack {
                 count: 1,
                 item: Item("input".into()),
-            }]),
+            }
+            .into()]),
             recipe_section: RecipeSection(vec![Recipe {
+                doc: None,
+                preferred: false,
+                byproducts: vec![],
                 output: ItemStack {
                     count: 1,
                     item: Item("output".into()),
@@ -190,7 +957,8 @@ mod tests {
                     count: 1,
                     item: Item("input".into()),
                 }],
-            }]),
+            }
+            .into()]),
         };
 
         let context = evaluate(&program).unwrap();
@@ -201,12 +969,23 @@ mod tests {
     #[test]
     fn test_single_recipe_has_nothing() {
         let program = Program {
+            version: None,
+            includes: vec![],
+            included_recipes: vec![],
+            inventories: vec![],
+            option_section: OptionSection(vec![]),
+            source_section: SourceSection(vec![]),
+            category_section: CategorySection(vec![]),
             need_section: NeedSection(vec![ItemStack {
                 count: 1,
                 item: Item("output".into()),
-            }]),
+            }
+            .into()]),
             have_section: HaveSection(vec![]),
             recipe_section: RecipeSection(vec![Recipe {
+                doc: None,
+                preferred: false,
+                byproducts: vec![],
                 output: ItemStack {
                     count: 1,
                     item: Item("output".into()),
@@ -215,7 +994,8 @@ mod tests {
                     count: 1,
                     item: Item("input".into()),
                 }],
-            }]),
+            }
+            .into()]),
         };
 
         let context = evaluate(&program).unwrap();
@@ -232,16 +1012,28 @@ mod tests {
     #[test]
     fn test_double_recipe_has_everything() {
         let program = Program {
+            version: None,
+            includes: vec![],
+            included_recipes: vec![],
+            inventories: vec![],
+            option_section: OptionSection(vec![]),
+            source_section: SourceSection(vec![]),
+            category_section: CategorySection(vec![]),
             need_section: NeedSection(vec![ItemStack {
                 count: 1,
                 item: Item("output".into()),
-            }]),
+            }
+            .into()]),
             have_section: HaveSection(vec![ItemStack {
                 count: 1,
                 item: Item("input".into()),
-            }]),
+            }
+            .into()]),
             recipe_section: RecipeSection(vec![
                 Recipe {
+                    doc: None,
+                    preferred: false,
+                    byproducts: vec![],
                     output: ItemStack {
                         count: 1,
                         item: Item("output".into()),
@@ -250,8 +1042,12 @@ mod tests {
                         count: 1,
                         item: Item("middle".into()),
                     }],
-                },
+                }
+                .into(),
                 Recipe {
+                    doc: None,
+                    preferred: false,
+                    byproducts: vec![],
                     output: ItemStack {
                         count: 1,
                         item: Item("middle".into()),
@@ -260,7 +1056,8 @@ mod tests {
                         count: 1,
                         item: Item("input".into()),
                     }],
-                },
+                }
+                .into(),
             ]),
         };
 
@@ -272,13 +1069,24 @@ mod tests {
     #[test]
     fn test_double_recipe_has_nothing() {
         let program = Program {
+            version: None,
+            includes: vec![],
+            included_recipes: vec![],
+            inventories: vec![],
+            option_section: OptionSection(vec![]),
+            source_section: SourceSection(vec![]),
+            category_section: CategorySection(vec![]),
             need_section: NeedSection(vec![ItemStack {
                 count: 1,
                 item: Item("output".into()),
-            }]),
+            }
+            .into()]),
             have_section: HaveSection(vec![]),
             recipe_section: RecipeSection(vec![
                 Recipe {
+                    doc: None,
+                    preferred: false,
+                    byproducts: vec![],
                     output: ItemStack {
                         count: 1,
                         item: Item("output".into()),
@@ -287,8 +1095,12 @@ mod tests {
                         count: 1,
                         item: Item("middle".into()),
                     }],
-                },
+                }
+                .into(),
                 Recipe {
+                    doc: None,
+                    preferred: false,
+                    byproducts: vec![],
                     output: ItemStack {
                         count: 1,
                         item: Item("middle".into()),
@@ -297,7 +1109,8 @@ mod tests {
                         count: 1,
                         item: Item("input".into()),
                     }],
-                },
+                }
+                .into(),
             ]),
         };
 
@@ -315,12 +1128,23 @@ mod tests {
     #[test]
     fn test_run_recipe_multiple_times() {
         let program = Program {
+            version: None,
+            includes: vec![],
+            included_recipes: vec![],
+            inventories: vec![],
+            option_section: OptionSection(vec![]),
+            source_section: SourceSection(vec![]),
+            category_section: CategorySection(vec![]),
             need_section: NeedSection(vec![ItemStack {
                 count: 10,
                 item: Item("output".into()),
-            }]),
+            }
+            .into()]),
             have_section: HaveSection(vec![]),
             recipe_section: RecipeSection(vec![Recipe {
+                doc: None,
+                preferred: false,
+                byproducts: vec![],
                 output: ItemStack {
                     count: 1,
                     item: Item("output".into()),
@@ -329,7 +1153,8 @@ mod tests {
                     count: 1,
                     item: Item("input".into()),
                 }],
-            }]),
+            }
+            .into()]),
         };
 
         let context = evaluate(&program).unwrap();
@@ -346,19 +1171,31 @@ mod tests {
     #[test]
     fn test_need_items_dont_get_used_by_other_need_items() {
         let program = Program {
+            version: None,
+            includes: vec![],
+            included_recipes: vec![],
+            inventories: vec![],
+            option_section: OptionSection(vec![]),
+            source_section: SourceSection(vec![]),
+            category_section: CategorySection(vec![]),
             need_section: NeedSection(vec![
                 ItemStack {
                     count: 1,
                     item: Item("middle".into()),
-                },
+                }
+                .into(),
                 ItemStack {
                     count: 1,
                     item: Item("output".into()),
-                },
+                }
+                .into(),
             ]),
             have_section: HaveSection(vec![]),
             recipe_section: RecipeSection(vec![
                 Recipe {
+                    doc: None,
+                    preferred: false,
+                    byproducts: vec![],
                     output: ItemStack {
                         count: 1,
                         item: Item("output".into()),
@@ -367,8 +1204,12 @@ mod tests {
                         count: 1,
                         item: Item("middle".into()),
                     }],
-                },
+                }
+                .into(),
                 Recipe {
+                    doc: None,
+                    preferred: false,
+                    byproducts: vec![],
                     output: ItemStack {
                         count: 1,
                         item: Item("middle".into()),
@@ -377,7 +1218,8 @@ mod tests {
                         count: 1,
                         item: Item("input".into()),
                     }],
-                },
+                }
+                .into(),
             ]),
         };
 
@@ -395,18 +1237,30 @@ mod tests {
     #[test]
     fn test_can_have_duplicate_need_items() {
         let program = Program {
+            version: None,
+            includes: vec![],
+            included_recipes: vec![],
+            inventories: vec![],
+            option_section: OptionSection(vec![]),
+            source_section: SourceSection(vec![]),
+            category_section: CategorySection(vec![]),
             need_section: NeedSection(vec![
                 ItemStack {
                     count: 1,
                     item: Item("output".into()),
-                },
+                }
+                .into(),
                 ItemStack {
                     count: 1,
                     item: Item("output".into()),
-                },
+                }
+                .into(),
             ]),
             have_section: HaveSection(vec![]),
             recipe_section: RecipeSection(vec![Recipe {
+                doc: None,
+                preferred: false,
+                byproducts: vec![],
                 output: ItemStack {
                     count: 1,
                     item: Item("output".into()),
@@ -415,7 +1269,8 @@ mod tests {
                     count: 1,
                     item: Item("input".into()),
                 }],
-            }]),
+            }
+            .into()]),
         };
 
         let context = evaluate(&program).unwrap();
@@ -432,21 +1287,34 @@ mod tests {
     #[test]
     fn test_can_have_duplicate_have_items() {
         let program = Program {
+            version: None,
+            includes: vec![],
+            included_recipes: vec![],
+            inventories: vec![],
+            option_section: OptionSection(vec![]),
+            source_section: SourceSection(vec![]),
+            category_section: CategorySection(vec![]),
             need_section: NeedSection(vec![ItemStack {
                 count: 1,
                 item: Item("output".into()),
-            }]),
+            }
+            .into()]),
             have_section: HaveSection(vec![
                 ItemStack {
                     count: 1,
                     item: Item("input".into()),
-                },
+                }
+                .into(),
                 ItemStack {
                     count: 1,
                     item: Item("input".into()),
-                },
+                }
+                .into(),
             ]),
             recipe_section: RecipeSection(vec![Recipe {
+                doc: None,
+                preferred: false,
+                byproducts: vec![],
                 output: ItemStack {
                     count: 1,
                     item: Item("output".into()),
@@ -455,7 +1323,8 @@ mod tests {
                     count: 2,
                     item: Item("input".into()),
                 }],
-            }]),
+            }
+            .into()]),
         };
 
         let context = evaluate(&program).unwrap();
@@ -463,16 +1332,66 @@ mod tests {
         assert_eq!(context.get_available_items(), vec![]);
     }
 
+    #[test]
+    fn test_deep_recipe_chain() {
+        // a chain much longer than the loop limit, which isn't a loop
+        let recipes: String = (1..=1000)
+            .map(|i| format!("- 1 item {i} = 1 item {}\n", i - 1))
+            .collect();
+        let input = format!("need:\n- 2 item 1000\nhave:\n- 1 item 0\nrecipes:\n{recipes}");
+        let program = Program::parse_from_string(&input).unwrap();
+        let context = evaluate(&program).unwrap();
+
+        let missing: Vec<_> = context
+            .get_missing_items()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(missing, ["1 item 0"]);
+        assert_eq!(context.get_statistics().max_depth, 1000);
+        assert_eq!(audit(&program, &context), vec![]);
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_huge_counts() {
+        // recipes are multiplied by their runs instead of run one at a time, so this is instant
+        let input = "need:\n- 1000000000000 pickaxe\nhave:\n- 7 log\nrecipes:\n- 4 plank = 1 log\n\
+            - 4 stick = 2 plank\n- 1 pickaxe = 2 stick + 3 plank\n";
+        let program = Program::parse_from_string(input).unwrap();
+        let context = evaluate(&program).unwrap();
+
+        let missing: Vec<_> = context
+            .get_missing_items()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(missing, ["999999999993 log"]);
+        assert_eq!(context.get_statistics().recipe_runs, 2500000000000);
+        assert_eq!(audit(&program, &context), vec![]);
+    }
+
     #[test]
     #[ntest::timeout(100)]
     fn test_prevent_infinite_loop() {
         let program = Program {
+            version: None,
+            includes: vec![],
+            included_recipes: vec![],
+            inventories: vec![],
+            option_section: OptionSection(vec![]),
+            source_section: SourceSection(vec![]),
+            category_section: CategorySection(vec![]),
             need_section: NeedSection(vec![ItemStack {
                 count: 1,
                 item: Item("item".into()),
-            }]),
+            }
+            .into()]),
             have_section: HaveSection(vec![]),
             recipe_section: RecipeSection(vec![Recipe {
+                doc: None,
+                preferred: false,
+                byproducts: vec![],
                 output: ItemStack {
                     count: 1,
                     item: Item("item".into()),
@@ -481,7 +1400,8 @@ mod tests {
                     count: 1,
                     item: Item("item".into()),
                 }],
-            }]),
+            }
+            .into()]),
         };
 
         let result = evaluate(&program);
@@ -489,4 +1409,611 @@ mod tests {
         assert!(result.is_err());
         assert_eq!(result.unwrap_err(), EvaluationError::MaxDepthExceeded);
     }
+
+    #[test]
+    fn test_recipe_without_output() {
+        let input = "need:\n- 1 b\nhave:\nrecipes:\n- 0 b = 1 a\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        assert_eq!(
+            evaluate(&program).unwrap_err(),
+            EvaluationError::RecipeWithoutOutput(Item("b".into()))
+        );
+    }
+
+    #[test]
+    fn test_count_overflow() {
+        let inputs = [
+            "need:\n- 18446744073709551615 b\nhave:\nrecipes:\n- 1 b = 2 a\n",
+            "need:\n- 1 a\nhave:\n- 18446744073709551615 a\n- 1 a\nrecipes:\n",
+            "need:\n- 18446744073709551615 a\n- 1 a\nhave:\nrecipes:\n",
+        ];
+
+        for input in inputs {
+            let program = Program::parse_from_string(input).unwrap();
+            assert_eq!(
+                evaluate(&program).unwrap_err(),
+                EvaluationError::CountOverflow,
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_unproducible_needs() {
+        let program = Program {
+            version: None,
+            includes: vec![],
+            included_recipes: vec![],
+            inventories: vec![],
+            option_section: OptionSection(vec![]),
+            source_section: SourceSection(vec![]),
+            category_section: CategorySection(vec![]),
+            need_section: NeedSection(vec![
+                Spanned {
+                    value: Need {
+                        stack: ItemStack {
+                            count: 1,
+                            item: Item("iron blck".into()),
+                        },
+                        alternatives: vec![],
+                        exact: false,
+                    },
+                    span: Span { start: 8, end: 19 },
+                },
+                ItemStack {
+                    count: 1,
+                    item: Item("output".into()),
+                }
+                .into(),
+                ItemStack {
+                    count: 2,
+                    item: Item("input".into()),
+                }
+                .into(),
+            ]),
+            have_section: HaveSection(vec![ItemStack {
+                count: 1,
+                item: Item("input".into()),
+            }
+            .into()]),
+            recipe_section: RecipeSection(vec![Recipe {
+                doc: None,
+                preferred: false,
+                byproducts: vec![],
+                output: ItemStack {
+                    count: 1,
+                    item: Item("output".into()),
+                },
+                inputs: vec![ItemStack {
+                    count: 1,
+                    item: Item("raw".into()),
+                }],
+            }
+            .into()]),
+        };
+
+        let context = evaluate(&program).unwrap();
+        assert_eq!(
+            unproducible_needs(&program, &context),
+            vec![Diagnostic {
+                span: Span { start: 8, end: 19 },
+                severity: Severity::Warning,
+                message: "no recipe known for 'iron blck'".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_annotations() {
+        let program = Program {
+            version: None,
+            includes: vec![],
+            included_recipes: vec![],
+            inventories: vec![],
+            option_section: OptionSection(vec![]),
+            source_section: SourceSection(vec![]),
+            category_section: CategorySection(vec![]),
+            need_section: NeedSection(vec![
+                ItemStack {
+                    count: 1,
+                    item: Item("input".into()),
+                }
+                .into(),
+                ItemStack {
+                    count: 3,
+                    item: Item("output".into()),
+                }
+                .into(),
+            ]),
+            have_section: HaveSection(vec![]),
+            recipe_section: RecipeSection(vec![
+                Recipe {
+                    doc: None,
+                    preferred: false,
+                    byproducts: vec![],
+                    output: ItemStack {
+                        count: 2,
+                        item: Item("output".into()),
+                    },
+                    inputs: vec![ItemStack {
+                        count: 1,
+                        item: Item("input".into()),
+                    }],
+                }
+                .into(),
+                Recipe {
+                    doc: None,
+                    preferred: false,
+                    byproducts: vec![],
+                    output: ItemStack {
+                        count: 1,
+                        item: Item("unused".into()),
+                    },
+                    inputs: vec![],
+                }
+                .into(),
+            ]),
+        };
+
+        let context = evaluate(&program).unwrap();
+        let labels: Vec<_> = annotations(&program, &context)
+            .into_iter()
+            .map(|a| a.label)
+            .collect();
+        assert_eq!(labels, vec!["→ needs 3 input total", "runs ×2"]);
+    }
+
+    #[test]
+    fn test_need_coverage() {
+        let stack = |count, item: &str| ItemStack {
+            count,
+            item: Item(item.into()),
+        };
+        let program = Program {
+            version: None,
+            includes: vec![],
+            included_recipes: vec![],
+            inventories: vec![],
+            option_section: OptionSection(vec![]),
+            source_section: SourceSection(vec![]),
+            category_section: CategorySection(vec![]),
+            need_section: NeedSection(vec![
+                stack(1, "output").into(),
+                stack(2, "output").into(),
+                stack(1, "output").into(),
+            ]),
+            have_section: HaveSection(vec![stack(2, "input").into()]),
+            recipe_section: RecipeSection(vec![Recipe {
+                doc: None,
+                preferred: false,
+                byproducts: vec![],
+                output: stack(1, "output"),
+                inputs: vec![stack(1, "input")],
+            }
+            .into()]),
+        };
+
+        let context = evaluate(&program).unwrap();
+        assert_eq!(
+            context.get_need_coverage(),
+            &[Coverage::Full, Coverage::Partial, Coverage::None]
+        );
+    }
+
+    #[test]
+    fn test_alternative_needs() {
+        let input = "need:\n- 1 diamond sword | 1 iron sword | 1 stone sword\n\
+            have:\n- 2 iron\n- 1 stick\n\
+            recipes:\n\
+            - 1 diamond sword = 2 diamond + 1 stick\n\
+            - 1 iron sword = 2 iron + 1 stick\n\
+            - 1 stone sword = 2 stone + 1 stick\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        let context = evaluate(&program).unwrap();
+        assert_eq!(context.get_missing_items(), vec![]);
+        assert_eq!(&*context.get_crafting_tree()[0].stack.item.0, "iron sword");
+        assert_eq!(context.get_need_coverage(), [Coverage::Full]);
+        assert_eq!(audit(&program, &context), vec![]);
+
+        // equally good alternatives keep the first one
+        let input = "need:\n- 1 a | 1 b\nhave:\nrecipes:\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        let context = evaluate(&program).unwrap();
+        assert_eq!(
+            context.get_missing_items(),
+            vec![ItemStack {
+                count: 1,
+                item: Item("a".into()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_evaluate_with_options() {
+        let input = "need:\n- 1 sword\n- 1 shield\nhave:\n- 4 plank\n\
+            recipes:\n- 1 sword = 2 plank\n- 1 shield = 6 plank\n";
+        let program = Program::parse_from_string(input).unwrap();
+        assert_eq!(evaluate(&program).unwrap().get_dropped_needs(), vec![]);
+
+        // the given options are used instead of the empty options section
+        let options = options::Options {
+            need_mode: options::NeedMode::BestEffort,
+            ..Default::default()
+        };
+        let report = evaluate_with_options(&program, &options).unwrap();
+        let dropped: Vec<_> = report
+            .dropped_needs
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(dropped, ["1 shield"]);
+        assert_eq!(report.missing_items, []);
+
+        // without given options, the options section is used
+        let input = format!("options:\n- need mode = best effort\n{input}");
+        let program = Program::parse_from_string(&input).unwrap();
+        assert_eq!(super::evaluate(&program).unwrap(), report);
+    }
+
+    #[test]
+    fn test_best_effort() {
+        let input = "options:\n- need mode = best effort\n\
+            need:\n- 1 sword\n- 1 shield\n- 1 stick\n\
+            have:\n- 4 plank\n\
+            recipes:\n\
+            - 1 sword = 2 plank + 1 stick\n\
+            - 1 shield = 6 plank\n\
+            - 1 stick = 1 plank\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        let context = evaluate(&program).unwrap();
+        assert_eq!(context.get_missing_items(), vec![]);
+        assert_eq!(
+            context.get_dropped_needs(),
+            vec![ItemStack {
+                count: 1,
+                item: Item("shield".into()),
+            }]
+        );
+        assert_eq!(
+            context.get_need_coverage(),
+            [Coverage::Full, Coverage::None, Coverage::Full]
+        );
+        assert_eq!(context.get_available_items(), vec![]);
+        assert_eq!(audit(&program, &context), vec![]);
+
+        let dropped = dropped_needs(&program, &context);
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(
+            &input[dropped[0].span.start..dropped[0].span.end],
+            "1 shield"
+        );
+    }
+
+    #[test]
+    fn test_recipe_loop_seed() {
+        // a refiner loop that turns 1 carbon into 2
+        let input = "options:\n- recipe loops = seed\n\
+            need:\n- 10 carbon\n\
+            have:\n\
+            recipes:\n\
+            - 2 carbon = 1 condensed carbon\n\
+            - 1 condensed carbon = 1 carbon\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        let context = evaluate(&program).unwrap();
+        assert_eq!(
+            context.get_missing_items(),
+            vec![ItemStack {
+                count: 5,
+                item: Item("carbon".into()),
+            }]
+        );
+        assert_eq!(audit(&program, &context), vec![]);
+
+        // without the option, the loop is an error like before
+        let input = input.replace("recipe loops = seed", "recipe loops = error");
+        let program = Program::parse_from_string(&input).unwrap();
+        assert_eq!(
+            evaluate(&program).unwrap_err(),
+            EvaluationError::MaxDepthExceeded
+        );
+    }
+
+    #[test]
+    fn test_overproduced_exact_needs() {
+        let input = "need:\n- exact 6 stick\n- exact 2 stick\n- 1 plank\n\
+            have:\n- 1 plank\n\
+            recipes:\n\
+            - 4 stick = 2 plank\n\
+            - 4 plank = 1 log\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        let context = evaluate(&program).unwrap();
+        let warnings = overproduced_exact_needs(&program, &context);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].message,
+            "2 more stick than needed will be made, because its recipe makes 4 at a time"
+        );
+        assert_eq!(
+            &input[warnings[0].span.start..warnings[0].span.end],
+            "exact 6 stick"
+        );
+
+        // missing items are no surplus, whether nothing or only some of them are there
+        for input in [
+            "need:\n- exact 3 stick\nhave:\nrecipes:\n",
+            "need:\n- exact 3 stick\nhave:\n- 1 stick\nrecipes:\n",
+        ] {
+            let program = Program::parse_from_string(input).unwrap();
+            let context = evaluate(&program).unwrap();
+            assert_eq!(overproduced_exact_needs(&program, &context), []);
+        }
+    }
+
+    #[test]
+    fn test_unused_haves() {
+        let input = "need:\n- 1 pickaxe | 1 axe\n\
+            have:\n- 3 iron ignot\n- 2 stick\n- 1 log\n- 1 axe\n\
+            recipes:\n\
+            - 1 pickaxe = 2 stick + 3 iron ingot\n\
+            - 4 plank = 1 log\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        let warnings = unused_haves(&program);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].message,
+            "'iron ignot' is not used by any need or recipe"
+        );
+        assert_eq!(
+            &input[warnings[0].span.start..warnings[0].span.end],
+            "3 iron ignot"
+        );
+    }
+
+    #[test]
+    fn test_alternative_recipes() {
+        let missing = |input: &str| {
+            let evaluation = Program::parse_from_string(input)
+                .unwrap()
+                .evaluate_audited();
+            assert_eq!(evaluation.imbalances, Some(vec![]));
+            let report = evaluation.report.unwrap();
+            let missing: Vec<_> = report
+                .missing_items
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            missing.join(", ")
+        };
+
+        // the recipe that leaves the fewest items missing is used, or else the first one
+        let recipes = "recipes:\n- 1 iron = 1 ore\n- 1 iron = 2 scrap\n";
+        assert_eq!(
+            missing(&format!("need:\n- 2 iron\nhave:\n- 4 scrap\n{recipes}")),
+            ""
+        );
+        assert_eq!(
+            missing(&format!("need:\n- 2 iron\nhave:\n{recipes}")),
+            "2 ore"
+        );
+        // each craft picks again, so the scrap is used up first
+        assert_eq!(
+            missing(&format!(
+                "need:\n- 1 iron\n- 1 iron\nhave:\n- 2 scrap\n{recipes}"
+            )),
+            "1 ore"
+        );
+
+        // a preferred recipe is always used
+        let recipes = "recipes:\n- 1 iron = 1 ore\n- prefer 1 iron = 2 scrap\n";
+        assert_eq!(
+            missing(&format!("need:\n- 1 iron\nhave:\n- 1 ore\n{recipes}")),
+            "2 scrap"
+        );
+
+        // recipes that are already running are skipped while there are others
+        let input = "need:\n- 1 a\nhave:\nrecipes:\n- 1 a = 1 b\n- 1 b = 1 a\n- 1 b = 1 c\n";
+        assert_eq!(missing(input), "1 c");
+    }
+
+    #[test]
+    fn test_byproducts() {
+        let input = "need:\n- 1 refined oil\n- 1 gas\n- 3 gas\n\
+            have:\n- 8 crude oil\n\
+            recipes:\n\
+            - 1 refined oil + 2 gas = 4 crude oil\n\
+            - 1 gas = 2 crude oil\n";
+        let program = Program::parse_from_string(input).unwrap();
+        let evaluation = program.evaluate_audited();
+        assert_eq!(evaluation.imbalances, Some(vec![]));
+
+        // the byproduct covers the first gas need and part of the second
+        let report = evaluation.report.unwrap();
+        let recipes: Vec<_> = report
+            .executed_recipes
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            recipes,
+            [
+                "4 crude oil -> 2 gas",
+                "4 crude oil -> 1 refined oil + 2 gas"
+            ]
+        );
+        assert_eq!(report.missing_items, vec![]);
+        assert_eq!(report.leftover_items, vec![]);
+    }
+
+    #[test]
+    #[ntest::timeout(1000)]
+    fn test_deep_alternative_recipes() {
+        // trying both recipes of every item would take 2^40 evaluations
+        let mut input = String::from("need:\n- 1 item 0\nhave:\nrecipes:\n");
+        for i in 0..40 {
+            let next = i + 1;
+            input += &format!("- 1 item {i} = 1 item {next}\n- 1 item {i} = 2 item {next}\n");
+        }
+        let program = Program::parse_from_string(&input).unwrap();
+        let context = evaluate(&program).unwrap();
+
+        let missing: Vec<_> = context
+            .get_missing_items()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(missing, ["1 item 40"]);
+    }
+
+    #[test]
+    fn test_surplus_sharing() {
+        let input = "options:\n- surplus sharing = on\n\
+            need:\n- 1 stick\n- 1 stick\n\
+            have:\n\
+            recipes:\n\
+            - 4 stick = 2 plank\n";
+        let stack = |count, item: &str| ItemStack {
+            count,
+            item: Item(item.into()),
+        };
+
+        let program = Program::parse_from_string(input).unwrap();
+        let context = evaluate(&program).unwrap();
+        assert_eq!(context.get_missing_items(), vec![stack(2, "plank")]);
+        assert_eq!(context.get_available_items(), vec![stack(2, "stick")]);
+
+        // the second stick is crafted again instead of using the surplus of the first
+        let input = input.replace("surplus sharing = on", "surplus sharing = off");
+        let program = Program::parse_from_string(&input).unwrap();
+        let context = evaluate(&program).unwrap();
+        assert_eq!(context.get_missing_items(), vec![stack(4, "plank")]);
+        assert_eq!(context.get_available_items(), vec![stack(6, "stick")]);
+        assert_eq!(audit(&program, &context), vec![]);
+
+        // the surplus is set aside even if the need also used up what was there before
+        let input = "options:\n- surplus sharing = off\n\
+            need:\n- 7 x\n- 2 x\n\
+            have:\n- 5 x\n\
+            recipes:\n\
+            - 4 x = 1 y\n";
+        let program = Program::parse_from_string(input).unwrap();
+        let context = evaluate(&program).unwrap();
+        assert_eq!(context.get_missing_items(), vec![stack(2, "y")]);
+        assert_eq!(context.get_available_items(), vec![stack(4, "x")]);
+        assert_eq!(audit(&program, &context), vec![]);
+    }
+
+    #[test]
+    fn test_trace() {
+        let input = "need:\n- 2 stick\nhave:\n- 1 stick\nrecipes:\n- 4 stick = 2 plank\n";
+        let program = Program::parse_from_string(input).unwrap();
+        let event = |item: &str, depth, action, count| TraceEvent {
+            item: Item(item.into()),
+            depth,
+            action,
+            count,
+        };
+
+        let context = evaluate(&program).unwrap();
+        assert_eq!(
+            context.get_trace(),
+            [
+                event("stick", 0, TraceAction::Requested, 2),
+                event("stick", 0, TraceAction::Taken, 1),
+                event("stick", 0, TraceAction::Crafted { runs: 1 }, 4),
+                event("plank", 1, TraceAction::Requested, 2),
+                event("plank", 1, TraceAction::Missing, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_statistics() {
+        let input = include_str!("../parsing/example_input.txt");
+        let program = Program::parse_from_string(input).unwrap();
+
+        let context = evaluate(&program).unwrap();
+        assert_eq!(
+            context.get_statistics(),
+            Statistics {
+                recipes: 3,
+                recipe_runs: 3,
+                items_touched: 5,
+                max_depth: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn test_audit() {
+        let input = include_str!("../parsing/example_input.txt");
+        let program = Program::parse_from_string(input).unwrap();
+
+        let mut context = evaluate(&program).unwrap();
+        assert_eq!(audit(&program, &context), vec![]);
+
+        // a leftover item that was never made
+        context.items_available.insert(Item("stick".into()), 3);
+        assert_eq!(
+            audit(&program, &context),
+            vec![Imbalance {
+                item: Item("stick".into()),
+                produced: 4,
+                consumed: 5,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_crafting_tree() {
+        let stack = |count, item: &str| ItemStack {
+            count,
+            item: Item(item.into()),
+        };
+        let program = Program {
+            version: None,
+            includes: vec![],
+            included_recipes: vec![],
+            inventories: vec![],
+            option_section: OptionSection(vec![]),
+            source_section: SourceSection(vec![]),
+            category_section: CategorySection(vec![]),
+            need_section: NeedSection(vec![stack(3, "output").into()]),
+            have_section: HaveSection(vec![stack(1, "output").into(), stack(1, "input").into()]),
+            recipe_section: RecipeSection(vec![Recipe {
+                doc: None,
+                preferred: false,
+                byproducts: vec![],
+                output: stack(4, "output"),
+                inputs: vec![stack(2, "input")],
+            }
+            .into()]),
+        };
+
+        let context = evaluate(&program).unwrap();
+        assert_eq!(
+            context.get_crafting_tree(),
+            &[CraftingNode {
+                stack: stack(3, "output"),
+                taken_from_inventory: 1,
+                recipe_runs: 1,
+                crafted: 4,
+                missing: 0,
+                inputs: vec![CraftingNode {
+                    stack: stack(2, "input"),
+                    taken_from_inventory: 1,
+                    recipe_runs: 0,
+                    crafted: 0,
+                    missing: 1,
+                    inputs: vec![],
+                }],
+            }]
+        );
+    }
 }
@@ -0,0 +1,301 @@
+use super::{
+    options, Diagnostic, IncludePin, ItemStack, Program, Recipe, RecipeSuggestion, Severity,
+};
+
+impl Program {
+    /// Load the recipes of included documents into [Program::included_recipes], with errors
+    /// for includes that can't be resolved.
+    ///
+    /// `load` gets the content of a document by its name. Included documents can include
+    /// other documents, but not themselves. A document that is included more than once only
+    /// has its recipes added once.
+    pub fn resolve_includes(&mut self, load: impl Fn(&str) -> Option<String>) -> Vec<Diagnostic> {
+        let mut resolver = Resolver::new(&load);
+        let errors = self
+            .includes
+            .iter()
+            .filter_map(|include| {
+                let message = resolver.resolve(&include.value).err()?;
+                Some(Diagnostic {
+                    span: include.span,
+                    severity: Severity::Error,
+                    message,
+                })
+            })
+            .collect();
+
+        self.included_recipes = resolver.recipes;
+        // included recipes may spell items differently
+        options::normalize_item_names(self);
+        errors
+    }
+
+    /// The documents this one includes, directly or through other included documents, with
+    /// the fingerprint of their current content. Includes that can't be resolved are left
+    /// out.
+    pub fn include_pins(&self, load: impl Fn(&str) -> Option<String>) -> Vec<IncludePin> {
+        let mut resolver = Resolver::new(&load);
+        for include in &self.includes {
+            // errors are shown by resolve_includes already
+            let _ = resolver.resolve(&include.value);
+        }
+        resolver.resolved
+    }
+
+    /// Look through the recipes of other saved `documents`, as pairs of their name and
+    /// recipes, for recipes that make the `missing` items. Only the first recipe found for
+    /// each item is suggested.
+    ///
+    /// Documents this one already includes are skipped, since their recipes are used already.
+    /// The recipes are passed in instead of the content, so callers can keep them around
+    /// instead of reading every document again.
+    pub fn suggest_recipes<'d>(
+        &self,
+        missing: &[ItemStack],
+        documents: impl IntoIterator<Item = (&'d str, &'d [Recipe])>,
+    ) -> Vec<RecipeSuggestion> {
+        let mut suggestions: Vec<RecipeSuggestion> = vec![];
+        if missing.is_empty() {
+            return suggestions;
+        }
+        for (name, recipes) in documents {
+            if self.includes.iter().any(|include| include.value == name) {
+                continue;
+            }
+
+            for recipe in recipes {
+                let item = &recipe.output.item;
+                let is_missing = missing.iter().any(|stack| stack.item == *item);
+                let suggested = suggestions.iter().any(|s| s.recipe.output.item == *item);
+                if is_missing && !suggested {
+                    suggestions.push(RecipeSuggestion {
+                        document: name.to_string(),
+                        recipe: recipe.clone(),
+                    });
+                }
+            }
+        }
+        suggestions
+    }
+}
+
+impl IncludePin {
+    /// Write `pins` as text, one `<fingerprint> <name>` line each.
+    pub fn encode(pins: &[IncludePin]) -> String {
+        let lines = pins
+            .iter()
+            .map(|pin| format!("{:016x} {}\n", pin.fingerprint, pin.name));
+        lines.collect()
+    }
+
+    /// Read pins written by [IncludePin::encode], skipping lines that can't be read.
+    pub fn decode(text: &str) -> Vec<IncludePin> {
+        text.lines()
+            .filter_map(|line| {
+                let (fingerprint, name) = line.split_once(' ')?;
+                Some(IncludePin {
+                    name: name.to_string(),
+                    fingerprint: u64::from_str_radix(fingerprint, 16).ok()?,
+                })
+            })
+            .collect()
+    }
+
+    /// What is different about the pinned document now, or [None] if `load` still gets the
+    /// same content for it.
+    pub fn mismatch(&self, load: impl Fn(&str) -> Option<String>) -> Option<String> {
+        let name = &self.name;
+        match load(name) {
+            None => Some(format!("'{name}' is not saved here")),
+            Some(text) if fingerprint(&text) != self.fingerprint => {
+                Some(format!("'{name}' is different from when the link was made"))
+            }
+            Some(_) => None,
+        }
+    }
+}
+
+/// The 64-bit FNV-1a hash of `text`, which is the same on every platform and version.
+fn fingerprint(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+struct Resolver<'l> {
+    load: &'l dyn Fn(&str) -> Option<String>,
+    /// The documents that are currently being included, to detect cycles.
+    stack: Vec<String>,
+    /// The documents whose recipes were already added.
+    resolved: Vec<IncludePin>,
+    recipes: Vec<Recipe>,
+}
+
+impl<'l> Resolver<'l> {
+    fn new(load: &'l dyn Fn(&str) -> Option<String>) -> Self {
+        Self {
+            load,
+            stack: vec![],
+            resolved: vec![],
+            recipes: vec![],
+        }
+    }
+
+    fn resolve(&mut self, name: &str) -> Result<(), String> {
+        if self.stack.iter().any(|included| included == name) {
+            let chain = self.stack.join("' → '");
+            return Err(format!("include cycle: '{chain}' → '{name}'"));
+        }
+        if self.resolved.iter().any(|resolved| resolved.name == name) {
+            return Ok(());
+        }
+
+        let text = (self.load)(name).ok_or_else(|| format!("no saved document named '{name}'"))?;
+        let program = Program::parse_from_string(&text)
+            .map_err(|e| format!("could not parse '{name}': {}", e.message))?;
+
+        self.stack.push(name.to_string());
+        let result = program
+            .includes
+            .iter()
+            .try_for_each(|include| self.resolve(&include.value));
+        self.stack.pop();
+        result?;
+
+        let recipes = program.recipe_section.0.into_iter();
+        self.recipes.extend(recipes.map(|recipe| recipe.value));
+        self.resolved.push(IncludePin {
+            name: name.to_string(),
+            fingerprint: fingerprint(&text),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logic::{IncludePin, Program, Recipe, Severity};
+
+    fn load(name: &str) -> Option<String> {
+        let text = match name {
+            "base" => "need:\nhave:\nrecipes:\n- 4 plank = 1 log\n- 4 stick = 2 plank\n",
+            "tools" => "include base\nneed:\nhave:\nrecipes:\n- 1 pickaxe = 2 stick + 3 plank\n",
+            "loop a" => "include loop b\nneed:\nhave:\nrecipes:\n",
+            "loop b" => "include loop a\nneed:\nhave:\nrecipes:\n",
+            "broken" => "recipes:\n",
+            _ => return None,
+        };
+        Some(text.to_string())
+    }
+
+    #[test]
+    fn test_resolve_includes() {
+        let input = "include tools\ninclude base\nneed:\n- 1 pickaxe\nhave:\nrecipes:\n- 4 stick = 1 plank\n";
+        let mut program = Program::parse_from_string(input).unwrap();
+
+        assert_eq!(program.resolve_includes(load), vec![]);
+        let outputs: Vec<_> = program
+            .recipes()
+            .map(|recipe| recipe.output.to_string())
+            .collect();
+        assert_eq!(outputs, ["4 plank", "4 stick", "1 pickaxe", "4 stick"]);
+
+        // the document's own stick recipe is used
+        let report = program.evaluate().report.unwrap();
+        assert_eq!(report.missing_items.len(), 1);
+        assert_eq!(report.missing_items[0].to_string(), "1 log");
+    }
+
+    #[test]
+    fn test_suggest_recipes() {
+        let input =
+            "include base\nneed:\n- 1 pickaxe\nhave:\nrecipes:\n- 1 pickaxe = 2 stick + 3 iron\n";
+        let mut program = Program::parse_from_string(input).unwrap();
+        program.resolve_includes(load);
+        let report = program.evaluate().report.unwrap();
+
+        let documents = [
+            ("tools", load("tools").unwrap()),
+            ("broken", "recipes:\n- 1 iron = 1 ore\n".to_string()),
+            (
+                "smelting",
+                "need:\nhave:\nrecipes:\n- 2 iron = 1 ore\n- 1 log = 1 sapling\n".to_string(),
+            ),
+            (
+                "forestry",
+                "need:\nhave:\nrecipes:\n- 1 log = 2 sapling\n".to_string(),
+            ),
+        ];
+        // documents that can't be parsed have no recipes to suggest
+        let recipes: Vec<(&str, Vec<Recipe>)> = documents
+            .iter()
+            .map(|(name, text)| {
+                let program = Program::parse_from_string(text);
+                let recipes = program.map(|p| p.recipes().cloned().collect());
+                (*name, recipes.unwrap_or_default())
+            })
+            .collect();
+        let documents = recipes.iter().map(|(name, recipes)| (*name, &recipes[..]));
+        let suggestions = program.suggest_recipes(&report.missing_items, documents);
+
+        // only the first log recipe is used
+        let suggestions: Vec<_> = suggestions
+            .iter()
+            .map(|s| format!("{}: {}", s.document, s.recipe))
+            .collect();
+        assert_eq!(
+            suggestions,
+            ["smelting: 1 ore -> 2 iron", "smelting: 1 sapling -> 1 log"]
+        );
+
+        // an included document's recipes are already used
+        let base = Program::parse_from_string("need:\nhave:\nrecipes:\n- 1 log = 1 sapling\n");
+        let base: Vec<Recipe> = base.unwrap().recipes().cloned().collect();
+        let documents = [("base", &base[..])];
+        let suggestions = program.suggest_recipes(&report.missing_items, documents);
+        assert_eq!(suggestions, vec![]);
+    }
+
+    #[test]
+    fn test_include_pins() {
+        let input = "include tools\ninclude missing\nneed:\nhave:\nrecipes:\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        // included documents come before the ones that include them
+        let pins = program.include_pins(load);
+        let names: Vec<_> = pins.iter().map(|pin| pin.name.as_str()).collect();
+        assert_eq!(names, ["base", "tools"]);
+        assert_eq!(IncludePin::decode(&IncludePin::encode(&pins)), pins);
+        assert!(pins.iter().all(|pin| pin.mismatch(load).is_none()));
+
+        let changed = |name: &str| match name {
+            "base" => Some("need:\nhave:\nrecipes:\n- 2 plank = 1 log\n".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            pins[0].mismatch(changed).unwrap(),
+            "'base' is different from when the link was made"
+        );
+        assert_eq!(
+            pins[1].mismatch(changed).unwrap(),
+            "'tools' is not saved here"
+        );
+    }
+
+    #[test]
+    fn test_resolve_includes_errors() {
+        let input = "include loop a\ninclude missing\ninclude broken\nneed:\nhave:\nrecipes:\n";
+        let mut program = Program::parse_from_string(input).unwrap();
+
+        let errors = program.resolve_includes(load);
+        let messages: Vec<_> = errors.iter().map(|error| error.message.as_str()).collect();
+        assert_eq!(messages[0], "include cycle: 'loop a' → 'loop b' → 'loop a'");
+        assert_eq!(messages[1], "no saved document named 'missing'");
+        assert!(messages[2].starts_with("could not parse 'broken'"));
+        assert!(errors.iter().all(|error| error.severity == Severity::Error));
+        assert_eq!(
+            &input[errors[1].span.start..errors[1].span.end],
+            "include missing"
+        );
+    }
+}
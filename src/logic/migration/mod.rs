@@ -0,0 +1,111 @@
+use super::{parsing, Diagnostic, Program, Severity};
+
+/// The version of the recipe language that this app understands and writes.
+///
+/// Documents without a `version:` header are from before versions existed, which is
+/// version 1.
+pub const CURRENT_VERSION: u32 = 2;
+
+/// A document that was brought up to date with [migrate].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Migration {
+    pub text: String,
+    /// What was changed, to tell the user.
+    pub changes: Vec<String>,
+}
+
+/// Rewrite a document for an older version of the language into [CURRENT_VERSION], or
+/// [None] if it doesn't need any changes.
+///
+/// Documents for newer versions are left alone, since there is no way to know what they
+/// mean. [Program::evaluate] warns about them instead.
+pub fn migrate(source: &str) -> Option<Migration> {
+    // comments can come before the header, and are kept
+    let header_start = parsing::blank(source).map_or(source, |(rest, _)| rest);
+    let before = source[..source.len() - header_start.len()].trim_start();
+    let (before, body, version) = match parsing::version(header_start) {
+        Ok((body, version)) => (before, body, version),
+        Err(_) => ("", source, 1),
+    };
+    if version >= CURRENT_VERSION {
+        return None;
+    }
+
+    // version 2 only added the header, so older documents don't need other changes yet.
+    // Rewrites for syntax that changes in later versions go here, oldest first.
+    let text = format!("{before}version: {CURRENT_VERSION}\n{body}");
+    let changes = vec![format!(
+        "Marked the document as version {CURRENT_VERSION} of the recipe language"
+    )];
+    Some(Migration { text, changes })
+}
+
+/// A warning for documents written for a newer version of the language than this one.
+pub fn version_warning(program: &Program) -> Option<Diagnostic> {
+    let version = program.version.as_ref()?;
+    let message = if version.value == 0 {
+        "unknown version 0, versions start at 1".to_string()
+    } else if version.value > CURRENT_VERSION {
+        format!(
+            "this document is for version {} of the recipe language, but only versions up \
+            to {CURRENT_VERSION} are known, so it may not be read as intended",
+            version.value
+        )
+    } else {
+        return None;
+    };
+
+    Some(Diagnostic {
+        span: version.span,
+        severity: Severity::Warning,
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logic::{Program, Severity};
+
+    use super::{migrate, CURRENT_VERSION};
+
+    #[test]
+    fn test_migrate() {
+        let input = "need:\n- 1 a\nhave:\nrecipes:\n";
+        let migration = migrate(input).unwrap();
+        assert_eq!(
+            migration.text,
+            format!("version: {CURRENT_VERSION}\n{input}")
+        );
+        assert_eq!(migration.changes.len(), 1);
+
+        // the migrated document is current, and means the same
+        assert_eq!(migrate(&migration.text), None);
+        let program = Program::parse_from_string(&migration.text).unwrap();
+        assert_eq!(program.need_section.0.len(), 1);
+
+        let migration = migrate("\nversion: 1\nneed:\nhave:\nrecipes:\n").unwrap();
+        assert_eq!(
+            migration.text,
+            format!("version: {CURRENT_VERSION}\nneed:\nhave:\nrecipes:\n")
+        );
+        assert_eq!(migrate("version: 3\nneed:\nhave:\nrecipes:\n"), None);
+        assert_eq!(
+            migrate("# my base\nversion: 1\nneed:\n").unwrap().text,
+            format!("# my base\nversion: {CURRENT_VERSION}\nneed:\n")
+        );
+        assert_eq!(migrate("# my base\nversion: 2\nneed:\n"), None);
+    }
+
+    #[test]
+    fn test_version_warning() {
+        let input = "version: 3\nneed:\nhave:\nrecipes:\n";
+        let evaluation = Program::parse_from_string(input).unwrap().evaluate();
+        assert_eq!(evaluation.diagnostics.len(), 1);
+        assert_eq!(evaluation.diagnostics[0].severity, Severity::Warning);
+        assert!(evaluation.diagnostics[0].message.contains("version 3"));
+
+        let input = format!("version: {CURRENT_VERSION}\nneed:\nhave:\nrecipes:\n");
+        let evaluation = Program::parse_from_string(&input).unwrap().evaluate();
+        assert_eq!(evaluation.diagnostics, vec![]);
+    }
+}
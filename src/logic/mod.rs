@@ -1,107 +1,728 @@
+pub mod analysis;
+pub mod batch;
+pub mod editing;
 mod evaluation;
+mod includes;
+pub mod migration;
+pub mod options;
 mod parsing;
+pub mod progress;
+pub mod report;
 
-use std::fmt::Display;
+use std::{collections::HashSet, fmt::Display, ops::Deref, rc::Rc};
 
 use log::{debug, error, info};
 
+use self::{
+    analysis::Analysis,
+    options::{NeedMode, Options},
+    report::Report,
+};
+
+pub use self::evaluation::{evaluate, evaluate_with_options, EvaluationError};
+
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 #[derive(Debug)]
 pub struct Program {
+    /// The version of the recipe language from the `version:` header, if there is one.
+    pub version: Option<Spanned<u32>>,
+    /// The names of the documents this one includes recipes from.
+    pub includes: Vec<Spanned<String>>,
+    /// The recipes from included documents, filled in by [Program::resolve_includes].
+    pub included_recipes: Vec<Recipe>,
+    pub option_section: OptionSection,
+    pub source_section: SourceSection,
+    pub category_section: CategorySection,
     pub need_section: NeedSection,
     pub have_section: HaveSection,
+    /// The `have <player>:` sections after the have section, in order.
+    pub inventories: Vec<Inventory>,
     pub recipe_section: RecipeSection,
 }
 
 impl Program {
-    pub fn parse_from_string(input: &str) -> Result<Self, String> {
+    /// How many lines or sections [Program::parse_leniently] leaves out before it gives up.
+    const MAX_LEFT_OUT: usize = 20;
+
+    pub fn parse_from_string(input: &str) -> Result<Self, Diagnostic> {
         debug!("Parsing input with length {}", input.len());
+        let parsed = Self::parse(input);
+        match &parsed {
+            Ok(_) => info!("Parsed input"),
+            // the message can hold the rest of the document
+            Err(e) => error!("Error while parsing input at offset {}", e.span.start),
+        }
+        parsed
+    }
+
+    /// [Program::parse_from_string] without logging, since [Program::parse_leniently] tries
+    /// again after most errors.
+    fn parse(input: &str) -> Result<Self, Diagnostic> {
         match parsing::program(input) {
-            Ok(("", output)) => {
-                info!("Parsed input");
+            Ok(("", mut output)) => {
+                output.share_item_names();
+                options::normalize_item_names(&mut output);
                 Ok(output)
             }
-            Ok((remaining, _)) => {
-                error!("Parsed input but {} chars were remaining", remaining.len());
-                Err(format!("Remaining: {remaining}"))
-            }
+            Ok((remaining, _)) => Err(Diagnostic::parse_error(
+                input,
+                remaining,
+                format!("Remaining: {remaining}"),
+            )),
             Err(e) => {
-                error!("Error while parsing input: {}", e);
-                Err(format!("{}", e))
+                let remaining = match &e {
+                    nom::Err::Error(e) | nom::Err::Failure(e) => e.input,
+                    nom::Err::Incomplete(_) => "",
+                };
+                Err(Diagnostic::parse_error(input, remaining, format!("{}", e)))
             }
         }
     }
 
-    pub fn evaluate(&self) -> String {
-        let context = evaluation::evaluate(self);
+    /// Parse `input` like [Program::parse_from_string], but leave out the lines that can't be
+    /// read instead of failing, with an error for each of them.
+    ///
+    /// A line that looks like a section header but can't be read is left out together with
+    /// the entries below it, up to the next header, since they can't be read without it.
+    ///
+    /// This still fails if the document can't be read without those lines either, such as
+    /// when a section header is missing, or when more than [Program::MAX_LEFT_OUT] lines or
+    /// sections would have to be left out. In that case the first error is returned.
+    pub fn parse_leniently(input: &str) -> Result<(Self, Vec<Diagnostic>), Diagnostic> {
+        debug!("Parsing input with length {} leniently", input.len());
+        let parsed = Self::parse_leaving_out(input);
+        match &parsed {
+            Ok((_, errors)) if errors.is_empty() => info!("Parsed input"),
+            Ok((_, errors)) => info!("Parsed input, leaving out {} parts", errors.len()),
+            // the message can hold the rest of the document
+            Err(e) => error!("Error while parsing input at offset {}", e.span.start),
+        }
+        parsed
+    }
 
-        let context = match context {
-            Ok(c) => c,
-            Err(e) => return format!("Error during evaluation: {e:?}"),
-        };
+    /// [Program::parse_leniently] without logging the outcome.
+    fn parse_leaving_out(input: &str) -> Result<(Self, Vec<Diagnostic>), Diagnostic> {
+        let mut text = input.to_string();
+        let mut errors = vec![];
+        let mut first_error = None;
+        loop {
+            let error = match Self::parse(&text) {
+                Ok(program) => return Ok((program, errors)),
+                Err(error) => error,
+            };
+            debug!(
+                "Leaving out what can't be parsed at offset {}",
+                error.span.start
+            );
+            let first_error = first_error.get_or_insert(error.clone());
+            // every line that is left out takes another parse, so this has to end somewhere
+            if errors.len() == Self::MAX_LEFT_OUT {
+                return Err(first_error.clone());
+            }
 
-        let mut result = String::new();
+            let line_start = text[..error.span.start].rfind('\n').map_or(0, |i| i + 1);
+            let line = &text[line_start..error.span.end];
+            let indent = line.len() - line.trim_start().len();
+            // a missing line or one that is a comment already can't be left out
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                return Err(first_error.clone());
+            }
+
+            let is_header = line
+                .split('#')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .ends_with(':');
+            let (end, message) = if is_header {
+                // entries, comments and empty lines up to the next header belong to this one
+                let mut end = error.span.end;
+                let mut start = text[end..].find('\n').map_or(text.len(), |i| end + i + 1);
+                for next in text[start..].split_inclusive('\n') {
+                    let trimmed = next.trim();
+                    if !(trimmed.is_empty() || trimmed.starts_with(['-', '#'])) {
+                        break;
+                    }
+                    if !trimmed.is_empty() {
+                        end = start + next.trim_end().len();
+                    }
+                    start += next.len();
+                }
+                (end, "This section could not be read, so it is left out")
+            } else {
+                (
+                    error.span.end,
+                    "This line could not be read, so it is left out",
+                )
+            };
+
+            // turn the lines into comments of the same length, so spans stay the same
+            let lines = text[line_start..end].split_inclusive('\n');
+            let comment: String = lines.map(comment_out).collect();
+            text.replace_range(line_start..end, &comment);
+            errors.push(Diagnostic {
+                span: Span {
+                    start: line_start + indent,
+                    end,
+                },
+                severity: Severity::Error,
+                message: message.to_string(),
+            });
+        }
+    }
+
+    /// Every item name in the program, including those of included recipes, with where the
+    /// entry it is in starts. Included recipes come from elsewhere, so they are at
+    /// [usize::MAX].
+    fn items_mut(&mut self) -> impl Iterator<Item = (usize, &mut Item)> {
+        let needs = self.need_section.0.iter_mut().flat_map(|need| {
+            let start = need.span.start;
+            let need = &mut need.value;
+            let stacks = std::iter::once(&mut need.stack).chain(&mut need.alternatives);
+            stacks.map(move |stack| (start, stack))
+        });
+        let inventories = self.inventories.iter_mut().flat_map(|i| &mut i.items);
+        let haves = self.have_section.0.iter_mut().chain(inventories);
+        let haves = haves.map(|have| (have.span.start, &mut have.value));
+        let recipes = self
+            .recipe_section
+            .0
+            .iter_mut()
+            .map(|recipe| (recipe.span.start, &mut recipe.value));
+        let included = self.included_recipes.iter_mut().map(|r| (usize::MAX, r));
+        let recipes = recipes.chain(included).flat_map(|(start, recipe)| {
+            let outputs = std::iter::once(&mut recipe.output).chain(&mut recipe.byproducts);
+            outputs
+                .chain(&mut recipe.inputs)
+                .map(move |stack| (start, stack))
+        });
 
-        // TODO: show which items will actually be used?
+        let sources = self
+            .source_section
+            .0
+            .iter_mut()
+            .map(|source| (source.span.start, &mut source.value.item));
+        let categories = self.category_section.0.iter_mut().flat_map(|category| {
+            let start = category.span.start;
+            category
+                .value
+                .items
+                .iter_mut()
+                .map(move |item| (start, item))
+        });
 
-        let missing_items = context.get_missing_items();
-        if missing_items.is_empty() {
-            result.push_str("You have all the required items!\n");
-        } else {
-            result.push_str("Missing items:\n");
-            for stack in missing_items {
-                result.push_str(&format!("- {} {}\n", stack.count, stack.item.0));
+        needs
+            .chain(haves)
+            .chain(recipes)
+            .map(|(start, stack)| (start, &mut stack.item))
+            .chain(sources)
+            .chain(categories)
+    }
+
+    /// Make every occurrence of an item name point to the same text, so a document that
+    /// mentions an item many times only keeps its name once.
+    fn share_item_names(&mut self) {
+        let mut names = HashSet::<Rc<str>>::new();
+        for (_, item) in self.items_mut() {
+            match names.get(&item.0) {
+                Some(name) => item.0 = Rc::clone(name),
+                None => {
+                    names.insert(Rc::clone(&item.0));
+                }
             }
         }
-        result.push('\n');
+    }
+
+    /// All recipes of this document and the included ones, with the included ones first.
+    pub fn recipes(&self) -> impl Iterator<Item = &Recipe> {
+        let own = self.recipe_section.0.iter().map(|recipe| &recipe.value);
+        self.included_recipes.iter().chain(own)
+    }
+
+    /// The recipes to evaluate with. Recipes from this document replace included ones for
+    /// the same item, while several recipes for an item in one document are alternatives.
+    pub fn usable_recipes(&self) -> impl Iterator<Item = &Recipe> {
+        let own = self.recipe_section.0.iter().map(|recipe| &recipe.value);
+        let own_items: HashSet<&Item> = own.clone().map(|recipe| &recipe.output.item).collect();
+        let included = self.included_recipes.iter();
+        included
+            .filter(move |recipe| !own_items.contains(&recipe.output.item))
+            .chain(own)
+    }
+
+    /// The recipe that is used for `item` unless another one leaves fewer items missing:
+    /// the first one that starts with `prefer`, or else the first one.
+    pub fn default_recipe(&self, item: &Item) -> Option<&Recipe> {
+        let mut recipes = self
+            .usable_recipes()
+            .filter(|recipe| recipe.output.item == *item);
+        let first = recipes.next()?;
+        if first.preferred {
+            return Some(first);
+        }
+        Some(recipes.find(|recipe| recipe.preferred).unwrap_or(first))
+    }
+
+    /// Leave out the recipes that make any of `outputs`, both from this document and
+    /// included ones, so those items have to be gathered instead.
+    pub fn disable_recipes(&mut self, outputs: &HashSet<Item>) {
+        let enabled = |recipe: &Recipe| !outputs.contains(&recipe.output.item);
+        self.recipe_section
+            .0
+            .retain(|recipe| enabled(&recipe.value));
+        self.included_recipes.retain(enabled);
+    }
+
+    /// Empty the have section, so evaluating shows everything the needs take to make from
+    /// raw items.
+    pub fn ignore_inventory(&mut self) {
+        self.have_section.0.clear();
+        self.inventories.clear();
+    }
+
+    /// Evaluate the program as if the have section were empty, which shows everything the
+    /// needs take to make from raw items. The needs that the normal `evaluation` dropped are
+    /// left out of the program, and the others are all made, since [NeedMode::BestEffort]
+    /// would drop most of them without the haves.
+    pub fn evaluate_from_scratch(&mut self, evaluation: &Evaluation) -> Evaluation {
+        self.ignore_inventory();
+        let needs = std::mem::take(&mut self.need_section.0)
+            .into_iter()
+            .enumerate();
+        self.need_section.0 = needs
+            .filter(|(index, _)| !evaluation.dropped_needs.contains(index))
+            .map(|(_, need)| need)
+            .collect();
+
+        let options = Options {
+            need_mode: NeedMode::All,
+            ..evaluation.options.clone()
+        };
+        self.evaluate_with_options(&options)
+    }
+
+    /// The entries of the have section followed by those of the players' inventories.
+    pub fn haves(&self) -> impl Iterator<Item = &Spanned<ItemStack>> {
+        let inventories = self.inventories.iter().flat_map(|i| &i.items);
+        self.have_section.0.iter().chain(inventories)
+    }
+
+    pub fn evaluate(&self) -> Evaluation {
+        self.evaluate_inner(None, false)
+    }
+
+    /// Evaluate the program with `options` instead of the settings of its options section.
+    pub fn evaluate_with_options(&self, options: &Options) -> Evaluation {
+        self.evaluate_inner(Some(options), false)
+    }
+
+    /// Evaluate the program and also check that no items were lost or made up along the way.
+    ///
+    /// This is only meant for finding bugs in the evaluator.
+    pub fn evaluate_audited(&self) -> Evaluation {
+        self.evaluate_inner(None, true)
+    }
+
+    fn evaluate_inner(&self, options: Option<&Options>, audit: bool) -> Evaluation {
+        let (read, mut warnings) = options::read(self);
+        let options = options.cloned().unwrap_or(read);
+        warnings.extend(migration::version_warning(self));
+        let analysis = analysis::analyze(self);
+        warnings.extend(analysis::loop_warnings(self, &analysis));
+        warnings.extend(evaluation::unused_haves(self));
+        // entries that were added after parsing have nowhere to point to
+        warnings.retain(|warning| !warning.span.is_made_up());
+        let evaluated = evaluation::evaluate_in_context(self, &options);
 
-        let leftover_items = context.get_available_items();
-        if leftover_items.is_empty() {
-            result.push_str("No items are left over after crafting.\n");
-        } else {
-            result.push_str("Leftover items after crafting:\n");
-            for stack in leftover_items {
-                result.push_str(&format!("- {} {}\n", stack.count, stack.item.0));
+        let (context, report) = match evaluated {
+            Ok(evaluated) => evaluated,
+            Err(e) => {
+                return Evaluation {
+                    // these can explain the error, such as a loop making evaluation fail
+                    diagnostics: warnings,
+                    analysis,
+                    options,
+                    ..Evaluation::error(format!("Error during evaluation: {e:?}"))
+                };
             }
+        };
+
+        let mut diagnostics = evaluation::unproducible_needs(self, &context);
+        diagnostics.extend(evaluation::dropped_needs(self, &context));
+        diagnostics.extend(evaluation::overproduced_exact_needs(self, &context));
+        diagnostics.extend(warnings);
+        diagnostics.extend(self.duplicate_hints());
+        diagnostics.retain(|diagnostic| !diagnostic.span.is_made_up());
+        let mut annotations = evaluation::annotations(self, &context);
+        annotations.retain(|annotation| !annotation.span.is_made_up());
+
+        Evaluation {
+            report: Ok(report),
+            diagnostics,
+            annotations,
+            statistics: context.get_statistics(),
+            analysis,
+            imbalances: audit.then(|| evaluation::audit(self, &context)),
+            options,
+            dropped_needs: context.get_dropped_need_indices().to_vec(),
+            coverage: self
+                .need_section
+                .0
+                .iter()
+                .map(|need| need.span)
+                .zip(context.get_need_coverage().iter().copied())
+                .collect(),
         }
-        result.push('\n');
+    }
+
+    /// Hints for items that are listed multiple times in a section and could be merged.
+    fn duplicate_hints(&self) -> Vec<Diagnostic> {
+        editing::find_duplicates(self)
+            .iter()
+            .flat_map(|duplicates| {
+                let message = format!(
+                    "'{}' is listed {} times in this section",
+                    duplicates.item.0,
+                    duplicates.entries.len()
+                );
+
+                duplicates.entries.iter().map(move |entry| Diagnostic {
+                    span: entry.span,
+                    severity: Severity::Hint,
+                    message: message.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// `line` turned into a comment of the same length, or unchanged if there is nothing on it.
+fn comment_out(line: &str) -> String {
+    let content = line.trim_end_matches(['\r', '\n']);
+    if content.trim().is_empty() {
+        return line.to_string();
+    }
+    format!(
+        "#{}{}",
+        " ".repeat(content.len() - 1),
+        &line[content.len()..]
+    )
+}
+
+/// The result of evaluating a [Program].
+#[derive(Debug, PartialEq, Eq)]
+pub struct Evaluation {
+    /// The report, or a message explaining why the program could not be evaluated.
+    pub report: Result<Report, String>,
+    /// Warnings that point back into the source document.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Computed information to show next to parts of the source document.
+    pub annotations: Vec<Annotation>,
+    /// How well each need entry is covered by the have section.
+    pub coverage: Vec<(Span, Coverage)>,
+    /// How much work the evaluation took.
+    pub statistics: Statistics,
+    /// Facts about the recipe graph of the document.
+    pub analysis: Analysis,
+    /// Items that did not add up, if the evaluation was audited.
+    pub imbalances: Option<Vec<Imbalance>>,
+    /// The settings from the options section.
+    pub options: Options,
+    /// The indices of the need entries that were dropped with [NeedMode::BestEffort].
+    pub dropped_needs: Vec<usize>,
+}
 
-        result.push_str("Executed recipes:\n");
-        for recipe in context.get_executed_recipes() {
-            result.push_str(&format!("- {recipe}\n"));
+impl Evaluation {
+    /// An evaluation that only consists of an error message.
+    pub fn error(message: String) -> Self {
+        Self {
+            report: Err(message),
+            diagnostics: vec![],
+            annotations: vec![],
+            coverage: vec![],
+            statistics: Statistics::default(),
+            analysis: Analysis::default(),
+            imbalances: None,
+            options: Options::default(),
+            dropped_needs: vec![],
         }
+    }
+}
+
+/// Counters that show how much work an evaluation took.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Statistics {
+    /// How many recipes could be used, including included ones.
+    pub recipes: usize,
+    /// How often recipes were executed in total.
+    pub recipe_runs: u64,
+    /// How many different items were asked for.
+    pub items_touched: usize,
+    /// How deep the chain of recipes went.
+    pub max_depth: usize,
+}
+
+/// An item that appeared or disappeared during evaluation, found by [Program::evaluate_audited].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Imbalance {
+    pub item: Item,
+    /// The amount that was available, crafted or missing.
+    pub produced: u128,
+    /// The amount that was needed, used as an ingredient or left over.
+    pub consumed: u128,
+}
+
+/// A recipe for a missing item from another saved document, found by
+/// [Program::suggest_recipes].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RecipeSuggestion {
+    /// The name of the document the recipe is in.
+    pub document: String,
+    pub recipe: Recipe,
+}
+
+/// The version of an included document that a document was shared with, found by
+/// [Program::include_pins], so opening the link can tell when it changed since.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct IncludePin {
+    pub name: String,
+    /// A hash of the content of the document.
+    pub fingerprint: u64,
+}
+
+/// How much of a need entry can be made from the items in the have section.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Coverage {
+    /// Everything can be made, nothing is missing.
+    Full,
+    /// Some items are available, but others are missing.
+    Partial,
+    /// None of the available items could be used.
+    None,
+}
+
+/// A label shown after a part of the source document.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Annotation {
+    pub span: Span,
+    pub label: String,
+}
+
+/// A message attached to a part of the source document.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Diagnostic {
+    pub span: Span,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// An error at the start of the line where parsing stopped, with `remaining` being the
+    /// unparsed end of `input`.
+    fn parse_error(input: &str, remaining: &str, message: String) -> Self {
+        let start = input.len() - remaining.len();
+        let line_length = remaining.find(['\r', '\n']).unwrap_or(remaining.len());
+
+        Self {
+            span: Span {
+                start,
+                end: start + line_length,
+            },
+            severity: Severity::Error,
+            message,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+    Hint,
+}
+
+/// A byte range in the source document.
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// Whether this is the span of something that did not come from the document, such as
+    /// the haves that [progress::Progress::apply] adds, which is empty at its start.
+    pub fn is_made_up(&self) -> bool {
+        *self == Span::default()
+    }
+}
+
+/// A value together with the location it was parsed from.
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: Span,
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
 
-        result
+/// Wraps a value that did not come from a document, such as in tests.
+impl<T> From<T> for Spanned<T> {
+    fn from(value: T) -> Self {
+        Self {
+            value,
+            span: Span::default(),
+        }
     }
 }
 
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[derive(Debug)]
+pub struct OptionSection(Vec<Spanned<Setting>>);
+
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[derive(Debug)]
+pub struct SourceSection(Vec<Spanned<Source>>);
+
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[derive(Debug)]
+pub struct CategorySection(Vec<Spanned<Category>>);
+
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[derive(Debug)]
+pub struct NeedSection(Vec<Spanned<Need>>);
+
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 #[derive(Debug)]
-pub struct NeedSection(Vec<ItemStack>);
+pub struct HaveSection(Vec<Spanned<ItemStack>>);
 
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 #[derive(Debug)]
-pub struct HaveSection(Vec<ItemStack>);
+pub struct RecipeSection(Vec<Spanned<Recipe>>);
 
+/// The items of one player, from a `have <player>:` section such as `have alice:`. They are
+/// evaluated as if they were in the have section, but the report tells who gave what.
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 #[derive(Debug)]
-pub struct RecipeSection(Vec<Recipe>);
+pub struct Inventory {
+    pub player: Spanned<String>,
+    pub items: Vec<Spanned<ItemStack>>,
+}
+
+/// An entry in the options section, such as `need mode = best effort`.
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Setting {
+    pub key: String,
+    pub value: String,
+}
+
+/// An entry in the sources section, such as `iron ore @ mines`.
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Source {
+    pub item: Item,
+    /// Where the item is gathered, such as an area of the game.
+    pub source: String,
+}
+
+/// An entry in the categories section, such as `ores: iron ore, copper ore`.
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Category {
+    pub name: String,
+    pub items: Vec<Item>,
+}
+
+/// An entry in the need section, such as `1 diamond sword | 1 netherite sword`. Only one
+/// of the stacks has to be made.
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Need {
+    /// The first stack, which is used wherever only a single stack can be shown.
+    pub stack: ItemStack,
+    /// The stacks that can be made instead.
+    pub alternatives: Vec<ItemStack>,
+    /// Whether a warning is shown when more than the needed amount is made.
+    pub exact: bool,
+}
+
+impl Need {
+    /// All stacks that can fulfill this need, starting with [Need::stack].
+    pub fn stacks(&self) -> impl Iterator<Item = &ItemStack> {
+        std::iter::once(&self.stack).chain(&self.alternatives)
+    }
+}
+
+impl Deref for Need {
+    type Target = ItemStack;
+
+    fn deref(&self) -> &Self::Target {
+        &self.stack
+    }
+}
+
+/// A need without alternatives that did not come from a document, such as in tests.
+impl From<ItemStack> for Spanned<Need> {
+    fn from(stack: ItemStack) -> Self {
+        Need {
+            stack,
+            alternatives: vec![],
+            exact: false,
+        }
+        .into()
+    }
+}
+
+impl Display for Need {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.exact {
+            write!(f, "exact ")?;
+        }
+        let stacks: Vec<String> = self.stacks().map(ItemStack::to_string).collect();
+        write!(f, "{}", stacks.join(" | "))
+    }
+}
 
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub struct Recipe {
+    /// The `## ` comment lines above the recipe, such as where it can be crafted.
+    pub doc: Option<Rc<str>>,
+    /// Whether the recipe starts with `prefer`, so it is used over other recipes for the
+    /// same item.
+    pub preferred: bool,
+    /// The item the recipe is for, which is what it is looked up by.
     pub output: ItemStack,
+    /// Other items the recipe makes along the way, such as `2 petroleum gas` in
+    /// `1 refined oil + 2 petroleum gas = 4 crude oil`.
+    pub byproducts: Vec<ItemStack>,
     pub inputs: Vec<ItemStack>,
 }
 
 impl Recipe {
-    pub fn multiplied_by(&self, count: u64) -> Self {
-        let mut cloned = self.clone();
-        cloned.multiply(count);
-        cloned
+    /// The output followed by the byproducts.
+    pub fn outputs(&self) -> impl Iterator<Item = &ItemStack> {
+        std::iter::once(&self.output).chain(&self.byproducts)
     }
 
-    pub fn multiply(&mut self, count: u64) {
-        self.output.count *= count;
+    /// Multiply all counts in the recipe, or [None] if one of them would overflow.
+    pub fn checked_multiplied_by(&self, count: u64) -> Option<Self> {
+        let mut cloned = self.clone();
 
-        for input in &mut self.inputs {
-            input.count *= count;
+        cloned.output.count = cloned.output.count.checked_mul(count)?;
+        for stack in cloned.byproducts.iter_mut().chain(&mut cloned.inputs) {
+            stack.count = stack.count.checked_mul(count)?;
         }
+
+        Some(cloned)
     }
 }
 
@@ -118,23 +739,211 @@ impl Display for Recipe {
             is_first = false;
         }
 
-        write!(f, "-> {} {}", self.output.count, &self.output.item.0)?;
+        write!(f, "->")?;
+        for (i, output) in self.outputs().enumerate() {
+            if i > 0 {
+                write!(f, " +")?;
+            }
+            write!(f, " {} {}", output.count, &output.item.0)?;
+        }
 
         Ok(())
     }
 }
 
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct ItemStack {
-    count: u64,
-    item: Item,
+    pub count: u64,
+    pub item: Item,
 }
 
+impl Display for ItemStack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.count, self.item)
+    }
+}
+
+/// The name of an item, which is cheap to copy since evaluation and reports repeat it a lot.
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
-pub struct Item(String);
+pub struct Item(Rc<str>);
 
 impl Item {
-    fn new(text: impl Into<String>) -> Self {
+    fn new(text: impl Into<Rc<str>>) -> Self {
         Self(text.into())
     }
 }
+
+impl Display for Item {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_error_span() {
+        let input = "need:\n- 1 a\nhave:\nrecipes:\n- oops\n- 1 b = 1 a\n";
+        let error = Program::parse_from_string(input).unwrap_err();
+
+        assert_eq!(error.severity, Severity::Error);
+        assert_eq!(&input[error.span.start..error.span.end], "- oops");
+    }
+
+    #[test]
+    fn test_parse_leniently() {
+        let input = "need:\n- 1 a\n- lots of b\nhave:\nrecipes:\n- oops\n- 1 a = 1 b\n";
+        let (program, errors) = Program::parse_leniently(input).unwrap();
+
+        let lines: Vec<_> = errors
+            .iter()
+            .map(|error| &input[error.span.start..error.span.end])
+            .collect();
+        assert_eq!(lines, ["- lots of b", "- oops"]);
+        let [recipe] = &program.recipe_section.0[..] else {
+            panic!("expected one recipe");
+        };
+        assert_eq!(&input[recipe.span.start..recipe.span.end], "1 a = 1 b");
+
+        let input = "need:\n- 1 a\nrecipes:\n";
+        let error = Program::parse_leniently(input).unwrap_err();
+        assert_eq!(error, Program::parse_from_string(input).unwrap_err());
+
+        // a broken header is left out with its entries in one go
+        let input = "need:\n- 1 a\nhave:\nrecipes:\nsource:\n- a @ mines\n\n# b too\n- b @ mines\n";
+        let (program, errors) = Program::parse_leniently(input).unwrap();
+        let [error] = &errors[..] else {
+            panic!("expected one error");
+        };
+        assert_eq!(
+            &input[error.span.start..error.span.end],
+            "source:\n- a @ mines\n\n# b too\n- b @ mines"
+        );
+        assert!(program.source_section.0.is_empty());
+
+        // every line that is left out takes a parse, so there is a limit
+        let lines = "- oops\n".repeat(Program::MAX_LEFT_OUT + 1);
+        let input = format!("need:\n{lines}have:\nrecipes:\n");
+        assert!(Program::parse_leniently(&input).is_err());
+    }
+
+    #[test]
+    fn test_disable_recipes() {
+        let input = "need:\n- 1 pickaxe\nhave:\nrecipes:\n- 4 plank = 1 log\n- 4 stick = 2 plank\n- 1 pickaxe = 2 stick + 3 plank\n";
+        let mut program = Program::parse_from_string(input).unwrap();
+
+        program.disable_recipes(&HashSet::from([Item::new("stick")]));
+        assert_eq!(program.recipes().count(), 2);
+
+        let report = program.evaluate().report.unwrap();
+        let missing: Vec<_> = report
+            .missing_items
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(missing, ["2 stick", "1 log"]);
+    }
+
+    #[test]
+    fn test_share_item_names() {
+        let input = "need:\n- 1 pickaxe\nhave:\n- 1 log\nrecipes:\n- 4 plank = 1 log\n\
+            - 1 pickaxe = 3 plank\n";
+        let program = Program::parse_from_string(input).unwrap();
+        let have = &program.have_section.0[0].item;
+        let recipes = &program.recipe_section.0;
+        assert!(Rc::ptr_eq(&have.0, &recipes[0].inputs[0].item.0));
+        assert!(Rc::ptr_eq(
+            &recipes[0].output.item.0,
+            &recipes[1].inputs[0].item.0
+        ));
+        assert!(Rc::ptr_eq(
+            &program.need_section.0[0].stack.item.0,
+            &recipes[1].output.item.0
+        ));
+    }
+
+    #[test]
+    fn test_ignore_inventory() {
+        let input = "need:\n- 1 pickaxe\nhave:\n- 4 stick\n- 1 log\nrecipes:\n- 4 plank = 1 log\n- 4 stick = 2 plank\n- 1 pickaxe = 2 stick + 3 plank\n";
+        let mut program = Program::parse_from_string(input).unwrap();
+        let missing = |program: &Program| {
+            let report = program.evaluate().report.unwrap();
+            let missing = report.missing_items.iter().map(ToString::to_string);
+            missing.collect::<Vec<_>>()
+        };
+        assert_eq!(missing(&program), Vec::<String>::new());
+
+        program.ignore_inventory();
+        assert_eq!(missing(&program), ["2 log"]);
+    }
+
+    #[test]
+    fn test_evaluate_from_scratch() {
+        let input = "options:\n- need mode = best effort\n\
+            need:\n- 1 pickaxe\n- 1 sword\nhave:\n- 2 log\nrecipes:\n- 4 plank = 1 log\n\
+            - 4 stick = 2 plank\n- 1 pickaxe = 2 stick + 3 plank\n- 1 sword = 1 stick + 2 diamond\n";
+        let mut program = Program::parse_from_string(input).unwrap();
+        let evaluation = program.evaluate();
+        assert_eq!(evaluation.dropped_needs, [1]);
+
+        // the dropped sword doesn't count, and the logs are needed even though they are there
+        let from_scratch = program.evaluate_from_scratch(&evaluation);
+        let report = from_scratch.report.unwrap();
+        let missing: Vec<_> = report
+            .missing_items
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(missing, ["2 log"]);
+        assert_eq!(report.dropped_needs, []);
+    }
+
+    #[test]
+    #[ntest::timeout(5000)]
+    fn test_large_program() {
+        // 10k recipes in a binary tree, where every item is made from 2 deeper ones
+        let recipes: String = (0..10_000)
+            .map(|i| {
+                format!(
+                    "- 1 item {i} = 1 item {} + 1 item {}\n",
+                    2 * i + 1,
+                    2 * i + 2
+                )
+            })
+            .collect();
+        let input = format!("need:\n- 1 item 0\nhave:\nrecipes:\n{recipes}");
+
+        let program = Program::parse_from_string(&input).unwrap();
+        let evaluation = program.evaluate();
+
+        let report = evaluation.report.unwrap();
+        assert_eq!(report.executed_recipes.len(), 10_000);
+        assert_eq!(report.missing_items.len(), 10_001);
+        assert_eq!(evaluation.statistics.recipes, 10_000);
+        assert_eq!(evaluation.analysis.raw_items.len(), 10_001);
+    }
+
+    #[test]
+    fn test_adversarial_input_does_not_panic() {
+        let inputs = [
+            "",
+            "need:",
+            "need:\n- 99999999999999999999999 a\nhave:\nrecipes:\n",
+            "need:\n- 1 ünïcödé 🦀\nhave:\n- 1 🦀\nrecipes:\n- 1 ünïcödé 🦀 = 1 🦀\n",
+            "need:\n- 1 a\nhave:\nrecipes:\n- 1 a = 1 b\n- 1 b = 1 a\n",
+            "need:\n- 1 \u{300}\nhave:\nrecipes:\n- 🦀\n",
+            "need:\r\n- 1 a\r\nhave:\r\nrecipes:\r\n- 0 a = 0 a\r\n",
+        ];
+
+        for input in inputs {
+            if let Ok(program) = Program::parse_from_string(input) {
+                let evaluation = program.evaluate_audited();
+                assert_eq!(evaluation.imbalances.unwrap_or_default(), vec![], "{input}");
+            }
+        }
+    }
+}
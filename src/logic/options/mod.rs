@@ -0,0 +1,465 @@
+use std::collections::HashMap;
+
+use super::{Diagnostic, Item, ItemStack, Program, Recipe, Setting, Severity};
+
+/// Settings from the options section that change how a program is read and evaluated.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Options {
+    pub need_mode: NeedMode,
+    pub recipe_loops: RecipeLoops,
+    pub surplus_sharing: SurplusSharing,
+    /// Whether item names that only differ in case are the same item.
+    pub ignore_case: bool,
+    /// Whether runs of spaces in item names count as a single space.
+    pub collapse_spaces: bool,
+    /// Whether plural item names are the same item as their singular.
+    pub ignore_plurals: bool,
+    pub number_format: NumberFormat,
+    /// Crafted ingredients below this count are folded away in the crafting tree, or none if
+    /// this is 0.
+    pub tree_threshold: u64,
+}
+
+impl Options {
+    /// The name of an item with these options, as it should be shown.
+    fn spelling(&self, name: &str) -> String {
+        if self.collapse_spaces {
+            name.split_whitespace().collect::<Vec<_>>().join(" ")
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// What is compared to find out whether two item names are the same item.
+    fn key(&self, spelling: &str) -> String {
+        let key = if self.ignore_case {
+            spelling.to_lowercase()
+        } else {
+            spelling.to_string()
+        };
+
+        if self.ignore_plurals {
+            singular(&key)
+        } else {
+            key
+        }
+    }
+}
+
+/// How the entries in the need section are fulfilled.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum NeedMode {
+    /// Every need is crafted, listing whatever is missing for it.
+    #[default]
+    All,
+    /// Needs are fulfilled in order as long as nothing goes missing, and dropped otherwise.
+    BestEffort,
+}
+
+/// What happens when an item is needed to craft itself, such as in a refiner loop.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum RecipeLoops {
+    /// Keep crafting until the depth limit is hit, which fails the evaluation.
+    #[default]
+    Error,
+    /// Count the item as missing instead of crafting it again, as the seed that starts the
+    /// loop.
+    Seed,
+}
+
+/// Whether items that were made too many of for one need can be used by later needs.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum SurplusSharing {
+    /// Later needs use the surplus before crafting more.
+    #[default]
+    On,
+    /// Surplus is set aside, so every need is crafted as if it were the only one made from
+    /// scratch. The have section is still shared.
+    Off,
+}
+
+/// How counts are shown in the report. Exports and the raw text always use plain numbers.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum NumberFormat {
+    /// `1234567`
+    #[default]
+    Plain,
+    /// `1,234,567`
+    Commas,
+    /// `1.234.567`
+    Dots,
+    /// `1 234 567`
+    Spaces,
+    /// `1.2M`, rounded to one decimal.
+    Compact,
+}
+
+impl NumberFormat {
+    pub fn count(self, count: u64) -> String {
+        let separator = match self {
+            NumberFormat::Plain => return count.to_string(),
+            NumberFormat::Commas => ',',
+            NumberFormat::Dots => '.',
+            NumberFormat::Spaces => ' ',
+            NumberFormat::Compact => return compact(count),
+        };
+
+        let digits = count.to_string();
+        let mut output = String::with_capacity(digits.len() * 4 / 3);
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && i % 3 == digits.len() % 3 {
+                output.push(separator);
+            }
+            output.push(digit);
+        }
+        output
+    }
+
+    pub fn stack(self, stack: &ItemStack) -> String {
+        format!("{} {}", self.count(stack.count), stack.item)
+    }
+
+    pub fn recipe(self, recipe: &Recipe) -> String {
+        let inputs: Vec<_> = recipe
+            .inputs
+            .iter()
+            .map(|input| self.stack(input))
+            .collect();
+        let outputs: Vec<_> = recipe.outputs().map(|output| self.stack(output)).collect();
+        format!("{} -> {}", inputs.join(" + "), outputs.join(" + "))
+    }
+}
+
+/// Shorten `count` to at most one decimal and a suffix, such as `1.2k` for 1234.
+fn compact(count: u64) -> String {
+    const UNITS: [(u64, &str); 4] = [
+        (1_000, "k"),
+        (1_000_000, "M"),
+        (1_000_000_000, "B"),
+        (1_000_000_000_000, "T"),
+    ];
+
+    let mut shortened = None;
+    for (unit, suffix) in UNITS {
+        if shortened.is_none() && count < unit {
+            break;
+        }
+
+        let tenths = (u128::from(count) * 10 + u128::from(unit) / 2) / u128::from(unit);
+        shortened = Some((tenths, suffix));
+        // rounding can reach the next unit, such as 999,950 turning into 1000.0k
+        if tenths < 10_000 {
+            break;
+        }
+    }
+
+    match shortened {
+        Some((tenths, suffix)) if tenths % 10 == 0 => format!("{}{suffix}", tenths / 10),
+        Some((tenths, suffix)) => format!("{}.{}{suffix}", tenths / 10, tenths % 10),
+        None => count.to_string(),
+    }
+}
+
+/// Read the options section of `program`, with warnings for settings that aren't understood.
+///
+/// Keys and values are not case sensitive. Unknown settings are ignored.
+pub fn read(program: &Program) -> (Options, Vec<Diagnostic>) {
+    let mut options = Options::default();
+    let mut warnings = vec![];
+
+    for setting in &program.option_section.0 {
+        let Setting { key, value } = &setting.value;
+        let message = match key.to_lowercase().as_str() {
+            "need mode" => match value.to_lowercase().as_str() {
+                "all" => {
+                    options.need_mode = NeedMode::All;
+                    continue;
+                }
+                "best effort" => {
+                    options.need_mode = NeedMode::BestEffort;
+                    continue;
+                }
+                _ => format!("unknown need mode '{value}', expected 'all' or 'best effort'"),
+            },
+            "recipe loops" => match value.to_lowercase().as_str() {
+                "error" => {
+                    options.recipe_loops = RecipeLoops::Error;
+                    continue;
+                }
+                "seed" => {
+                    options.recipe_loops = RecipeLoops::Seed;
+                    continue;
+                }
+                _ => format!("unknown recipe loop mode '{value}', expected 'error' or 'seed'"),
+            },
+            "surplus sharing" => match value.to_lowercase().as_str() {
+                "on" => {
+                    options.surplus_sharing = SurplusSharing::On;
+                    continue;
+                }
+                "off" => {
+                    options.surplus_sharing = SurplusSharing::Off;
+                    continue;
+                }
+                _ => format!("unknown surplus sharing '{value}', expected 'on' or 'off'"),
+            },
+            "ignore case" => match on_off(value) {
+                Some(on) => {
+                    options.ignore_case = on;
+                    continue;
+                }
+                None => format!("unknown value '{value}' for ignore case, expected 'on' or 'off'"),
+            },
+            "collapse spaces" => match on_off(value) {
+                Some(on) => {
+                    options.collapse_spaces = on;
+                    continue;
+                }
+                None => {
+                    format!("unknown value '{value}' for collapse spaces, expected 'on' or 'off'")
+                }
+            },
+            "ignore plurals" => match on_off(value) {
+                Some(on) => {
+                    options.ignore_plurals = on;
+                    continue;
+                }
+                None => {
+                    format!("unknown value '{value}' for ignore plurals, expected 'on' or 'off'")
+                }
+            },
+            "number format" => match value.to_lowercase().as_str() {
+                "plain" => {
+                    options.number_format = NumberFormat::Plain;
+                    continue;
+                }
+                "commas" => {
+                    options.number_format = NumberFormat::Commas;
+                    continue;
+                }
+                "dots" => {
+                    options.number_format = NumberFormat::Dots;
+                    continue;
+                }
+                "spaces" => {
+                    options.number_format = NumberFormat::Spaces;
+                    continue;
+                }
+                "compact" => {
+                    options.number_format = NumberFormat::Compact;
+                    continue;
+                }
+                _ => format!(
+                    "unknown number format '{value}', expected 'plain', 'commas', 'dots', \
+                    'spaces' or 'compact'"
+                ),
+            },
+            "tree threshold" => match value.parse() {
+                Ok(threshold) => {
+                    options.tree_threshold = threshold;
+                    continue;
+                }
+                Err(_) => format!("unknown tree threshold '{value}', expected a number"),
+            },
+            _ => format!("unknown option '{key}'"),
+        };
+
+        warnings.push(Diagnostic {
+            span: setting.span,
+            severity: Severity::Warning,
+            message,
+        });
+    }
+
+    (options, warnings)
+}
+
+fn on_off(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Turn the last word of `name` into its singular with a few simple rules, such as
+/// `iron ingots` into `iron ingot` and `berries` into `berry`.
+///
+/// Irregular plurals are left alone, as are words that probably aren't plurals, like `glass`.
+fn singular(name: &str) -> String {
+    let word_start = name.rfind(' ').map_or(0, |space| space + 1);
+    let (start, word) = name.split_at(word_start);
+
+    // short words like "gas" are more likely to be singular
+    if word.len() <= 3 {
+        return name.to_string();
+    }
+
+    let word = if let Some(stem) = word.strip_suffix("ies") {
+        format!("{stem}y")
+    } else if ["ches", "shes", "xes", "sses", "zzes"]
+        .iter()
+        .any(|suffix| word.ends_with(suffix))
+    {
+        word[..word.len() - 2].to_string()
+    } else if word.ends_with("ses") {
+        // "buses" drops "es" but "cases" only "s", and there's no telling which is which
+        word.to_string()
+    } else if word.ends_with('s') && !["ss", "us", "is"].iter().any(|end| word.ends_with(end)) {
+        word[..word.len() - 1].to_string()
+    } else {
+        word.to_string()
+    };
+
+    format!("{start}{word}")
+}
+
+/// Give item names that are the same item with the options of `program` the same spelling,
+/// which is the first one in the document.
+pub fn normalize_item_names(program: &mut Program) {
+    let (options, _) = read(program);
+    if !options.ignore_case && !options.collapse_spaces && !options.ignore_plurals {
+        return;
+    }
+
+    // sections can be in any order, and the sort keeps names within an entry in order
+    let mut items: Vec<_> = program.items_mut().collect();
+    items.sort_by_key(|(start, _)| *start);
+
+    let mut spellings = HashMap::<String, Item>::new();
+    for (_, item) in items {
+        let spelling = options.spelling(&item.0);
+        *item = spellings
+            .entry(options.key(&spelling))
+            .or_insert_with(|| Item::new(spelling))
+            .clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logic::{Item, Program, Severity};
+
+    use super::{read, singular, NeedMode, NumberFormat, RecipeLoops, SurplusSharing};
+
+    #[test]
+    fn test_read() {
+        let input = "options:\n- Need Mode = Best Effort\n- speed = fast\n- need mode = some\n\
+            - recipe loops = seed\n- recipe loops = maybe\n- surplus sharing = OFF\n\
+            - ignore case = on\n- collapse spaces = yes\n- tree threshold = many\n\
+            need:\nhave:\nrecipes:\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        let (options, warnings) = read(&program);
+        assert_eq!(options.need_mode, NeedMode::BestEffort);
+        assert_eq!(options.recipe_loops, RecipeLoops::Seed);
+        assert_eq!(options.surplus_sharing, SurplusSharing::Off);
+        assert!(options.ignore_case);
+        assert!(!options.collapse_spaces);
+
+        let messages: Vec<_> = warnings.iter().map(|w| w.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "unknown option 'speed'",
+                "unknown need mode 'some', expected 'all' or 'best effort'",
+                "unknown recipe loop mode 'maybe', expected 'error' or 'seed'",
+                "unknown value 'yes' for collapse spaces, expected 'on' or 'off'",
+                "unknown tree threshold 'many', expected a number",
+            ]
+        );
+        assert!(warnings.iter().all(|w| w.severity == Severity::Warning));
+        assert_eq!(
+            &input[warnings[0].span.start..warnings[0].span.end],
+            "speed = fast"
+        );
+    }
+
+    #[test]
+    fn test_number_format() {
+        let input = "options:\n- number format = compact\nneed:\nhave:\nrecipes:\n";
+        let program = Program::parse_from_string(input).unwrap();
+        assert_eq!(read(&program).0.number_format, NumberFormat::Compact);
+
+        assert_eq!(NumberFormat::Plain.count(1234567), "1234567");
+        assert_eq!(NumberFormat::Commas.count(1234567), "1,234,567");
+        assert_eq!(NumberFormat::Dots.count(123456), "123.456");
+        assert_eq!(NumberFormat::Spaces.count(999), "999");
+
+        let compact = NumberFormat::Compact;
+        assert_eq!(compact.count(999), "999");
+        assert_eq!(compact.count(1000), "1k");
+        assert_eq!(compact.count(1234), "1.2k");
+        assert_eq!(compact.count(1_250_000), "1.3M");
+        assert_eq!(compact.count(999_950), "1M");
+        assert_eq!(compact.count(u64::MAX), "18446744.1T");
+
+        let program =
+            Program::parse_from_string("need:\nhave:\nrecipes:\n- 4000 b = 1500 a + 2 c\n")
+                .unwrap();
+        let recipe = &program.recipe_section.0[0].value;
+        assert_eq!(
+            NumberFormat::Commas.recipe(recipe),
+            "1,500 a + 2 c -> 4,000 b"
+        );
+    }
+
+    #[test]
+    fn test_normalize_item_names() {
+        let input = "options:\n- ignore case = on\n- collapse spaces = on\n\
+            need:\n- 1 Iron  Ingot\nhave:\n- 1 iron ingot\nrecipes:\n- 1 IRON INGOT = 1 ore\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        let spelling = Item::new("Iron Ingot");
+        assert_eq!(program.need_section.0[0].item, spelling);
+        assert_eq!(program.have_section.0[0].item, spelling);
+        assert_eq!(program.recipe_section.0[0].output.item, spelling);
+
+        let report = program.evaluate().report.unwrap();
+        assert_eq!(report.missing_items, vec![]);
+
+        // the first spelling in the document is used, even if its section comes later
+        let input = "options:\n- ignore case = on\n\
+            have:\n- 1 Iron Ingot\nneed:\n- 1 iron ingot\nrecipes:\n";
+        let program = Program::parse_from_string(input).unwrap();
+        assert_eq!(program.need_section.0[0].item, Item::new("Iron Ingot"));
+
+        // without the options, the names stay as they are
+        let input = "need:\n- 1 Iron  Ingot\nhave:\n- 1 iron ingot\nrecipes:\n";
+        let program = Program::parse_from_string(input).unwrap();
+        assert_eq!(program.need_section.0[0].item.to_string(), "Iron  Ingot");
+    }
+
+    #[test]
+    fn test_singular() {
+        let cases = [
+            ("iron ingots", "iron ingot"),
+            ("sweet berries", "sweet berry"),
+            ("torches", "torch"),
+            ("boxes", "box"),
+            ("glasses", "glass"),
+            ("cases", "cases"),
+            ("buses", "buses"),
+            ("glass", "glass"),
+            ("cactus", "cactus"),
+            ("gas", "gas"),
+            ("iron ingot", "iron ingot"),
+        ];
+
+        for (plural, expected) in cases {
+            assert_eq!(singular(plural), expected, "{plural}");
+        }
+    }
+
+    #[test]
+    fn test_ignore_plurals() {
+        let input = "options:\n- ignore plurals = on\n\
+            need:\n- 2 torches\nhave:\n- 1 sticks\nrecipes:\n- 4 torch = 1 stick + 1 coal\n";
+        let program = Program::parse_from_string(input).unwrap();
+
+        let report = program.evaluate().report.unwrap();
+        assert_eq!(report.missing_items.len(), 1);
+        assert_eq!(report.missing_items[0].to_string(), "1 coal");
+        assert_eq!(report.leftover_items[0].to_string(), "2 torches");
+    }
+}
@@ -1,75 +1,274 @@
 use nom::{
     branch::{alt, permutation},
     bytes::complete::{is_not, tag, take_while1},
-    character::complete::{char, line_ending, multispace0, space0},
-    combinator::eof,
+    character::complete::{char, line_ending, multispace1, one_of, space0},
+    combinator::{consumed, eof, map_opt, opt, recognize},
     multi::{many0, separated_list1},
     sequence::{delimited, pair, preceded, separated_pair, terminated, tuple, Tuple},
-    IResult, Parser,
+    IResult, Offset, Parser,
 };
 
-use super::{HaveSection, Item, ItemStack, NeedSection, Program, Recipe, RecipeSection};
+use super::{
+    Category, CategorySection, HaveSection, Inventory, Item, ItemStack, Need, NeedSection,
+    OptionSection, Program, Recipe, RecipeSection, Setting, Source, SourceSection, Span, Spanned,
+};
+
+/// A parsed value with the text it was parsed from, to find its [Span] with [spanned_all].
+type Entry<'i, T> = (&'i str, T);
+
+/// A section that describes the document rather than what to craft.
+enum Metadata<'i> {
+    Options(Vec<(&'i str, Setting)>),
+    Sources(Vec<(&'i str, Source)>),
+    Categories(Vec<(&'i str, Category)>),
+}
 
 /// Parses a full program.
+///
+/// The optional version header comes first, then include directives. The optional options, sources and categories sections
+/// have to come before or after all other sections.
+///
+/// `#` starts a comment that runs until the end of the line, see [comment].
 pub fn program(input: &str) -> IResult<&str, Program> {
-    let need_section = section("need", item_with_count);
-    let have_section = section("have", item_with_count);
-    let recipe_section = section("recipes", recipe);
+    let need_section = section("need", consumed(need));
+    let have_section = section("have", consumed(item_with_count));
+    let metadata = || {
+        many0(preceded(
+            blank,
+            alt((
+                section("options", consumed(setting)).map(Metadata::Options),
+                section("sources", consumed(source)).map(Metadata::Sources),
+                section("categories", consumed(category)).map(Metadata::Categories),
+            )),
+        ))
+    };
 
     terminated(
-        permutation((
-            preceded(multispace0, need_section),
-            preceded(multispace0, have_section),
-            preceded(multispace0, recipe_section),
+        tuple((
+            opt(preceded(blank, consumed(version))),
+            many0(preceded(blank, consumed(include))),
+            metadata(),
+            permutation((
+                preceded(blank, need_section),
+                preceded(blank, pair(have_section, many0(preceded(blank, inventory)))),
+                preceded(blank, recipe_section),
+            )),
+            metadata(),
         )),
-        multispace0,
+        blank,
+    )
+    .map(
+        |(version, includes, before, (n, (h, inventories), r), after)| {
+            let mut settings = vec![];
+            let mut sources = vec![];
+            let mut categories = vec![];
+            for metadata in before.into_iter().chain(after) {
+                match metadata {
+                    Metadata::Options(entries) => settings.extend(entries),
+                    Metadata::Sources(entries) => sources.extend(entries),
+                    Metadata::Categories(entries) => categories.extend(entries),
+                }
+            }
+
+            Program {
+                version: spanned_all(input, version.into_iter().collect()).pop(),
+                includes: spanned_all(input, includes),
+                included_recipes: vec![],
+                option_section: OptionSection(spanned_all(input, settings)),
+                source_section: SourceSection(spanned_all(input, sources)),
+                category_section: CategorySection(spanned_all(input, categories)),
+                need_section: NeedSection(spanned_all(input, n)),
+                have_section: HaveSection(spanned_all(input, h)),
+                inventories: inventories
+                    .into_iter()
+                    .map(|(player, items)| Inventory {
+                        player: spanned_all(input, vec![player]).remove(0),
+                        items: spanned_all(input, items),
+                    })
+                    .collect(),
+                recipe_section: RecipeSection(spanned_all(input, r)),
+            }
+        },
     )
-    .map(|(n, h, r)| Program {
-        need_section: NeedSection(n),
-        have_section: HaveSection(h),
-        recipe_section: RecipeSection(r),
-    })
     .parse(input)
 }
 
-/// Parses a headered section, such as `section:\n-test1\ntest2\n`.
+/// Attaches the location of each consumed slice within `input` to its value.
+///
+/// Trailing whitespace is not included in the span.
+fn spanned_all<T>(input: &str, values: Vec<(&str, T)>) -> Vec<Spanned<T>> {
+    values
+        .into_iter()
+        .map(|(text, value)| {
+            let start = input.offset(text);
+            let end = start + text.trim_end().len();
+            Spanned {
+                value,
+                span: Span { start, end },
+            }
+        })
+        .collect()
+}
+
+/// Parses a headered section, such as `section:\n-test1\ntest2\n`. Entries can be separated
+/// by comment lines.
 fn section<'i, O, F>(head: &'i str, body: F) -> impl FnMut(&'i str) -> IResult<&'i str, Vec<O>>
 where
     F: Parser<&'i str, O, nom::error::Error<&'i str>>,
 {
     preceded(
         tuple((tag(head), char(':'), fuzzy_line_ending)),
-        many0(list_item(body)),
+        many0(preceded(many0(comment_line), list_item(body))),
     )
 }
 
+/// The items of a player, such as `have alice:\n- 10 iron\n` (where `alice` and `10 iron`
+/// are matched).
+fn inventory(input: &str) -> IResult<&str, (Entry<'_, String>, Vec<Entry<'_, ItemStack>>)> {
+    let player = is_not(":#\r\n").map(|player: &str| player.trim().to_string());
+    let head = delimited(
+        pair(tag("have"), take_while1(|c| c == ' ')),
+        consumed(player),
+        pair(char(':'), fuzzy_line_ending),
+    );
+    let entry = list_item(consumed(item_with_count));
+
+    pair(head, many0(preceded(many0(comment_line), entry))).parse(input)
+}
+
+/// The recipes section, in which a recipe can be preceded by `## ` doc comment lines that
+/// are stored in [Recipe::doc].
+fn recipe_section(input: &str) -> IResult<&str, Vec<(&str, Recipe)>> {
+    let doc_line = delimited(pair(tag("##"), space0), opt(is_not("\r\n")), end_of_line)
+        .map(|line: Option<&str>| Some(line.unwrap_or_default().trim_end()));
+    // plain comments between the doc comment lines are skipped
+    let doc = many0(alt((doc_line, comment_line.map(|_| None))));
+
+    let entry = pair(doc, list_item(consumed(recipe))).map(|(doc, (text, mut recipe))| {
+        let doc: Vec<&str> = doc.into_iter().flatten().collect();
+        if !doc.is_empty() {
+            recipe.doc = Some(doc.join("\n").into());
+        }
+        (text, recipe)
+    });
+
+    preceded(
+        tuple((tag("recipes"), char(':'), fuzzy_line_ending)),
+        many0(entry),
+    )
+    .parse(input)
+}
+
 /// An object inside a line, such as `- wooper!\n` (where `wooper!` is matched).
 fn list_item<'i, O, F>(f: F) -> impl FnMut(&'i str) -> IResult<&'i str, O>
 where
     F: Parser<&'i str, O, nom::error::Error<&'i str>>,
 {
-    delimited(pair(char('-'), space0), f, alt((fuzzy_line_ending, eof)))
+    delimited(pair(char('-'), space0), f, end_of_line)
+}
+
+/// The version header, such as `version: 2\n` (where `2` is matched).
+pub fn version(input: &str) -> IResult<&str, u32> {
+    delimited(
+        pair(tag("version:"), space0),
+        nom::character::complete::u32,
+        end_of_line,
+    )
+    .parse(input)
+}
+
+/// An include directive, such as `include base recipes\n` (where `base recipes` is matched).
+fn include(input: &str) -> IResult<&str, String> {
+    let name = is_not("#\r\n").map(|name: &str| name.trim().to_string());
+
+    delimited(
+        pair(tag("include"), take_while1(|c| c == ' ')),
+        name,
+        end_of_line,
+    )
+    .parse(input)
+}
+
+/// A setting in the options section, such as `need mode = best effort`.
+fn setting(input: &str) -> IResult<&str, Setting> {
+    separated_pair(is_not("=\r\n"), char('='), is_not("#\r\n"))
+        .map(|(key, value): (&str, &str)| Setting {
+            key: key.trim().to_string(),
+            value: value.trim().to_string(),
+        })
+        .parse(input)
+}
+
+/// An entry in the sources section, such as `iron ore @ mines`.
+fn source(input: &str) -> IResult<&str, Source> {
+    separated_pair(is_not("@\r\n"), char('@'), is_not("#\r\n"))
+        .map(|(item, source): (&str, &str)| Source {
+            item: Item::new(item.trim()),
+            source: source.trim().to_string(),
+        })
+        .parse(input)
 }
 
-/// A recipe, such as `1 diamond shovel = 2 stick + 1 diamond`.
-fn recipe(input: &str) -> IResult<&str, Recipe> {
+/// An entry in the categories section, such as `ores: iron ore, copper ore`.
+fn category(input: &str) -> IResult<&str, Category> {
+    let items = separated_list1(char(','), is_not("#,\r\n"));
+
+    separated_pair(is_not(":\r\n"), char(':'), items)
+        .map(|(name, items): (&str, Vec<&str>)| Category {
+            name: name.trim().to_string(),
+            items: items
+                .into_iter()
+                .map(|item| Item::new(item.trim()))
+                .collect(),
+        })
+        .parse(input)
+}
+
+/// A need entry, such as `1 diamond sword`, `1 diamond sword | 1 netherite sword` or
+/// `exact 4 stick`.
+fn need(input: &str) -> IResult<&str, Need> {
+    let exact = opt(terminated(tag("exact"), take_while1(|c| c == ' ')));
+    let or = delimited(space0, char('|'), space0);
+
+    pair(exact, separated_list1(or, item_with_count))
+        .map(|(exact, mut stacks)| Need {
+            stack: stacks.remove(0),
+            alternatives: stacks,
+            exact: exact.is_some(),
+        })
+        .parse(input)
+}
+
+/// A recipe, such as `1 diamond shovel = 2 stick + 1 diamond`. The first output is what the
+/// recipe is for, any others are byproducts. It can start with `prefer` to be used over
+/// other recipes for the same item.
+pub fn recipe(input: &str) -> IResult<&str, Recipe> {
     let equal = delimited(space0, char('='), space0);
-    let plus = delimited(space0, char('+'), space0);
+    let stacks = || separated_list1(delimited(space0, char('+'), space0), item_with_count);
 
-    let mut recipe = separated_pair(
-        item_with_count,
-        equal,
-        separated_list1(plus, item_with_count),
-    );
+    let prefer = opt(terminated(tag("prefer"), take_while1(|c| c == ' ')));
 
-    let (input, (output, inputs)) = recipe.parse(input)?;
+    let mut recipe = pair(prefer, separated_pair(stacks(), equal, stacks()));
 
-    Ok((input, Recipe { output, inputs }))
+    let (input, (prefer, (mut outputs, inputs))) = recipe.parse(input)?;
+    let output = outputs.remove(0);
+
+    Ok((
+        input,
+        Recipe {
+            doc: None,
+            preferred: prefer.is_some(),
+            output,
+            byproducts: outputs,
+            inputs,
+        },
+    ))
 }
 
 /// An item with a count, such as `1 wood` or `10 diamond shovel`.
+///
+/// The count can be a simple expression such as `64/4`, see [count].
 fn item_with_count(input: &str) -> IResult<&str, ItemStack> {
-    let count = nom::character::complete::u64;
     let space = take_while1(|c| c == ' ');
 
     let (input, (count, _, item)) = (count, space, item).parse(input)?;
@@ -77,28 +276,104 @@ fn item_with_count(input: &str) -> IResult<&str, ItemStack> {
     Ok((input, ItemStack { count, item }))
 }
 
+/// A count, which is a number or an expression such as `64/4` or `2*(8+1)`, evaluated
+/// while parsing.
+///
+/// `+` and `-` are only allowed inside parentheses, since `+` also separates the inputs of
+/// a recipe. Division has to be exact, and counts that overflow are rejected.
+fn count(input: &str) -> IResult<&str, u64> {
+    product(input)
+}
+
+/// Counts multiplied or divided by each other, such as `64/4` or `2*3`.
+fn product(input: &str) -> IResult<&str, u64> {
+    let operator = delimited(space0, one_of("*/"), space0);
+
+    map_opt(
+        pair(factor, many0(pair(operator, factor))),
+        |(first, rest)| {
+            rest.into_iter()
+                .try_fold(first, |value, (operator, factor)| match operator {
+                    '*' => value.checked_mul(factor),
+                    _ if factor != 0 && value % factor == 0 => Some(value / factor),
+                    _ => None,
+                })
+        },
+    )
+    .parse(input)
+}
+
+/// Products added to or subtracted from each other, such as `8+1`.
+fn sum(input: &str) -> IResult<&str, u64> {
+    let operator = delimited(space0, one_of("+-"), space0);
+
+    map_opt(
+        pair(product, many0(pair(operator, product))),
+        |(first, rest)| {
+            rest.into_iter()
+                .try_fold(first, |value, (operator, product)| match operator {
+                    '+' => value.checked_add(product),
+                    _ => value.checked_sub(product),
+                })
+        },
+    )
+    .parse(input)
+}
+
+/// A number, or a sum in parentheses.
+fn factor(input: &str) -> IResult<&str, u64> {
+    alt((
+        nom::character::complete::u64,
+        delimited(pair(char('('), space0), sum, pair(space0, char(')'))),
+    ))
+    .parse(input)
+}
+
 /// An item name, such as `wood` or `diamond shovel`.
 fn item(input: &str) -> IResult<&str, Item> {
-    is_not("+=\r\n")
+    is_not("#+=|\r\n")
         .map(|item: &str| item.trim()) // this trim is somewhat hacky
         .map(Item::new)
         .parse(input)
 }
 
-/// A line ending that may be preceeded by spaces.
+/// A comment, such as `# from the wiki`, which runs until the end of the line and is ignored.
+fn comment(input: &str) -> IResult<&str, &str> {
+    recognize(pair(char('#'), opt(is_not("\r\n")))).parse(input)
+}
+
+/// A line that only holds a comment, such as a disabled entry `# - 1 stick`.
+fn comment_line(input: &str) -> IResult<&str, &str> {
+    recognize(tuple((space0, comment, line_ending))).parse(input)
+}
+
+/// Whitespace and comments between sections, which may be empty.
+pub fn blank(input: &str) -> IResult<&str, &str> {
+    recognize(many0(alt((multispace1, comment)))).parse(input)
+}
+
+/// The end of an entry: a line ending or the end of the document, which may be preceded by
+/// spaces and a comment.
+fn end_of_line(input: &str) -> IResult<&str, &str> {
+    alt((fuzzy_line_ending, preceded(pair(space0, opt(comment)), eof))).parse(input)
+}
+
+/// A line ending that may be preceeded by spaces and a comment.
 ///
 /// This type does not return the entire matched `&str` because it's
 /// impractical to do so. It would either require stitching 2 separate `&str`s
 /// together or custom matching logic.
 fn fuzzy_line_ending(input: &str) -> IResult<&str, &str> {
-    preceded(space0, line_ending).parse(input)
+    preceded(pair(space0, opt(comment)), line_ending).parse(input)
 }
 
 #[cfg(test)]
 mod tests {
     use nom::character::complete::{alpha1, alphanumeric1};
 
-    use crate::logic::{parsing::*, Item, ItemStack, Recipe};
+    use crate::logic::{
+        parsing::*, Category, Item, ItemStack, Need, Recipe, Setting, Source, Span,
+    };
 
     #[test]
     fn smoke_test_example_input() {
@@ -111,6 +386,134 @@ mod tests {
         assert_eq!(program.recipe_section.0.len(), 3);
     }
 
+    #[test]
+    fn test_program_spans() {
+        let input = "need:\n- 1 output \nhave:\nrecipes:\n- 1 output = 1 input\n";
+
+        let (_, program) = program(input).unwrap();
+
+        let need_span = program.need_section.0[0].span;
+        assert_eq!(need_span, Span { start: 8, end: 16 });
+        assert_eq!(&input[need_span.start..need_span.end], "1 output");
+
+        let recipe_span = program.recipe_section.0[0].span;
+        assert_eq!(
+            &input[recipe_span.start..recipe_span.end],
+            "1 output = 1 input"
+        );
+    }
+
+    #[test]
+    fn test_includes() {
+        let input = "include base recipes \ninclude  tools\nneed:\nhave:\nrecipes:\n";
+
+        let (remaining, parsed) = program(input).unwrap();
+        assert_eq!(remaining, "");
+
+        let includes: Vec<_> = parsed.includes.iter().map(|i| i.value.as_str()).collect();
+        assert_eq!(includes, ["base recipes", "tools"]);
+        let span = parsed.includes[0].span;
+        assert_eq!(&input[span.start..span.end], "include base recipes");
+    }
+
+    #[test]
+    fn test_option_section() {
+        let setting = |key: &str, value: &str| Setting {
+            key: key.into(),
+            value: value.into(),
+        };
+
+        let input = "options:\n- need mode = best effort\nneed:\nhave:\nrecipes:\n";
+        let (remaining, parsed) = program(input).unwrap();
+        assert_eq!(remaining, "");
+        assert_eq!(
+            parsed.option_section.0[0].value,
+            setting("need mode", "best effort")
+        );
+
+        let input = "need:\nhave:\nrecipes:\n\noptions:\n- a=b\n";
+        let (remaining, parsed) = program(input).unwrap();
+        assert_eq!(remaining, "");
+        assert_eq!(parsed.option_section.0[0].value, setting("a", "b"));
+
+        let (_, parsed) = program("need:\nhave:\nrecipes:\n").unwrap();
+        assert!(parsed.option_section.0.is_empty());
+    }
+
+    #[test]
+    fn test_source_section() {
+        let source = |item: &str, source: &str| Source {
+            item: Item::new(item),
+            source: source.into(),
+        };
+
+        let input = "sources:\n- iron ore @ mines\noptions:\n- a = b\nneed:\nhave:\nrecipes:\n\
+            sources:\n- string@spiders\n";
+        let (remaining, parsed) = program(input).unwrap();
+        assert_eq!(remaining, "");
+        let sources: Vec<_> = parsed.source_section.0.iter().map(|s| &s.value).collect();
+        assert_eq!(
+            sources,
+            [&source("iron ore", "mines"), &source("string", "spiders")]
+        );
+        assert_eq!(parsed.option_section.0.len(), 1);
+    }
+
+    #[test]
+    fn test_version() {
+        let input = "version: 2\ninclude base\nneed:\nhave:\nrecipes:\n";
+        let (remaining, parsed) = program(input).unwrap();
+        assert_eq!(remaining, "");
+        let version = parsed.version.unwrap();
+        assert_eq!(version.value, 2);
+        assert_eq!(&input[version.span.start..version.span.end], "version: 2");
+        assert_eq!(parsed.includes.len(), 1);
+
+        let (_, parsed) = program("need:\nhave:\nrecipes:\n").unwrap();
+        assert!(parsed.version.is_none());
+    }
+
+    #[test]
+    fn test_recipe_doc() {
+        let input =
+            "need:\nhave:\nrecipes:\n## level 3 blacksmith\n##\n## costs gold\n- 1 a = 1 b\n\
+            - 1 c = 1 d\n";
+        let (remaining, parsed) = program(input).unwrap();
+        assert_eq!(remaining, "");
+
+        let recipes = &parsed.recipe_section.0;
+        assert_eq!(
+            recipes[0].doc.as_deref(),
+            Some("level 3 blacksmith\n\ncosts gold")
+        );
+        assert_eq!(
+            &input[recipes[0].span.start..recipes[0].span.end],
+            "1 a = 1 b"
+        );
+        assert_eq!(recipes[1].doc, None);
+    }
+
+    #[test]
+    fn test_category_section() {
+        let input = "need:\nhave:\nrecipes:\ncategories:\n- ores: iron ore, coal \n- crops:wheat\n";
+        let (remaining, parsed) = program(input).unwrap();
+        assert_eq!(remaining, "");
+        let categories: Vec<_> = parsed.category_section.0.iter().map(|c| &c.value).collect();
+        assert_eq!(
+            categories,
+            [
+                &Category {
+                    name: "ores".into(),
+                    items: vec![Item::new("iron ore"), Item::new("coal")],
+                },
+                &Category {
+                    name: "crops".into(),
+                    items: vec![Item::new("wheat")],
+                },
+            ]
+        );
+    }
+
     #[test]
     fn test_section() {
         assert_eq!(
@@ -143,6 +546,9 @@ mod tests {
             Ok((
                 "",
                 Recipe {
+                    doc: None,
+                    preferred: false,
+                    byproducts: vec![],
                     output: ItemStack {
                         count: 1,
                         item: Item::new("output")
@@ -159,6 +565,9 @@ mod tests {
             Ok((
                 "",
                 Recipe {
+                    doc: None,
+                    preferred: false,
+                    byproducts: vec![],
                     output: ItemStack {
                         count: 1,
                         item: Item::new("output")
@@ -181,6 +590,9 @@ mod tests {
             Ok((
                 "",
                 Recipe {
+                    doc: None,
+                    preferred: false,
+                    byproducts: vec![],
                     output: ItemStack {
                         count: 1,
                         item: Item::new("output")
@@ -203,6 +615,9 @@ mod tests {
             Ok((
                 "",
                 Recipe {
+                    doc: None,
+                    preferred: false,
+                    byproducts: vec![],
                     output: ItemStack {
                         count: 1,
                         item: Item::new("output thing")
@@ -222,6 +637,99 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_inventories() {
+        let input = "need:\nhave:\n- 1 a\nhave alice:\n- 2 b\n\nhave  bob :\nrecipes:\n";
+        let (_, program) = program(input).expect("should parse");
+        assert_eq!(program.have_section.0.len(), 1);
+
+        let players: Vec<_> = program.inventories.iter().map(|i| &i.player).collect();
+        assert_eq!(players[0].value, "alice");
+        let bob = players[1].span;
+        assert_eq!(&input[bob.start..bob.end], "bob");
+        assert_eq!(program.inventories[0].items[0].to_string(), "2 b");
+        assert_eq!(program.inventories[1].items, vec![]);
+        assert_eq!(program.haves().count(), 2);
+    }
+
+    #[test]
+    fn test_recipe_prefer() {
+        let (_, preferred) = recipe("prefer 1 iron = 2 scrap").unwrap();
+        assert!(preferred.preferred);
+        assert_eq!(preferred.output.item, Item::new("iron"));
+
+        let (_, plain) = recipe("1 preferred iron = 2 scrap").unwrap();
+        assert!(!plain.preferred);
+        assert_eq!(plain.output.item, Item::new("preferred iron"));
+    }
+
+    #[test]
+    fn test_recipe_byproducts() {
+        let (_, recipe) = recipe("1 refined oil + 2 petroleum gas = 4 crude oil").unwrap();
+        assert_eq!(recipe.output.item, Item::new("refined oil"));
+        assert_eq!(
+            recipe.byproducts,
+            vec![ItemStack {
+                count: 2,
+                item: Item::new("petroleum gas")
+            }]
+        );
+        assert_eq!(recipe.inputs.len(), 1);
+    }
+
+    #[test]
+    fn test_need() {
+        let stack = |count, item: &str| ItemStack {
+            count,
+            item: Item::new(item),
+        };
+
+        assert_eq!(
+            need("1 diamond sword"),
+            Ok((
+                "",
+                Need {
+                    stack: stack(1, "diamond sword"),
+                    alternatives: vec![],
+                    exact: false,
+                }
+            ))
+        );
+        assert_eq!(
+            need("1 diamond sword | 1 netherite sword|2 stone sword"),
+            Ok((
+                "",
+                Need {
+                    stack: stack(1, "diamond sword"),
+                    alternatives: vec![stack(1, "netherite sword"), stack(2, "stone sword")],
+                    exact: false,
+                }
+            ))
+        );
+        assert_eq!(
+            need("exact 4 stick"),
+            Ok((
+                "",
+                Need {
+                    stack: stack(4, "stick"),
+                    alternatives: vec![],
+                    exact: true,
+                }
+            ))
+        );
+        assert_eq!(
+            need("4 exact stick"),
+            Ok((
+                "",
+                Need {
+                    stack: stack(4, "exact stick"),
+                    alternatives: vec![],
+                    exact: false,
+                }
+            ))
+        );
+    }
+
     #[test]
     fn test_item_with_count() {
         assert_eq!(
@@ -246,6 +754,97 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_count() {
+        assert_eq!(count("12"), Ok(("", 12)));
+        assert_eq!(count("64/4 coal"), Ok((" coal", 16)));
+        assert_eq!(count("2 * 3 coal"), Ok((" coal", 6)));
+        assert_eq!(count("2*(3+1)-1"), Ok(("-1", 8)));
+        assert_eq!(count("(10 - 4)/3"), Ok(("", 2)));
+        assert!(count("10/3").is_err());
+        assert!(count("1/0").is_err());
+        assert!(count("(1-2)").is_err());
+        assert!(count("18446744073709551615*2").is_err());
+
+        assert_eq!(
+            recipe("1 torch stack = 64/4 coal + 64/4 stick"),
+            Ok((
+                "",
+                Recipe {
+                    doc: None,
+                    preferred: false,
+                    byproducts: vec![],
+                    output: ItemStack {
+                        count: 1,
+                        item: Item::new("torch stack")
+                    },
+                    inputs: vec![
+                        ItemStack {
+                            count: 16,
+                            item: Item::new("coal")
+                        },
+                        ItemStack {
+                            count: 16,
+                            item: Item::new("stick")
+                        },
+                    ],
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        let input = "# my recipes\n\
+            need: # for the base\n\
+            - 1 pickaxe # the good one\n\
+            # - 1 shovel\n\
+            \x20 # indented\n\
+            - 1 axe\n\
+            have:\n\
+            # nothing yet\n\
+            recipes:\n\
+            ## from the wiki\n\
+            # not a note\n\
+            ## at the workbench\n\
+            - 1 pickaxe = 3 iron + 2 stick # or gold\n\
+            ## a stray note\n\
+            \n\
+            # the end";
+        let (remaining, program) = program(input).unwrap();
+        assert_eq!(remaining, "");
+
+        let needs: Vec<_> = program
+            .need_section
+            .0
+            .iter()
+            .map(|need| need.value.to_string())
+            .collect();
+        assert_eq!(needs, ["1 pickaxe", "1 axe"]);
+        assert_eq!(program.have_section.0, vec![]);
+        let recipe = &program.recipe_section.0[0];
+        assert_eq!(
+            recipe.value.doc.as_deref(),
+            Some("from the wiki\nat the workbench")
+        );
+        assert_eq!(recipe.value.inputs[1].item, Item::new("stick"));
+        assert_eq!(
+            &input[recipe.span.start..recipe.span.end],
+            "1 pickaxe = 3 iron + 2 stick"
+        );
+
+        assert_eq!(
+            setting("need mode = best effort # for now"),
+            Ok((
+                "# for now",
+                Setting {
+                    key: "need mode".into(),
+                    value: "best effort".into()
+                }
+            ))
+        );
+    }
+
     #[test]
     fn test_fuzzy_line_ending() {
         assert_eq!(
@@ -0,0 +1,122 @@
+use super::{parsing, ItemStack, Program, Recipe, Span, Spanned};
+
+/// Crafting steps that are done already, so a big project can be worked through over
+/// several sessions.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Progress {
+    /// The steps in the order they were checked off, with the counts they were done with.
+    pub completed: Vec<Recipe>,
+}
+
+impl Progress {
+    /// Write the completed steps as one recipe per line, such as `8 plank = 2 log`.
+    pub fn encode(&self) -> String {
+        let line = |recipe: &Recipe| {
+            let outputs: Vec<String> = recipe.outputs().map(ItemStack::to_string).collect();
+            let inputs: Vec<String> = recipe.inputs.iter().map(ItemStack::to_string).collect();
+            format!("{} = {}\n", outputs.join(" + "), inputs.join(" + "))
+        };
+        self.completed.iter().map(line).collect()
+    }
+
+    /// Read steps written by [Progress::encode], leaving out lines that can't be read.
+    pub fn decode(text: &str) -> Self {
+        let completed = text
+            .lines()
+            .filter_map(|line| match parsing::recipe(line) {
+                Ok(("", recipe)) => Some(recipe),
+                _ => None,
+            })
+            .collect();
+        Self { completed }
+    }
+
+    /// Evaluate `program` as if the completed steps were done: their ingredients are taken
+    /// from the have sections and what they made is added to the have section.
+    ///
+    /// Added have entries are not in the document, so their span is made up and nothing
+    /// points to them, see [Span::is_made_up].
+    pub fn apply(&self, program: &mut Program) {
+        for step in &self.completed {
+            for input in &step.inputs {
+                let mut left = input.count;
+                let haves = program.have_section.0.iter_mut();
+                let inventories = program.inventories.iter_mut().flat_map(|i| &mut i.items);
+                for have in haves.chain(inventories) {
+                    if have.value.item == input.item {
+                        let taken = left.min(have.value.count);
+                        have.value.count -= taken;
+                        left -= taken;
+                    }
+                }
+            }
+
+            for output in step.outputs() {
+                let haves = &mut program.have_section.0;
+                match haves.iter_mut().find(|have| have.value.item == output.item) {
+                    Some(have) => have.value.count = have.value.count.saturating_add(output.count),
+                    None => haves.push(Spanned {
+                        value: output.clone(),
+                        span: Span::default(),
+                    }),
+                }
+            }
+        }
+
+        // used up entries would be reported as surplus haves otherwise
+        program.have_section.0.retain(|have| have.count > 0);
+        for inventory in &mut program.inventories {
+            inventory.items.retain(|have| have.count > 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logic::Program;
+
+    use super::Progress;
+
+    #[test]
+    fn test_progress() {
+        let input = "need:\n- 1 pickaxe\nhave:\n- 2 log\nrecipes:\n- 4 plank = 1 log\n\
+            - 4 stick = 2 plank\n- 1 pickaxe = 2 stick + 3 plank\n";
+        let mut program = Program::parse_from_string(input).unwrap();
+        let report = program.evaluate().report.unwrap();
+        let step = report
+            .executed_recipes
+            .iter()
+            .find(|recipe| recipe.output.item.to_string() == "plank")
+            .unwrap();
+
+        let progress = Progress {
+            completed: vec![step.clone()],
+        };
+        assert_eq!(progress.encode(), "8 plank = 2 log\n");
+        assert_eq!(Progress::decode(&progress.encode()), progress);
+        assert_eq!(Progress::decode("oops\n").completed, []);
+
+        progress.apply(&mut program);
+        let haves: Vec<_> = program.haves().map(|have| have.to_string()).collect();
+        assert_eq!(haves, ["8 plank"]);
+
+        let evaluation = program.evaluate();
+        assert_eq!(evaluation.diagnostics, []);
+        let report = evaluation.report.unwrap();
+        assert!(report.missing_items.is_empty());
+        let steps: Vec<_> = report
+            .executed_recipes
+            .iter()
+            .map(|recipe| recipe.output.to_string())
+            .collect();
+        assert_eq!(steps, ["4 stick", "1 pickaxe"]);
+
+        // the unused planks that were added don't get a warning at the start of the document
+        let input = "need:\n- 1 stick\nhave:\n- 1 log\nrecipes:\n- 4 stick = 1 log\n";
+        let mut program = Program::parse_from_string(input).unwrap();
+        Progress::decode("4 plank = 1 log\n").apply(&mut program);
+        let evaluation = program.evaluate();
+        assert_eq!(evaluation.diagnostics, []);
+        assert_eq!(evaluation.report.unwrap().missing_items.len(), 1);
+    }
+}
@@ -0,0 +1,1121 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Display, Write},
+};
+
+use indexmap::IndexMap;
+
+use super::{
+    evaluation::Context, options::NumberFormat, Coverage, Item, ItemStack, Program, Recipe, Spanned,
+};
+
+/// The outcome of evaluating a program, in a form that can be rendered in
+/// different ways.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Report {
+    /// Items that are needed but not available and can't be crafted.
+    pub missing_items: Vec<ItemStack>,
+    /// Items that are left over after crafting.
+    pub leftover_items: Vec<ItemStack>,
+    /// The recipes that were used, multiplied by how often they are executed.
+    pub executed_recipes: Vec<Recipe>,
+    /// How each need entry is created.
+    pub crafting_tree: Vec<CraftingNode>,
+    /// Needs that were left out because not everything for them is available.
+    pub dropped_needs: Vec<ItemStack>,
+    /// Every step the evaluation took, to find out why a plan looks the way it does.
+    pub trace: Vec<TraceEvent>,
+    /// The missing items grouped by where they are gathered, in the order of the sources
+    /// section. Items without a source come last.
+    pub missing_by_source: Vec<ItemGroup>,
+    /// The missing items grouped like the categories section, with uncategorized items last.
+    pub missing_by_category: Vec<ItemGroup>,
+    /// What the inventory of each player gives, from the `have <player>:` sections.
+    pub contributions: Vec<Contribution>,
+    /// Byproducts that no need or recipe in the plan uses, which pile up and have to be
+    /// stored or thrown away.
+    pub dead_end_byproducts: Vec<DeadEndByproduct>,
+}
+
+/// A byproduct that nothing uses, see [Report::dead_end_byproducts].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DeadEndByproduct {
+    /// The byproduct, with how many the plan makes of it in total.
+    pub stack: ItemStack,
+    /// The item of the recipe that makes it.
+    pub recipe: Item,
+    /// How many are made every time the recipe runs.
+    pub per_run: u64,
+}
+
+/// Find the byproducts of the `executed` recipes, with how often they ran, that are not an
+/// ingredient of any of them nor needed.
+fn dead_end_byproducts(program: &Program, executed: &[(&Recipe, u64)]) -> Vec<DeadEndByproduct> {
+    let needs = program.need_section.0.iter().flat_map(|need| {
+        let need = &need.value;
+        std::iter::once(&need.stack).chain(&need.alternatives)
+    });
+    let inputs = executed.iter().flat_map(|(recipe, _)| &recipe.inputs);
+    let used: HashSet<&Item> = needs.chain(inputs).map(|stack| &stack.item).collect();
+
+    executed
+        .iter()
+        .flat_map(|&(recipe, runs)| {
+            let byproducts = recipe.byproducts.iter();
+            let unused = byproducts.filter(|byproduct| !used.contains(&byproduct.item));
+            unused.map(move |byproduct| DeadEndByproduct {
+                stack: ItemStack {
+                    item: byproduct.item.clone(),
+                    count: byproduct.count.saturating_mul(runs),
+                },
+                recipe: recipe.output.item.clone(),
+                per_run: byproduct.count,
+            })
+        })
+        .collect()
+}
+
+/// The items a player gives to the plan, see [Report::contributions].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Contribution {
+    pub player: String,
+    /// The items from the player's inventory that get used up.
+    pub consumed: Vec<ItemStack>,
+}
+
+/// Work out who gave the items that were used, for [Report::contributions].
+///
+/// The have section is used up first, and then the inventories in order, so the `leftover`
+/// items are given back starting at the last inventory.
+fn contributions(program: &Program, leftover: &[ItemStack]) -> Vec<Contribution> {
+    let mut left: HashMap<&Item, u64> = leftover.iter().map(|s| (&s.item, s.count)).collect();
+    let mut give_back = |entries: &[Spanned<ItemStack>]| {
+        let mut consumed = IndexMap::<Item, u64>::new();
+        for entry in entries.iter().rev() {
+            let kept = match left.get_mut(&entry.item) {
+                Some(left) => {
+                    let kept = entry.count.min(*left);
+                    *left -= kept;
+                    kept
+                }
+                None => 0,
+            };
+            let total = consumed.entry(entry.item.clone()).or_default();
+            *total = total.saturating_add(entry.count - kept);
+        }
+        consumed.reverse();
+        let consumed = consumed.into_iter().filter(|(_, count)| *count > 0);
+        consumed
+            .map(|(item, count)| ItemStack { item, count })
+            .collect()
+    };
+
+    let mut contributions: Vec<Contribution> = program
+        .inventories
+        .iter()
+        .rev()
+        .map(|inventory| Contribution {
+            player: inventory.player.value.clone(),
+            consumed: give_back(&inventory.items),
+        })
+        .collect();
+    contributions.reverse();
+    contributions
+}
+
+/// The items that go into a single need entry, see [Report::goals].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Goal {
+    /// What the need entry asked for.
+    pub stack: ItemStack,
+    /// The items it takes from what is available, including leftovers of earlier needs.
+    pub from_inventory: Vec<ItemStack>,
+    /// The items it takes that have to be gathered.
+    pub missing: Vec<ItemStack>,
+}
+
+/// Missing items that have something in common, such as where they are gathered.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ItemGroup {
+    /// What the items have in common, or [None] for the items that aren't in any group.
+    pub name: Option<String>,
+    pub items: Vec<ItemStack>,
+}
+
+impl ItemGroup {
+    /// The number of items in the group.
+    pub fn total(&self) -> u64 {
+        self.items
+            .iter()
+            .fold(0, |total, stack| total.saturating_add(stack.count))
+    }
+}
+
+/// Group `missing` by `names`, which gives the group of an item in the order the groups
+/// should be listed in. Only the first group of an item is used.
+fn group_missing<'p>(
+    missing: &[ItemStack],
+    names: impl Iterator<Item = (&'p Item, &'p str)>,
+) -> Vec<ItemGroup> {
+    let names: Vec<_> = names.collect();
+    let mut groups: Vec<ItemGroup> = vec![];
+    for (_, name) in &names {
+        if !groups
+            .iter()
+            .any(|group| group.name.as_deref() == Some(name))
+        {
+            groups.push(ItemGroup {
+                name: Some(name.to_string()),
+                items: vec![],
+            });
+        }
+    }
+
+    let mut ungrouped = ItemGroup {
+        name: None,
+        items: vec![],
+    };
+    for stack in missing {
+        let name = names.iter().find(|(item, _)| **item == stack.item);
+        let group = match name {
+            Some((_, name)) => groups
+                .iter_mut()
+                .find(|group| group.name.as_deref() == Some(name))
+                .expect("every name has a group"),
+            None => &mut ungrouped,
+        };
+        group.items.push(stack.clone());
+    }
+
+    groups.push(ungrouped);
+    groups.retain(|group| !group.items.is_empty());
+    groups
+}
+
+/// A single step of the evaluation.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TraceEvent {
+    pub item: Item,
+    /// How deep in the crafting tree the step happened, with 0 being a need entry.
+    pub depth: usize,
+    pub action: TraceAction,
+    /// How many items the step was about.
+    pub count: u64,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TraceAction {
+    /// The items were asked for.
+    Requested,
+    /// The items were taken from what is available.
+    Taken,
+    /// The items were crafted by running a recipe.
+    Crafted { runs: u64 },
+    /// The items were made along with another item, and are available from now on.
+    Byproduct,
+    /// The items could not be obtained.
+    Missing,
+}
+
+impl Display for TraceAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TraceAction::Requested => write!(f, "requested"),
+            TraceAction::Taken => write!(f, "taken"),
+            TraceAction::Crafted { runs } => write!(f, "crafted in {runs} runs"),
+            TraceAction::Byproduct => write!(f, "made as a byproduct"),
+            TraceAction::Missing => write!(f, "missing"),
+        }
+    }
+}
+
+/// How a stack of items is obtained.
+///
+/// Chains of recipes can be thousands of nodes deep, so cloning, comparing and dropping a
+/// tree is done with an explicit stack instead of recursion.
+#[derive(Debug)]
+pub struct CraftingNode {
+    /// The items that were asked for.
+    pub stack: ItemStack,
+    /// How many of these items were already available.
+    pub taken_from_inventory: u64,
+    /// How often a recipe was executed to craft the rest.
+    pub recipe_runs: u64,
+    /// How many items those recipe runs created, which may be more than needed.
+    pub crafted: u64,
+    /// How many items could not be obtained.
+    pub missing: u64,
+    /// The ingredients for the recipe runs.
+    pub inputs: Vec<CraftingNode>,
+}
+
+impl CraftingNode {
+    /// How much of the requested stack could be obtained, including its ingredients.
+    pub fn coverage(&self) -> Coverage {
+        if !self.any(&|node| node.missing > 0) {
+            Coverage::Full
+        } else if self.any(&|node| node.taken_from_inventory > 0) {
+            Coverage::Partial
+        } else {
+            Coverage::None
+        }
+    }
+
+    /// A short description of where the items come from, such as `1 from inventory, 3 missing`.
+    pub fn details(&self, format: NumberFormat) -> String {
+        let mut details = vec![];
+        if self.taken_from_inventory > 0 {
+            let taken = format.count(self.taken_from_inventory);
+            details.push(format!("{taken} from inventory"));
+        }
+        if self.recipe_runs > 0 {
+            details.push(format!(
+                "crafted {} in {} runs",
+                format.count(self.crafted),
+                format.count(self.recipe_runs)
+            ));
+        }
+        if self.missing > 0 {
+            details.push(format!("{} missing", format.count(self.missing)));
+        }
+        details.join(", ")
+    }
+
+    /// Whether this is a crafted ingredient of fewer than `threshold` items that has
+    /// everything it needs, which can be folded away to keep large trees readable.
+    ///
+    /// `coverage` is the [CraftingNode::coverage] of this node, which is worked out once for
+    /// all nodes when looking at many of them.
+    pub fn is_minor(&self, threshold: u64, coverage: Coverage) -> bool {
+        self.stack.count < threshold && self.recipe_runs > 0 && coverage == Coverage::Full
+    }
+
+    /// This node and all of its ingredients, recursively, with each node before its inputs.
+    fn nodes(&self) -> Vec<&CraftingNode> {
+        let mut nodes = vec![];
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            nodes.push(node);
+            stack.extend(node.inputs.iter().rev());
+        }
+        nodes
+    }
+
+    /// Whether this node or any of its ingredients match `predicate`.
+    fn any(&self, predicate: &impl Fn(&CraftingNode) -> bool) -> bool {
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            if predicate(node) {
+                return true;
+            }
+            stack.extend(&node.inputs);
+        }
+        false
+    }
+
+    /// A copy of this node without its inputs.
+    fn clone_shallow(&self) -> Self {
+        Self {
+            stack: self.stack.clone(),
+            taken_from_inventory: self.taken_from_inventory,
+            recipe_runs: self.recipe_runs,
+            crafted: self.crafted,
+            missing: self.missing,
+            inputs: Vec::with_capacity(self.inputs.len()),
+        }
+    }
+}
+
+impl Clone for CraftingNode {
+    fn clone(&self) -> Self {
+        // each original with its copy, which gets its inputs once they are copied themselves
+        let mut stack = vec![(self, self.clone_shallow())];
+        loop {
+            let (original, copy) = stack.last().unwrap();
+            let (original, copied) = (*original, copy.inputs.len());
+            match original.inputs.get(copied) {
+                Some(input) => stack.push((input, input.clone_shallow())),
+                None => {
+                    let (_, copy) = stack.pop().unwrap();
+                    match stack.last_mut() {
+                        Some((_, parent)) => parent.inputs.push(copy),
+                        None => return copy,
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl PartialEq for CraftingNode {
+    fn eq(&self, other: &Self) -> bool {
+        let mut stack = vec![(self, other)];
+        while let Some((a, b)) = stack.pop() {
+            let same = a.stack == b.stack
+                && a.taken_from_inventory == b.taken_from_inventory
+                && a.recipe_runs == b.recipe_runs
+                && a.crafted == b.crafted
+                && a.missing == b.missing
+                && a.inputs.len() == b.inputs.len();
+            if !same {
+                return false;
+            }
+            stack.extend(a.inputs.iter().zip(&b.inputs));
+        }
+        true
+    }
+}
+
+impl Eq for CraftingNode {}
+
+impl Drop for CraftingNode {
+    fn drop(&mut self) {
+        // move the inputs out first, so no node is dropped while it still has any
+        let mut stack = std::mem::take(&mut self.inputs);
+        while let Some(mut node) = stack.pop() {
+            stack.append(&mut node.inputs);
+        }
+    }
+}
+
+impl Report {
+    pub(super) fn new(context: &Context, program: &Program) -> Self {
+        let missing_items = context.get_missing_items();
+        Self {
+            missing_by_source: group_missing(
+                &missing_items,
+                program
+                    .source_section
+                    .0
+                    .iter()
+                    .map(|source| (&source.value.item, source.value.source.as_str())),
+            ),
+            missing_by_category: group_missing(
+                &missing_items,
+                program.category_section.0.iter().flat_map(|category| {
+                    let category = &category.value;
+                    let name = category.name.as_str();
+                    category.items.iter().map(move |item| (item, name))
+                }),
+            ),
+            contributions: contributions(program, &context.get_available_items()),
+            dead_end_byproducts: dead_end_byproducts(program, &context.get_recipe_run_counts()),
+            missing_items,
+            leftover_items: context.get_available_items(),
+            executed_recipes: context.get_executed_recipes(),
+            crafting_tree: context.get_crafting_tree().to_vec(),
+            dropped_needs: context.get_dropped_needs(),
+            trace: context.get_trace().to_vec(),
+        }
+    }
+
+    /// How often recipes are executed in total, which is how many crafting operations the
+    /// plan takes.
+    pub fn crafting_operations(&self) -> u64 {
+        let nodes = self.crafting_tree.iter().flat_map(CraftingNode::nodes);
+        nodes.fold(0, |total, node| total.saturating_add(node.recipe_runs))
+    }
+
+    /// What each need entry takes, in order, to split the cost of a shared project.
+    ///
+    /// Needs are made in order, so a need that uses the leftovers of an earlier one counts
+    /// those as taken from the inventory.
+    pub fn goals(&self) -> Vec<Goal> {
+        let total = |stacks: IndexMap<Item, u64>| {
+            let stacks = stacks.into_iter().filter(|(_, count)| *count > 0);
+            stacks
+                .map(|(item, count)| ItemStack { item, count })
+                .collect()
+        };
+
+        self.crafting_tree
+            .iter()
+            .map(|need| {
+                let mut from_inventory = IndexMap::<Item, u64>::new();
+                let mut missing = IndexMap::<Item, u64>::new();
+                for node in need.nodes() {
+                    let item = &node.stack.item;
+                    let taken = from_inventory.entry(item.clone()).or_default();
+                    *taken = taken.saturating_add(node.taken_from_inventory);
+                    let gathered = missing.entry(item.clone()).or_default();
+                    *gathered = gathered.saturating_add(node.missing);
+                }
+
+                Goal {
+                    stack: need.stack.clone(),
+                    from_inventory: total(from_inventory),
+                    missing: total(missing),
+                }
+            })
+            .collect()
+    }
+
+    /// How many different recipes the plan uses.
+    pub fn recipes_used(&self) -> usize {
+        let nodes = self.crafting_tree.iter().flat_map(CraftingNode::nodes);
+        let crafted = nodes.filter(|node| node.recipe_runs > 0);
+        crafted
+            .map(|node| &node.stack.item)
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// The parts of the report that are about items whose name contains `filter`, ignoring
+    /// case.
+    ///
+    /// Recipes are kept if any of their items match. The crafting tree of a need is kept as
+    /// a whole if an item anywhere in it matches, so it still shows what the item is for.
+    pub fn filtered(&self, filter: &str) -> Report {
+        let filter = filter.to_lowercase();
+        let matches = |item: &Item| item.to_string().to_lowercase().contains(&filter);
+        let stacks = |stacks: &[ItemStack]| {
+            let stacks = stacks.iter().filter(|stack| matches(&stack.item));
+            stacks.cloned().collect()
+        };
+        let groups = |groups: &[ItemGroup]| {
+            let groups = groups.iter().map(|group| ItemGroup {
+                name: group.name.clone(),
+                items: stacks(&group.items),
+            });
+            groups.filter(|group| !group.items.is_empty()).collect()
+        };
+
+        Report {
+            missing_items: stacks(&self.missing_items),
+            leftover_items: stacks(&self.leftover_items),
+            executed_recipes: self
+                .executed_recipes
+                .iter()
+                .filter(|recipe| {
+                    let mut stacks = recipe.outputs().chain(&recipe.inputs);
+                    stacks.any(|stack| matches(&stack.item))
+                })
+                .cloned()
+                .collect(),
+            crafting_tree: self
+                .crafting_tree
+                .iter()
+                .filter(|node| node.any(&|node| matches(&node.stack.item)))
+                .cloned()
+                .collect(),
+            dropped_needs: stacks(&self.dropped_needs),
+            trace: self.trace.clone(),
+            missing_by_source: groups(&self.missing_by_source),
+            missing_by_category: groups(&self.missing_by_category),
+            contributions: self
+                .contributions
+                .iter()
+                .map(|contribution| Contribution {
+                    player: contribution.player.clone(),
+                    consumed: stacks(&contribution.consumed),
+                })
+                .collect(),
+            dead_end_byproducts: self
+                .dead_end_byproducts
+                .iter()
+                .filter(|byproduct| matches(&byproduct.stack.item) || matches(&byproduct.recipe))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Render the report as a Markdown checklist: first the items to gather,
+    /// then the recipes to craft in order.
+    pub fn to_markdown_checklist(&self) -> String {
+        let mut markdown = String::new();
+
+        if !self.missing_items.is_empty() {
+            markdown.push_str("## Gather\n\n");
+            for (i, group) in self.missing_by_source.iter().enumerate() {
+                if i > 0 {
+                    markdown.push('\n');
+                }
+                match &group.name {
+                    Some(source) => writeln!(markdown, "### {source}\n").unwrap(),
+                    None if i > 0 => markdown.push_str("### Elsewhere\n\n"),
+                    None => {}
+                }
+                for stack in &group.items {
+                    writeln!(markdown, "- [ ] {stack}").unwrap();
+                }
+            }
+        }
+
+        if !self.executed_recipes.is_empty() {
+            if !markdown.is_empty() {
+                markdown.push('\n');
+            }
+            markdown.push_str("## Craft\n\n");
+            for recipe in &self.executed_recipes {
+                writeln!(markdown, "- [ ] {recipe}").unwrap();
+            }
+        }
+
+        markdown
+    }
+
+    /// Render the report as a self-contained HTML page.
+    ///
+    /// The crafting tree can be collapsed without any scripts, so the page can
+    /// be opened anywhere.
+    pub fn to_html(&self) -> String {
+        let mut html = String::from(HTML_HEADER);
+
+        html.push_str("<h2>Missing items</h2>\n");
+        if self.missing_items.is_empty() {
+            html.push_str("<p>You have all the required items!</p>\n");
+        } else {
+            html_list(&mut html, "ul", "missing", &self.missing_items);
+        }
+
+        html.push_str("<h2>Leftover items</h2>\n");
+        if self.leftover_items.is_empty() {
+            html.push_str("<p>No items are left over after crafting.</p>\n");
+        } else {
+            html_list(&mut html, "ul", "leftover", &self.leftover_items);
+        }
+
+        html.push_str("<h2>Steps</h2>\n");
+        if self.executed_recipes.is_empty() {
+            html.push_str("<p>No crafting needed.</p>\n");
+        } else {
+            html_list(&mut html, "ol", "step", &self.executed_recipes);
+        }
+
+        html.push_str("<h2>Crafting tree</h2>\n<ul class=\"tree\">\n");
+        for node in &self.crafting_tree {
+            html_tree_node(&mut html, node);
+        }
+        html.push_str("</ul>\n</body>\n</html>\n");
+
+        html
+    }
+
+    /// Render the flow of items between executed recipes as a Graphviz graph.
+    pub fn to_dot(&self) -> String {
+        let quote = |name: &str| format!("\"{}\"", name.replace('"', "\\\""));
+
+        let mut dot = String::from("digraph crafting {\n");
+        for recipe in &self.executed_recipes {
+            for input in &recipe.inputs {
+                writeln!(
+                    dot,
+                    "    {} -> {} [label=\"{}\"];",
+                    quote(&input.item.0),
+                    quote(&recipe.output.item.0),
+                    input.count
+                )
+                .unwrap();
+            }
+            // byproducts come out of the same recipe runs
+            for byproduct in &recipe.byproducts {
+                writeln!(
+                    dot,
+                    "    {} -> {} [label=\"{}\", style=dashed];",
+                    quote(&recipe.output.item.0),
+                    quote(&byproduct.item.0),
+                    byproduct.count
+                )
+                .unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+const HTML_HEADER: &str = "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>Crafting plan</title>
+<style>
+body { font-family: sans-serif; margin: 2rem; }
+.missing, .none { color: #d1242f; }
+.partial { color: #9a6700; }
+.leftover, .full { color: #1a7f37; }
+.details { color: #888; }
+.tree, .tree ul { list-style: none; padding-left: 1.5rem; }
+summary { cursor: pointer; }
+</style>
+</head>
+<body>
+<h1>Crafting plan</h1>
+";
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn html_list(html: &mut String, tag: &str, class: &str, entries: &[impl Display]) {
+    writeln!(html, "<{tag}>").unwrap();
+    for entry in entries {
+        let entry = escape_html(&entry.to_string());
+        writeln!(html, "<li class=\"{class}\">{entry}</li>").unwrap();
+    }
+    writeln!(html, "</{tag}>").unwrap();
+}
+
+fn html_tree_node(html: &mut String, node: &CraftingNode) {
+    // `None` closes the list of the node that was opened before it
+    let mut stack = vec![Some(node)];
+    while let Some(entry) = stack.pop() {
+        let Some(node) = entry else {
+            writeln!(html, "</ul></details></li>").unwrap();
+            continue;
+        };
+        let class = match node.coverage() {
+            Coverage::Full => "full",
+            Coverage::Partial => "partial",
+            Coverage::None => "none",
+        };
+        let label = format!(
+            "<span class=\"{class}\">{}</span> <span class=\"details\">({})</span>",
+            escape_html(&node.stack.to_string()),
+            node.details(NumberFormat::Plain)
+        );
+
+        if node.inputs.is_empty() {
+            writeln!(html, "<li>{label}</li>").unwrap();
+            continue;
+        }
+
+        writeln!(html, "<li><details open><summary>{label}</summary>\n<ul>").unwrap();
+        stack.push(None);
+        stack.extend(node.inputs.iter().rev().map(Some));
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.dropped_needs.is_empty() {
+            writeln!(f, "Dropped needs:")?;
+            for stack in &self.dropped_needs {
+                writeln!(f, "- {stack}")?;
+            }
+            writeln!(f)?;
+        }
+
+        if self.missing_items.is_empty() {
+            writeln!(f, "You have all the required items!")?;
+        } else {
+            writeln!(f, "Missing items:")?;
+            for stack in &self.missing_items {
+                writeln!(f, "- {stack}")?;
+            }
+        }
+        writeln!(f)?;
+
+        if self.leftover_items.is_empty() {
+            writeln!(f, "No items are left over after crafting.")?;
+        } else {
+            writeln!(f, "Leftover items after crafting:")?;
+            for stack in &self.leftover_items {
+                writeln!(f, "- {stack}")?;
+            }
+        }
+        writeln!(f)?;
+
+        for contribution in &self.contributions {
+            writeln!(f, "Used from {}:", contribution.player)?;
+            for stack in &contribution.consumed {
+                writeln!(f, "- {stack}")?;
+            }
+            writeln!(f)?;
+        }
+
+        if !self.dead_end_byproducts.is_empty() {
+            writeln!(f, "Byproducts nothing uses:")?;
+            for byproduct in &self.dead_end_byproducts {
+                writeln!(
+                    f,
+                    "- {} ({} per run of {})",
+                    byproduct.stack, byproduct.per_run, byproduct.recipe
+                )?;
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(f, "Executed recipes:")?;
+        for recipe in &self.executed_recipes {
+            writeln!(f, "- {recipe}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logic::{Coverage, ItemStack, Program};
+
+    use super::CraftingNode;
+
+    #[test]
+    fn test_report_text() {
+        let input = include_str!("../parsing/example_input.txt");
+        let report = Program::parse_from_string(input)
+            .unwrap()
+            .evaluate()
+            .report
+            .unwrap();
+
+        assert_eq!(
+            report.to_string(),
+            "Missing items:\n\
+            - 2 diamond\n\
+            - 1 log\n\
+            \n\
+            Leftover items after crafting:\n\
+            - 3 plank\n\
+            - 2 stick\n\
+            \n\
+            Executed recipes:\n\
+            - 1 log -> 4 plank\n\
+            - 2 plank -> 4 stick\n\
+            - 3 diamond + 2 stick -> 1 diamond pickaxe\n"
+        );
+    }
+
+    #[test]
+    fn test_crafting_operations() {
+        let input = "need:\n- 1 pickaxe\n- 1 shovel\nhave:\n- 1 log\n\
+            recipes:\n- 4 plank = 1 log\n- 4 stick = 2 plank\n\
+            - 1 pickaxe = 2 stick + 3 plank\n- 1 shovel = 2 stick + 1 plank\n";
+        let evaluation = Program::parse_from_string(input).unwrap().evaluate();
+        let report = evaluation.report.unwrap();
+
+        // 2 plank runs, 1 stick run, and 1 run each for the tools
+        assert_eq!(report.crafting_operations(), 5);
+        assert_eq!(
+            report.crafting_operations(),
+            evaluation.statistics.recipe_runs
+        );
+        assert_eq!(report.recipes_used(), 4);
+        assert_eq!(report.recipes_used(), report.executed_recipes.len());
+
+        // the shovel is made from sticks and planks that were left over from the pickaxe
+        let filtered = report.filtered("shovel");
+        assert_eq!(filtered.crafting_operations(), 1);
+        assert_eq!(filtered.recipes_used(), 1);
+    }
+
+    #[test]
+    fn test_contributions() {
+        let input = "need:\n- 1 iron sword\n- 1 gold\nhave:\n- 2 iron\n\
+            have alice:\n- 3 iron\n- 1 gold\n# bob has plenty\nhave bob :\n- 4 iron\n\
+            recipes:\n- 1 iron sword = 6 iron\n";
+        let evaluation = Program::parse_from_string(input)
+            .unwrap()
+            .evaluate_audited();
+        assert_eq!(evaluation.imbalances, Some(vec![]));
+        let report = evaluation.report.unwrap();
+        assert_eq!(report.missing_items, vec![]);
+
+        // the have section is used first, then alice's items and then bob's
+        let contributions: Vec<_> = report
+            .contributions
+            .iter()
+            .map(|c| {
+                let consumed: Vec<_> = c.consumed.iter().map(ToString::to_string).collect();
+                format!("{}: {}", c.player, consumed.join(", "))
+            })
+            .collect();
+        assert_eq!(contributions, ["alice: 3 iron, 1 gold", "bob: 1 iron"]);
+        assert_eq!(report.leftover_items[0].to_string(), "3 iron");
+    }
+
+    #[test]
+    fn test_dead_end_byproducts() {
+        let input = "need:\n- 4 plastic\nhave:\n\
+            recipes:\n\
+            - 2 heavy oil + 3 gas + 1 sulfur = 10 crude oil\n\
+            - 1 plastic = 1 heavy oil + 2 gas\n";
+        let report = Program::parse_from_string(input)
+            .unwrap()
+            .evaluate()
+            .report
+            .unwrap();
+
+        // the gas is used by the plastic recipe, the sulfur by nothing
+        let byproducts: Vec<_> = report
+            .dead_end_byproducts
+            .iter()
+            .map(|b| format!("{} ({} per {})", b.stack, b.per_run, b.recipe))
+            .collect();
+        assert_eq!(byproducts, ["2 sulfur (1 per heavy oil)"]);
+        assert!(report.to_string().contains("Byproducts nothing uses:\n"));
+        assert_eq!(report.filtered("gas").dead_end_byproducts, []);
+    }
+
+    #[test]
+    fn test_goals() {
+        let input = "need:\n- 1 pickaxe\n- 1 shovel\nhave:\n- 1 log\n- 2 iron\n\
+            recipes:\n- 4 plank = 1 log\n- 4 stick = 2 plank\n\
+            - 1 pickaxe = 2 stick + 3 iron\n- 1 shovel = 2 stick + 1 iron\n";
+        let report = Program::parse_from_string(input)
+            .unwrap()
+            .evaluate()
+            .report
+            .unwrap();
+        let names = |stacks: &[ItemStack]| -> Vec<String> {
+            stacks.iter().map(ToString::to_string).collect()
+        };
+
+        let goals = report.goals();
+        assert_eq!(goals.len(), 2);
+        assert_eq!(goals[0].stack.to_string(), "1 pickaxe");
+        assert_eq!(names(&goals[0].from_inventory), ["1 log", "2 iron"]);
+        assert_eq!(names(&goals[0].missing), ["1 iron"]);
+        // the shovel uses the sticks that were left over from the pickaxe
+        assert_eq!(names(&goals[1].from_inventory), ["2 stick"]);
+        assert_eq!(names(&goals[1].missing), ["1 iron"]);
+    }
+
+    #[test]
+    fn test_is_minor() {
+        let input = "options:\n- tree threshold = 3\nneed:\n- 1 pickaxe\n- 1 shovel\n\
+            have:\n- 3 plank\n- 2 iron\nrecipes:\n- 4 stick = 2 plank\n\
+            - 1 pickaxe = 2 stick + 3 iron\n- 1 shovel = 2 stick + 1 plank\n";
+        let evaluation = Program::parse_from_string(input).unwrap().evaluate();
+        let threshold = evaluation.options.tree_threshold;
+        assert_eq!(threshold, 3);
+        let tree = evaluation.report.unwrap().crafting_tree;
+        let is_minor = |node: &CraftingNode, threshold| node.is_minor(threshold, node.coverage());
+
+        // the sticks for the pickaxe are crafted and complete, but the iron is missing
+        let pickaxe = &tree[0];
+        assert!(!is_minor(pickaxe, threshold));
+        assert!(is_minor(&pickaxe.inputs[0], threshold));
+        assert!(!is_minor(&pickaxe.inputs[1], threshold));
+        // these sticks were left over from the pickaxe, so they aren't crafted
+        assert!(!is_minor(&tree[1].inputs[0], threshold));
+        assert!(!is_minor(&pickaxe.inputs[0], 2));
+    }
+
+    #[test]
+    fn test_filtered() {
+        let input = include_str!("../parsing/example_input.txt");
+        let report = Program::parse_from_string(input)
+            .unwrap()
+            .evaluate()
+            .report
+            .unwrap();
+
+        let filtered = report.filtered("STICK");
+        assert_eq!(filtered.missing_items, vec![]);
+        assert_eq!(filtered.leftover_items.len(), 1);
+        assert_eq!(filtered.leftover_items[0].to_string(), "2 stick");
+        let recipes: Vec<_> = filtered
+            .executed_recipes
+            .iter()
+            .map(|recipe| recipe.to_string())
+            .collect();
+        assert_eq!(
+            recipes,
+            [
+                "2 plank -> 4 stick",
+                "3 diamond + 2 stick -> 1 diamond pickaxe"
+            ]
+        );
+        assert_eq!(filtered.crafting_tree, report.crafting_tree);
+
+        let filtered = report.filtered("gold");
+        assert_eq!(filtered.executed_recipes, vec![]);
+        assert_eq!(filtered.crafting_tree, vec![]);
+        assert_eq!(report.filtered(""), report);
+    }
+
+    #[test]
+    fn test_report_text_dropped_needs() {
+        let input = "options:\n- need mode = best effort\nneed:\n- 1 a\nhave:\nrecipes:\n";
+        let report = Program::parse_from_string(input)
+            .unwrap()
+            .evaluate()
+            .report
+            .unwrap();
+
+        assert_eq!(
+            report.to_string(),
+            "Dropped needs:\n\
+            - 1 a\n\
+            \n\
+            You have all the required items!\n\
+            \n\
+            No items are left over after crafting.\n\
+            \n\
+            Executed recipes:\n"
+        );
+    }
+
+    #[test]
+    fn test_report_markdown_checklist() {
+        let input = include_str!("../parsing/example_input.txt");
+        let report = Program::parse_from_string(input)
+            .unwrap()
+            .evaluate()
+            .report
+            .unwrap();
+
+        assert_eq!(
+            report.to_markdown_checklist(),
+            "## Gather\n\n\
+            - [ ] 2 diamond\n\
+            - [ ] 1 log\n\
+            \n\
+            ## Craft\n\n\
+            - [ ] 1 log -> 4 plank\n\
+            - [ ] 2 plank -> 4 stick\n\
+            - [ ] 3 diamond + 2 stick -> 1 diamond pickaxe\n"
+        );
+    }
+
+    #[test]
+    fn test_report_markdown_checklist_sources() {
+        let input = "sources:\n- string @ spiders\n- iron ore @ mines\n- coal @ mines\n\
+            need:\n- 1 lantern\n- 1 bow\nhave:\nrecipes:\n\
+            - 1 lantern = 1 iron ore + 1 coal + 1 glass\n- 1 bow = 3 string + 3 stick\n";
+        let report = Program::parse_from_string(input)
+            .unwrap()
+            .evaluate()
+            .report
+            .unwrap();
+
+        assert_eq!(
+            report.to_markdown_checklist(),
+            "## Gather\n\n\
+            ### spiders\n\n\
+            - [ ] 3 string\n\
+            \n\
+            ### mines\n\n\
+            - [ ] 1 iron ore\n\
+            - [ ] 1 coal\n\
+            \n\
+            ### Elsewhere\n\n\
+            - [ ] 1 glass\n\
+            - [ ] 3 stick\n\
+            \n\
+            ## Craft\n\n\
+            - [ ] 3 string + 3 stick -> 1 bow\n\
+            - [ ] 1 iron ore + 1 coal + 1 glass -> 1 lantern\n"
+        );
+
+        let filtered = report.filtered("s");
+        let sources: Vec<_> = filtered
+            .missing_by_source
+            .iter()
+            .map(|group| group.name.as_deref())
+            .collect();
+        assert_eq!(sources, [Some("spiders"), None]);
+    }
+
+    #[test]
+    fn test_missing_by_category() {
+        let input = "categories:\n- mob drops: string, bone\n- ores: iron ore, coal\n\
+            need:\n- 1 lantern\n- 1 bow\nhave:\nrecipes:\n\
+            - 1 lantern = 1 iron ore + 2 coal + 1 glass\n- 1 bow = 3 string + 3 stick\n";
+        let report = Program::parse_from_string(input)
+            .unwrap()
+            .evaluate()
+            .report
+            .unwrap();
+
+        let groups: Vec<_> = report
+            .missing_by_category
+            .iter()
+            .map(|group| (group.name.as_deref(), group.items.len(), group.total()))
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                (Some("mob drops"), 1, 3),
+                (Some("ores"), 2, 3),
+                (None, 2, 4)
+            ]
+        );
+        // without a sources section, everything is in one group
+        assert_eq!(report.missing_by_source.len(), 1);
+        assert_eq!(report.missing_by_source[0].name, None);
+    }
+
+    #[test]
+    fn test_report_html() {
+        let input = "need:\n- 1 <b>\nhave:\n- 1 a\nrecipes:\n- 1 <b> = 2 a\n";
+        let html = Program::parse_from_string(input)
+            .unwrap()
+            .evaluate()
+            .report
+            .unwrap()
+            .to_html();
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<li class=\"missing\">1 a</li>"));
+        assert!(html.contains("<li class=\"step\">2 a -&gt; 1 &lt;b&gt;</li>"));
+        assert!(html.contains(
+            "<li><details open><summary><span class=\"partial\">1 &lt;b&gt;</span> \
+            <span class=\"details\">(crafted 1 in 1 runs)</span></summary>"
+        ));
+        assert!(html.contains(
+            "<li><span class=\"partial\">2 a</span> \
+            <span class=\"details\">(1 from inventory, 1 missing)</span></li>"
+        ));
+    }
+
+    #[test]
+    fn test_report_dot() {
+        let input = "need:\n- 1 b\nhave:\nrecipes:\n- 1 b = 2 a\n";
+        let report = Program::parse_from_string(input)
+            .unwrap()
+            .evaluate()
+            .report
+            .unwrap();
+
+        assert_eq!(
+            report.to_dot(),
+            "digraph crafting {\n    \"a\" -> \"b\" [label=\"2\"];\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_crafting_node_coverage() {
+        let input =
+            "need:\n- 1 b\n- 2 c\n- 1 d\nhave:\n- 2 a\nrecipes:\n- 1 b = 1 a\n- 1 c = 1 a\n";
+        let report = Program::parse_from_string(input)
+            .unwrap()
+            .evaluate()
+            .report
+            .unwrap();
+
+        let coverage: Vec<_> = report.crafting_tree.iter().map(|n| n.coverage()).collect();
+        assert_eq!(
+            coverage,
+            [Coverage::Full, Coverage::Partial, Coverage::None]
+        );
+    }
+
+    #[test]
+    fn test_deep_crafting_tree() {
+        let depth = 20_000;
+        let recipes: String = (1..=depth)
+            .map(|i| format!("- 1 item {i} = 1 item {}\n", i - 1))
+            .collect();
+        let input = format!("need:\n- 1 item {depth}\nhave:\n- 1 item 0\nrecipes:\n{recipes}");
+        let report = Program::parse_from_string(&input)
+            .unwrap()
+            .evaluate()
+            .report
+            .unwrap();
+
+        assert!(report.missing_items.is_empty());
+        assert_eq!(report.executed_recipes.len(), depth);
+        assert_eq!(report.crafting_operations(), depth as u64);
+        assert_eq!(report.crafting_tree[0].coverage(), Coverage::Full);
+
+        let copy = report.clone();
+        assert_eq!(copy, report);
+        drop(copy);
+    }
+}
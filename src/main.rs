@@ -4,6 +4,11 @@ mod logic;
 use app::App;
 
 fn main() {
-    console_log::init_with_level(log::Level::Debug).unwrap();
-    yew::Renderer::<App>::new().render();
+    app::logging::init();
+    app::service_worker::register();
+    if app::embed::is_embed() {
+        yew::Renderer::<app::embed::Embed>::new().render();
+    } else {
+        yew::Renderer::<App>::new().render();
+    }
 }
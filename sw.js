@@ -0,0 +1,63 @@
+// Lets the calculator work offline by caching every file it loads.
+//
+// Cached files are served right away and refreshed in the background, so a
+// slow connection never blocks the app. Trunk gives the wasm, js and css files a
+// hash in their name, so a new deployment is picked up on the next reload,
+// and the files of older deployments are removed once their new versions are
+// cached.
+//
+// Changing the version removes everything that was cached by older workers.
+const CACHE = "game-recipe-calc-v2";
+
+// The hash trunk adds to file names, such as in `app-1a2b3c_bg.wasm`.
+const HASH = /-[0-9a-f]+(?=(_bg)?\.(js|wasm|css)$)/;
+
+self.addEventListener("install", () => self.skipWaiting());
+self.addEventListener("activate", (event) =>
+  event.waitUntil(
+    caches
+      .keys()
+      .then((names) => Promise.all(names.filter((name) => name !== CACHE).map((name) => caches.delete(name))))
+      .then(() => self.clients.claim())
+  )
+);
+
+// Remove the other versions of a hashed file from the cache.
+async function removeOlderVersions(cache, url) {
+  if (!HASH.test(url)) {
+    return;
+  }
+  const unhashed = url.replace(HASH, "");
+  for (const request of await cache.keys()) {
+    if (request.url !== url && request.url.replace(HASH, "") === unhashed) {
+      await cache.delete(request);
+    }
+  }
+}
+
+self.addEventListener("fetch", (event) => {
+  const request = event.request;
+  if (request.method !== "GET" || new URL(request.url).origin !== self.location.origin) {
+    return;
+  }
+
+  event.respondWith(
+    caches.open(CACHE).then(async (cache) => {
+      const cached = await cache.match(request);
+      const fetched = fetch(request).then((response) => {
+        if (response.ok) {
+          const saved = cache.put(request, response.clone());
+          event.waitUntil(saved.then(() => removeOlderVersions(cache, request.url)));
+        }
+        return response;
+      });
+
+      if (cached) {
+        // keep the worker alive until the cache is updated
+        event.waitUntil(fetched.catch(() => {}));
+        return cached;
+      }
+      return fetched;
+    })
+  );
+});